@@ -95,8 +95,8 @@ fn main() -> io::Result<()> {
     let path = args.nth(1).unwrap();
     let buf = std::fs::read(path)?;
 
-    if list_people(Message::new(&buf)).is_err() {
-        eprintln!("Failed to parse address book.");
+    if let Err(e) = list_people(Message::new(&buf)) {
+        eprintln!("Failed to parse address book: {}.", e);
         exit(1);
     }
     Ok(())
@@ -0,0 +1,522 @@
+use super::{
+    DescriptorError, DescriptorPool, EnumDescriptor, EnumId, EnumValueDescriptor, FieldDescriptor,
+    FieldType, FileDescriptor, Label, MessageDescriptor, MessageId, OneofDescriptor, Symbol,
+    Syntax,
+};
+use crate::message::Message;
+use crate::Error;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+// The types below mirror the messages of `google/protobuf/descriptor.proto`, with type names not
+// yet resolved. Only the parts used by the descriptor model are kept.
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FileProto {
+    pub(crate) name: String,
+    pub(crate) package: String,
+    pub(crate) dependency: Vec<String>,
+    pub(crate) message_type: Vec<MessageProto>,
+    pub(crate) enum_type: Vec<EnumProto>,
+    pub(crate) extension: Vec<FieldProto>,
+    pub(crate) syntax: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MessageProto {
+    pub(crate) name: String,
+    pub(crate) field: Vec<FieldProto>,
+    pub(crate) extension: Vec<FieldProto>,
+    pub(crate) nested_type: Vec<MessageProto>,
+    pub(crate) enum_type: Vec<EnumProto>,
+    pub(crate) extension_range: Vec<Range<u64>>,
+    pub(crate) oneof_decl: Vec<String>,
+    pub(crate) map_entry: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FieldProto {
+    pub(crate) name: String,
+    pub(crate) extendee: Option<String>,
+    pub(crate) number: i32,
+    pub(crate) label: Option<i32>,
+    pub(crate) type_: Option<i32>,
+    pub(crate) type_name: Option<String>,
+    pub(crate) default_value: Option<String>,
+    pub(crate) oneof_index: Option<i32>,
+    pub(crate) json_name: Option<String>,
+    pub(crate) packed: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EnumProto {
+    pub(crate) name: String,
+    pub(crate) value: Vec<(String, i32)>,
+}
+
+pub(super) fn decode_file_descriptor_set(buf: &[u8]) -> Result<Vec<FileProto>, Error> {
+    let mut files = Vec::new();
+    for field in Message::new(buf).fields() {
+        let field = field?;
+        // repeated FileDescriptorProto file = 1;
+        if field.number == 1 {
+            files.push(decode_file(field.value.get_message()?)?);
+        }
+    }
+    Ok(files)
+}
+
+fn decode_file(message: Message<'_>) -> Result<FileProto, Error> {
+    let mut file = FileProto::default();
+    for field in message.fields() {
+        let field = field?;
+        match field.number {
+            1 => file.name = field.value.get_string()?.to_string(),
+            2 => file.package = field.value.get_string()?.to_string(),
+            3 => file.dependency.push(field.value.get_string()?.to_string()),
+            4 => file
+                .message_type
+                .push(decode_message(field.value.get_message()?)?),
+            5 => file
+                .enum_type
+                .push(decode_enum(field.value.get_message()?)?),
+            7 => file
+                .extension
+                .push(decode_field(field.value.get_message()?)?),
+            12 => file.syntax = field.value.get_string()?.to_string(),
+            _ => {}
+        }
+    }
+    Ok(file)
+}
+
+fn decode_message(message: Message<'_>) -> Result<MessageProto, Error> {
+    let mut proto = MessageProto::default();
+    for field in message.fields() {
+        let field = field?;
+        match field.number {
+            1 => proto.name = field.value.get_string()?.to_string(),
+            2 => proto.field.push(decode_field(field.value.get_message()?)?),
+            3 => proto
+                .nested_type
+                .push(decode_message(field.value.get_message()?)?),
+            4 => proto
+                .enum_type
+                .push(decode_enum(field.value.get_message()?)?),
+            5 => {
+                // ExtensionRange: optional int32 start = 1; optional int32 end = 2;
+                let range = field.value.get_message()?;
+                let start = range.get_last(1)?.map_or(Ok(0), |v| v.get_int32())?;
+                let end = range.get_last(2)?.map_or(Ok(0), |v| v.get_int32())?;
+                proto.extension_range.push(start as u64..end as u64);
+            }
+            6 => proto
+                .extension
+                .push(decode_field(field.value.get_message()?)?),
+            7 => {
+                // MessageOptions: optional bool map_entry = 7;
+                if let Some(value) = field.value.get_message()?.get_last(7)? {
+                    proto.map_entry = value.get_bool()?;
+                }
+            }
+            8 => {
+                // OneofDescriptorProto: optional string name = 1;
+                let oneof = field.value.get_message()?;
+                let name = oneof.get_last(1)?.map_or(Ok(""), |v| v.get_string())?;
+                proto.oneof_decl.push(name.to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(proto)
+}
+
+fn decode_field(message: Message<'_>) -> Result<FieldProto, Error> {
+    let mut proto = FieldProto::default();
+    for field in message.fields() {
+        let field = field?;
+        match field.number {
+            1 => proto.name = field.value.get_string()?.to_string(),
+            2 => proto.extendee = Some(field.value.get_string()?.to_string()),
+            3 => proto.number = field.value.get_int32()?,
+            4 => proto.label = Some(field.value.get_enum()?),
+            5 => proto.type_ = Some(field.value.get_enum()?),
+            6 => proto.type_name = Some(field.value.get_string()?.to_string()),
+            7 => proto.default_value = Some(field.value.get_string()?.to_string()),
+            8 => {
+                // FieldOptions: optional bool packed = 2;
+                if let Some(value) = field.value.get_message()?.get_last(2)? {
+                    proto.packed = Some(value.get_bool()?);
+                }
+            }
+            9 => proto.oneof_index = Some(field.value.get_int32()?),
+            10 => proto.json_name = Some(field.value.get_string()?.to_string()),
+            _ => {}
+        }
+    }
+    Ok(proto)
+}
+
+fn decode_enum(message: Message<'_>) -> Result<EnumProto, Error> {
+    let mut proto = EnumProto::default();
+    for field in message.fields() {
+        let field = field?;
+        match field.number {
+            1 => proto.name = field.value.get_string()?.to_string(),
+            2 => {
+                // EnumValueDescriptorProto: optional string name = 1; optional int32 number = 2;
+                let value = field.value.get_message()?;
+                let name = value.get_last(1)?.map_or(Ok(""), |v| v.get_string())?;
+                let number = value.get_last(2)?.map_or(Ok(0), |v| v.get_int32())?;
+                proto.value.push((name.to_string(), number));
+            }
+            _ => {}
+        }
+    }
+    Ok(proto)
+}
+
+// Returns the default JSON name of a field: the name in lowerCamelCase.
+pub(crate) fn to_json_name(name: &str) -> String {
+    let mut json_name = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            json_name.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+fn join(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        [scope, ".", name].concat()
+    }
+}
+
+struct Builder<'p> {
+    pool: DescriptorPool,
+    // Messages and extensions whose fields are resolved once all names are known.
+    messages: Vec<(MessageId, &'p MessageProto)>,
+    extensions: Vec<(&'p FieldProto, String, usize)>,
+}
+
+impl<'p> Builder<'p> {
+    fn define(&mut self, full_name: String, symbol: Symbol) -> Result<(), DescriptorError> {
+        match self.pool.names.get(&full_name) {
+            Some(Symbol::Package) if matches!(symbol, Symbol::Package) => Ok(()),
+            Some(_) => Err(DescriptorError::DuplicateName(full_name)),
+            None => {
+                self.pool.names.insert(full_name, symbol);
+                Ok(())
+            }
+        }
+    }
+
+    fn add_file(&mut self, proto: &'p FileProto) -> Result<(), DescriptorError> {
+        let index = self.pool.files.len();
+        let syntax = match proto.syntax.as_str() {
+            "proto3" => Syntax::Proto3,
+            "editions" => Syntax::Editions,
+            _ => Syntax::Proto2,
+        };
+        let mut package = String::new();
+        for part in proto.package.split('.').filter(|part| !part.is_empty()) {
+            package = join(&package, part);
+            self.define(package.clone(), Symbol::Package)?;
+        }
+        self.pool.files.push(FileDescriptor {
+            name: proto.name.clone(),
+            package: proto.package.clone(),
+            dependencies: proto.dependency.clone(),
+            syntax,
+            messages: Vec::new(),
+            enums: Vec::new(),
+            extensions: Vec::new(),
+        });
+        for message in &proto.message_type {
+            let id = self.add_message(message, index, &proto.package, None)?;
+            self.pool.files[index].messages.push(id);
+        }
+        for enum_type in &proto.enum_type {
+            let id = self.add_enum(enum_type, index, &proto.package, None)?;
+            self.pool.files[index].enums.push(id);
+        }
+        for extension in &proto.extension {
+            let extension = self.add_extension(extension, &proto.package, index)?;
+            self.pool.files[index].extensions.push(extension);
+        }
+        Ok(())
+    }
+
+    fn add_message(
+        &mut self,
+        proto: &'p MessageProto,
+        file: usize,
+        scope: &str,
+        parent: Option<MessageId>,
+    ) -> Result<MessageId, DescriptorError> {
+        let id = MessageId(self.pool.messages.len());
+        let full_name = join(scope, &proto.name);
+        self.define(full_name.clone(), Symbol::Message(id))?;
+        self.pool.messages.push(MessageDescriptor {
+            id,
+            name: proto.name.clone(),
+            full_name: full_name.clone(),
+            file,
+            parent,
+            fields: Vec::new(),
+            oneofs: Vec::new(),
+            messages: Vec::new(),
+            enums: Vec::new(),
+            extension_ranges: proto.extension_range.clone(),
+            map_entry: proto.map_entry,
+        });
+        self.messages.push((id, proto));
+        for message in &proto.nested_type {
+            let nested = self.add_message(message, file, &full_name, Some(id))?;
+            self.pool.messages[id.0].messages.push(nested);
+        }
+        for enum_type in &proto.enum_type {
+            let nested = self.add_enum(enum_type, file, &full_name, Some(id))?;
+            self.pool.messages[id.0].enums.push(nested);
+        }
+        for extension in &proto.extension {
+            self.add_extension(extension, &full_name, file)?;
+        }
+        Ok(id)
+    }
+
+    fn add_enum(
+        &mut self,
+        proto: &EnumProto,
+        file: usize,
+        scope: &str,
+        parent: Option<MessageId>,
+    ) -> Result<EnumId, DescriptorError> {
+        let id = EnumId(self.pool.enums.len());
+        let full_name = join(scope, &proto.name);
+        self.define(full_name.clone(), Symbol::Enum(id))?;
+        let values = proto
+            .value
+            .iter()
+            .map(|(name, number)| EnumValueDescriptor {
+                name: name.clone(),
+                number: *number,
+            })
+            .collect();
+        self.pool.enums.push(EnumDescriptor {
+            id,
+            name: proto.name.clone(),
+            full_name,
+            file,
+            parent,
+            values,
+        });
+        Ok(id)
+    }
+
+    fn add_extension(
+        &mut self,
+        proto: &'p FieldProto,
+        scope: &str,
+        file: usize,
+    ) -> Result<usize, DescriptorError> {
+        let index = self.extensions.len();
+        self.define(join(scope, &proto.name), Symbol::Extension(index))?;
+        self.extensions.push((proto, scope.to_string(), file));
+        Ok(index)
+    }
+
+    // Resolves a type name relative to `scope` following the protobuf scoping rules: the name is
+    // looked up in the innermost scope first, then in each enclosing scope.
+    fn resolve(&self, name: &str, scope: &str) -> Option<Symbol> {
+        if let Some(full_name) = name.strip_prefix('.') {
+            return self.pool.names.get(full_name).copied();
+        }
+        let mut scope = scope;
+        loop {
+            match self.pool.names.get(join(scope, name).as_str()) {
+                Some(Symbol::Package) | None => {}
+                Some(&symbol) => return Some(symbol),
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rsplit_once('.').map_or("", |(parent, _)| parent);
+        }
+    }
+
+    fn resolve_message(&self, name: &str, scope: &str) -> Result<MessageId, DescriptorError> {
+        match self.resolve(name, scope) {
+            Some(Symbol::Message(id)) => Ok(id),
+            _ => Err(DescriptorError::UnresolvedType(name.to_string())),
+        }
+    }
+
+    fn field(
+        &self,
+        proto: &FieldProto,
+        scope: &str,
+        syntax: Syntax,
+        oneofs: usize,
+    ) -> Result<FieldDescriptor, DescriptorError> {
+        let full_name = join(scope, &proto.name);
+        let invalid = || DescriptorError::InvalidField(full_name.clone());
+        if proto.number <= 0 {
+            return Err(invalid());
+        }
+        let label = match proto.label {
+            None | Some(1) => Label::Optional,
+            Some(2) => Label::Required,
+            Some(3) => Label::Repeated,
+            _ => return Err(invalid()),
+        };
+        let field_type = match proto.type_ {
+            Some(1) => FieldType::Double,
+            Some(2) => FieldType::Float,
+            Some(3) => FieldType::Int64,
+            Some(4) => FieldType::Uint64,
+            Some(5) => FieldType::Int32,
+            Some(6) => FieldType::Fixed64,
+            Some(7) => FieldType::Fixed32,
+            Some(8) => FieldType::Bool,
+            Some(9) => FieldType::String,
+            Some(12) => FieldType::Bytes,
+            Some(13) => FieldType::Uint32,
+            Some(15) => FieldType::Sfixed32,
+            Some(16) => FieldType::Sfixed64,
+            Some(17) => FieldType::Sint32,
+            Some(18) => FieldType::Sint64,
+            Some(10 | 11 | 14) | None => {
+                let name = proto.type_name.as_deref().ok_or_else(invalid)?;
+                let unresolved = || DescriptorError::UnresolvedType(name.to_string());
+                match (
+                    proto.type_,
+                    self.resolve(name, scope).ok_or_else(unresolved)?,
+                ) {
+                    (Some(10), Symbol::Message(id)) => FieldType::Group(id),
+                    (Some(11) | None, Symbol::Message(id)) => FieldType::Message(id),
+                    (Some(14) | None, Symbol::Enum(id)) => FieldType::Enum(id),
+                    _ => return Err(unresolved()),
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        let oneof = match proto.oneof_index {
+            Some(index) if index < 0 || index as usize >= oneofs => return Err(invalid()),
+            index => index.map(|index| index as usize),
+        };
+        let extendee = match &proto.extendee {
+            Some(name) => Some(self.resolve_message(name, scope)?),
+            None => None,
+        };
+        let repeated = label == Label::Repeated;
+        let packed = repeated
+            && field_type.is_packable()
+            && proto.packed.unwrap_or(syntax != Syntax::Proto2);
+        let map = repeated
+            && matches!(field_type, FieldType::Message(id) if self.pool.messages[id.0].map_entry);
+        Ok(FieldDescriptor {
+            name: proto.name.clone(),
+            json_name: proto
+                .json_name
+                .clone()
+                .unwrap_or_else(|| to_json_name(&proto.name)),
+            full_name,
+            number: proto.number as u64,
+            label,
+            field_type,
+            oneof,
+            packed,
+            map,
+            extendee,
+            default_value: proto.default_value.clone(),
+        })
+    }
+
+    fn resolve_fields(&mut self) -> Result<(), DescriptorError> {
+        for &(id, proto) in &self.messages {
+            let message = &self.pool.messages[id.0];
+            let syntax = self.pool.files[message.file].syntax;
+            let fields = proto
+                .field
+                .iter()
+                .map(|field| self.field(field, &message.full_name, syntax, proto.oneof_decl.len()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let oneofs = proto
+                .oneof_decl
+                .iter()
+                .enumerate()
+                .map(|(index, name)| OneofDescriptor {
+                    name: name.clone(),
+                    fields: (0..fields.len())
+                        .filter(|&i| fields[i].oneof == Some(index))
+                        .collect(),
+                })
+                .collect();
+            let message = &mut self.pool.messages[id.0];
+            message.fields = fields;
+            message.oneofs = oneofs;
+        }
+        // Map entries must have a singular key of an integral, bool or string type, and a value.
+        for message in &self.pool.messages {
+            for field in message.fields.iter().filter(|field| field.map) {
+                let FieldType::Message(entry) = field.field_type else {
+                    unreachable!()
+                };
+                let entry = &self.pool.messages[entry.0];
+                let valid = match (entry.field(1), entry.field(2)) {
+                    (Some(key), Some(value)) => {
+                        !key.is_repeated()
+                            && !value.is_repeated()
+                            && !matches!(
+                                key.field_type,
+                                FieldType::Double
+                                    | FieldType::Float
+                                    | FieldType::Bytes
+                                    | FieldType::Enum(_)
+                                    | FieldType::Message(_)
+                                    | FieldType::Group(_)
+                            )
+                    }
+                    _ => false,
+                };
+                if !valid {
+                    return Err(DescriptorError::InvalidField(field.full_name.clone()));
+                }
+            }
+        }
+        for (proto, scope, file) in &self.extensions {
+            let syntax = self.pool.files[*file].syntax;
+            let extension = self.field(proto, scope, syntax, 0)?;
+            if extension.extendee.is_none() {
+                return Err(DescriptorError::InvalidField(extension.full_name));
+            }
+            self.pool.extensions.push(extension);
+        }
+        Ok(())
+    }
+}
+
+// Builds a pool from files in dependency order. Names are resolved across all files.
+pub(crate) fn build(files: Vec<FileProto>) -> Result<DescriptorPool, DescriptorError> {
+    let mut builder = Builder {
+        pool: DescriptorPool::default(),
+        messages: Vec::new(),
+        extensions: Vec::new(),
+    };
+    for file in &files {
+        builder.add_file(file)?;
+    }
+    builder.resolve_fields()?;
+    Ok(builder.pool)
+}
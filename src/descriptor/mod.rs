@@ -0,0 +1,1090 @@
+mod build;
+mod parse;
+
+pub(crate) use build::to_json_name;
+
+use crate::text::ParseError;
+use crate::Error;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// Identifies a message type in a [`DescriptorPool`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MessageId(pub(crate) usize);
+
+/// Identifies an enum type in a [`DescriptorPool`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct EnumId(pub(crate) usize);
+
+/// A set of protobuf file descriptors.
+///
+/// A pool is decoded from a serialized `FileDescriptorSet`, as written by
+/// `protoc --descriptor_set_out`. Type references between messages, enums and fields are resolved
+/// when the pool is built.
+#[derive(Clone, Debug, Default)]
+pub struct DescriptorPool {
+    pub(crate) files: Vec<FileDescriptor>,
+    pub(crate) messages: Vec<MessageDescriptor>,
+    pub(crate) enums: Vec<EnumDescriptor>,
+    pub(crate) extensions: Vec<FieldDescriptor>,
+    pub(crate) names: BTreeMap<String, Symbol>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Symbol {
+    Package,
+    Message(MessageId),
+    Enum(EnumId),
+    Extension(usize),
+}
+
+impl DescriptorPool {
+    /// Decodes a serialized `FileDescriptorSet` and resolves the types it defines.
+    ///
+    /// Every type referenced by a file must be defined in the set.
+    pub fn decode(buf: &[u8]) -> Result<Self, DescriptorError> {
+        let files = build::decode_file_descriptor_set(buf)?;
+        build::build(files)
+    }
+
+    /// Parses `.proto` files and resolves the types they define.
+    ///
+    /// `files` holds the name and source of each file, such as
+    /// `("addressbook.proto", source)`. Messages, enums, nested types, oneofs, maps, groups and
+    /// extensions are parsed; services are skipped, and options other than `packed`, `default`
+    /// and `json_name` on fields are ignored. Imports are not read: every type referenced by a
+    /// file must be defined in `files`.
+    pub fn parse(files: &[(&str, &str)]) -> Result<Self, DescriptorError> {
+        let files = files
+            .iter()
+            .map(|&(name, source)| {
+                parse::parse_file(name, source)
+                    .map_err(|error| DescriptorError::Parse(name.into(), error))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        build::build(files)
+    }
+
+    /// Returns the files of the pool, in the order they were added.
+    #[inline]
+    pub fn files(&self) -> &[FileDescriptor] {
+        &self.files
+    }
+
+    /// Returns the message type with the given id.
+    #[inline]
+    pub fn message(&self, id: MessageId) -> &MessageDescriptor {
+        &self.messages[id.0]
+    }
+
+    /// Returns the enum type with the given id.
+    #[inline]
+    pub fn enum_type(&self, id: EnumId) -> &EnumDescriptor {
+        &self.enums[id.0]
+    }
+
+    /// Returns an iterator over all message types of the pool, including nested types.
+    #[inline]
+    pub fn messages(&self) -> impl Iterator<Item = &MessageDescriptor> + '_ {
+        self.messages.iter()
+    }
+
+    /// Returns an iterator over all enum types of the pool, including nested types.
+    #[inline]
+    pub fn enum_types(&self) -> impl Iterator<Item = &EnumDescriptor> + '_ {
+        self.enums.iter()
+    }
+
+    /// Returns an iterator over all extensions of the pool.
+    #[inline]
+    pub fn extensions(&self) -> impl Iterator<Item = &FieldDescriptor> + '_ {
+        self.extensions.iter()
+    }
+
+    /// Returns the message type with the given fully-qualified name, such as
+    /// `"tutorial.Person"`.
+    pub fn find_message(&self, full_name: &str) -> Option<&MessageDescriptor> {
+        match self
+            .names
+            .get(full_name.strip_prefix('.').unwrap_or(full_name))
+        {
+            Some(&Symbol::Message(id)) => Some(self.message(id)),
+            _ => None,
+        }
+    }
+
+    /// Returns the enum type with the given fully-qualified name.
+    pub fn find_enum(&self, full_name: &str) -> Option<&EnumDescriptor> {
+        match self
+            .names
+            .get(full_name.strip_prefix('.').unwrap_or(full_name))
+        {
+            Some(&Symbol::Enum(id)) => Some(self.enum_type(id)),
+            _ => None,
+        }
+    }
+
+    /// Returns the extension with the given fully-qualified name.
+    pub fn find_extension(&self, full_name: &str) -> Option<&FieldDescriptor> {
+        match self
+            .names
+            .get(full_name.strip_prefix('.').unwrap_or(full_name))
+        {
+            Some(&Symbol::Extension(index)) => Some(&self.extensions[index]),
+            _ => None,
+        }
+    }
+
+    /// Returns the extension of the message type `extendee` with the given field number.
+    pub fn find_extension_by_number(
+        &self,
+        extendee: MessageId,
+        number: u64,
+    ) -> Option<&FieldDescriptor> {
+        self.extensions
+            .iter()
+            .find(|ext| ext.extendee == Some(extendee) && ext.number == number)
+    }
+}
+
+/// The syntax of a protobuf file.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Syntax {
+    /// `syntax = "proto2"`, the default.
+    Proto2,
+    /// `syntax = "proto3"`.
+    Proto3,
+    /// A file using editions.
+    Editions,
+}
+
+/// A protobuf file.
+#[derive(Clone, Debug)]
+pub struct FileDescriptor {
+    pub(crate) name: String,
+    pub(crate) package: String,
+    pub(crate) dependencies: Vec<String>,
+    pub(crate) syntax: Syntax,
+    pub(crate) messages: Vec<MessageId>,
+    pub(crate) enums: Vec<EnumId>,
+    pub(crate) extensions: Vec<usize>,
+}
+
+impl FileDescriptor {
+    /// Returns the name of the file, such as `"addressbook.proto"`.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the package of the file, or an empty string if it has none.
+    #[inline]
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    /// Returns the names of the files imported by the file.
+    #[inline]
+    pub fn dependencies(&self) -> &[String] {
+        &self.dependencies
+    }
+
+    /// Returns the syntax of the file.
+    #[inline]
+    pub fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+
+    /// Returns the top-level message types of the file.
+    #[inline]
+    pub fn messages(&self) -> &[MessageId] {
+        &self.messages
+    }
+
+    /// Returns the top-level enum types of the file.
+    #[inline]
+    pub fn enum_types(&self) -> &[EnumId] {
+        &self.enums
+    }
+
+    /// Returns the indexes of the top-level extensions of the file in
+    /// [`DescriptorPool::extensions`].
+    #[inline]
+    pub fn extensions(&self) -> &[usize] {
+        &self.extensions
+    }
+}
+
+/// A message type.
+#[derive(Clone, Debug)]
+pub struct MessageDescriptor {
+    pub(crate) id: MessageId,
+    pub(crate) name: String,
+    pub(crate) full_name: String,
+    pub(crate) file: usize,
+    pub(crate) parent: Option<MessageId>,
+    pub(crate) fields: Vec<FieldDescriptor>,
+    pub(crate) oneofs: Vec<OneofDescriptor>,
+    pub(crate) messages: Vec<MessageId>,
+    pub(crate) enums: Vec<EnumId>,
+    pub(crate) extension_ranges: Vec<Range<u64>>,
+    pub(crate) map_entry: bool,
+}
+
+impl MessageDescriptor {
+    /// Returns the id of the message type.
+    #[inline]
+    pub fn id(&self) -> MessageId {
+        self.id
+    }
+
+    /// Returns the name of the message type, such as `"PhoneNumber"`.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the fully-qualified name of the message type, such as
+    /// `"tutorial.Person.PhoneNumber"`.
+    #[inline]
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// Returns the index of the file that defines the message type in
+    /// [`DescriptorPool::files`].
+    #[inline]
+    pub fn file(&self) -> usize {
+        self.file
+    }
+
+    /// Returns the message type this type is nested in, or `None` if it is a top-level type.
+    #[inline]
+    pub fn parent(&self) -> Option<MessageId> {
+        self.parent
+    }
+
+    /// Returns the fields of the message type, in declaration order.
+    #[inline]
+    pub fn fields(&self) -> &[FieldDescriptor] {
+        &self.fields
+    }
+
+    /// Returns the field with the given number.
+    pub fn field(&self, number: u64) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|field| field.number == number)
+    }
+
+    /// Returns the field with the given name.
+    pub fn field_by_name(&self, name: &str) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Returns the field with the given JSON name.
+    pub fn field_by_json_name(&self, json_name: &str) -> Option<&FieldDescriptor> {
+        self.fields
+            .iter()
+            .find(|field| field.json_name == json_name)
+    }
+
+    /// Returns the oneofs of the message type.
+    #[inline]
+    pub fn oneofs(&self) -> &[OneofDescriptor] {
+        &self.oneofs
+    }
+
+    /// Returns the message types nested in this type.
+    #[inline]
+    pub fn messages(&self) -> &[MessageId] {
+        &self.messages
+    }
+
+    /// Returns the enum types nested in this type.
+    #[inline]
+    pub fn enum_types(&self) -> &[EnumId] {
+        &self.enums
+    }
+
+    /// Returns the ranges of field numbers reserved for extensions.
+    #[inline]
+    pub fn extension_ranges(&self) -> &[Range<u64>] {
+        &self.extension_ranges
+    }
+
+    /// Returns `true` if the message type is the entry type of a map field.
+    ///
+    /// A map entry has a key field with number 1 and a value field with number 2.
+    #[inline]
+    pub fn is_map_entry(&self) -> bool {
+        self.map_entry
+    }
+}
+
+/// The label of a field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Label {
+    /// An optional or singular field.
+    Optional,
+    /// A required field.
+    Required,
+    /// A repeated field.
+    Repeated,
+}
+
+/// The type of a field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FieldType {
+    /// `double`.
+    Double,
+    /// `float`.
+    Float,
+    /// `int64`.
+    Int64,
+    /// `uint64`.
+    Uint64,
+    /// `int32`.
+    Int32,
+    /// `fixed64`.
+    Fixed64,
+    /// `fixed32`.
+    Fixed32,
+    /// `bool`.
+    Bool,
+    /// `string`.
+    String,
+    /// A group of the given message type.
+    Group(MessageId),
+    /// A message type.
+    Message(MessageId),
+    /// `bytes`.
+    Bytes,
+    /// `uint32`.
+    Uint32,
+    /// An enum type.
+    Enum(EnumId),
+    /// `sfixed32`.
+    Sfixed32,
+    /// `sfixed64`.
+    Sfixed64,
+    /// `sint32`.
+    Sint32,
+    /// `sint64`.
+    Sint64,
+}
+
+impl FieldType {
+    /// Returns `true` if values of this type can be packed.
+    #[inline]
+    pub fn is_packable(&self) -> bool {
+        !matches!(
+            self,
+            FieldType::String | FieldType::Bytes | FieldType::Message(_) | FieldType::Group(_)
+        )
+    }
+
+    /// Returns the wire type of values of this type.
+    pub fn wire_type(&self) -> u8 {
+        match self {
+            FieldType::Int64
+            | FieldType::Uint64
+            | FieldType::Int32
+            | FieldType::Bool
+            | FieldType::Uint32
+            | FieldType::Enum(_)
+            | FieldType::Sint32
+            | FieldType::Sint64 => 0,
+            FieldType::Double | FieldType::Fixed64 | FieldType::Sfixed64 => 1,
+            FieldType::String | FieldType::Message(_) | FieldType::Bytes => 2,
+            FieldType::Group(_) => 3,
+            FieldType::Float | FieldType::Fixed32 | FieldType::Sfixed32 => 5,
+        }
+    }
+}
+
+/// A field or an extension.
+#[derive(Clone, Debug)]
+pub struct FieldDescriptor {
+    pub(crate) name: String,
+    pub(crate) full_name: String,
+    pub(crate) json_name: String,
+    pub(crate) number: u64,
+    pub(crate) label: Label,
+    pub(crate) field_type: FieldType,
+    pub(crate) oneof: Option<usize>,
+    pub(crate) packed: bool,
+    pub(crate) map: bool,
+    pub(crate) extendee: Option<MessageId>,
+    pub(crate) default_value: Option<String>,
+}
+
+impl FieldDescriptor {
+    /// Returns the name of the field.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the fully-qualified name of the field.
+    #[inline]
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// Returns the JSON name of the field, by default the name in lowerCamelCase.
+    #[inline]
+    pub fn json_name(&self) -> &str {
+        &self.json_name
+    }
+
+    /// Returns the field number.
+    #[inline]
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// Returns the label of the field.
+    #[inline]
+    pub fn label(&self) -> Label {
+        self.label
+    }
+
+    /// Returns `true` if the field is repeated. Map fields are repeated.
+    #[inline]
+    pub fn is_repeated(&self) -> bool {
+        self.label == Label::Repeated
+    }
+
+    /// Returns the type of the field.
+    #[inline]
+    pub fn field_type(&self) -> FieldType {
+        self.field_type
+    }
+
+    /// Returns the index of the oneof the field is a member of in
+    /// [`MessageDescriptor::oneofs`].
+    #[inline]
+    pub fn oneof(&self) -> Option<usize> {
+        self.oneof
+    }
+
+    /// Returns `true` if the field is encoded packed.
+    ///
+    /// Repeated fields of packable types are packed by default in proto3 and editions, and
+    /// unpacked by default in proto2. The `packed` option overrides the default. Decoders accept
+    /// both encodings either way.
+    #[inline]
+    pub fn is_packed(&self) -> bool {
+        self.packed
+    }
+
+    /// Returns `true` if the field is a map field.
+    ///
+    /// The type of a map field is a [map entry][MessageDescriptor::is_map_entry] message type.
+    #[inline]
+    pub fn is_map(&self) -> bool {
+        self.map
+    }
+
+    /// Returns the message type extended by the field, or `None` if the field is not an
+    /// extension.
+    #[inline]
+    pub fn extendee(&self) -> Option<MessageId> {
+        self.extendee
+    }
+
+    /// Returns the default value of the field as written in the schema, if any.
+    #[inline]
+    pub fn default_value(&self) -> Option<&str> {
+        self.default_value.as_deref()
+    }
+}
+
+/// A oneof.
+#[derive(Clone, Debug)]
+pub struct OneofDescriptor {
+    pub(crate) name: String,
+    pub(crate) fields: Vec<usize>,
+}
+
+impl OneofDescriptor {
+    /// Returns the name of the oneof.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the indexes of the member fields in [`MessageDescriptor::fields`].
+    #[inline]
+    pub fn fields(&self) -> &[usize] {
+        &self.fields
+    }
+}
+
+/// An enum type.
+#[derive(Clone, Debug)]
+pub struct EnumDescriptor {
+    pub(crate) id: EnumId,
+    pub(crate) name: String,
+    pub(crate) full_name: String,
+    pub(crate) file: usize,
+    pub(crate) parent: Option<MessageId>,
+    pub(crate) values: Vec<EnumValueDescriptor>,
+}
+
+impl EnumDescriptor {
+    /// Returns the id of the enum type.
+    #[inline]
+    pub fn id(&self) -> EnumId {
+        self.id
+    }
+
+    /// Returns the name of the enum type, such as `"PhoneType"`.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the fully-qualified name of the enum type, such as
+    /// `"tutorial.Person.PhoneType"`.
+    #[inline]
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// Returns the index of the file that defines the enum type in [`DescriptorPool::files`].
+    #[inline]
+    pub fn file(&self) -> usize {
+        self.file
+    }
+
+    /// Returns the message type this type is nested in, or `None` if it is a top-level type.
+    #[inline]
+    pub fn parent(&self) -> Option<MessageId> {
+        self.parent
+    }
+
+    /// Returns the values of the enum type, in declaration order.
+    #[inline]
+    pub fn values(&self) -> &[EnumValueDescriptor] {
+        &self.values
+    }
+
+    /// Returns the first value with the given number.
+    pub fn value(&self, number: i32) -> Option<&EnumValueDescriptor> {
+        self.values.iter().find(|value| value.number == number)
+    }
+
+    /// Returns the value with the given name.
+    pub fn value_by_name(&self, name: &str) -> Option<&EnumValueDescriptor> {
+        self.values.iter().find(|value| value.name == name)
+    }
+}
+
+/// A value of an enum type.
+#[derive(Clone, Debug)]
+pub struct EnumValueDescriptor {
+    pub(crate) name: String,
+    pub(crate) number: i32,
+}
+
+impl EnumValueDescriptor {
+    /// Returns the name of the value.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of the value.
+    #[inline]
+    pub fn number(&self) -> i32 {
+        self.number
+    }
+}
+
+/// The error type returned when building a [`DescriptorPool`] fails.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DescriptorError {
+    /// The descriptor could not be decoded.
+    Decode(Error),
+    /// A name is defined more than once.
+    DuplicateName(String),
+    /// A type name could not be resolved. Holds the name as written.
+    UnresolvedType(String),
+    /// A field has an invalid number, label or type. Holds the full name of the field.
+    InvalidField(String),
+    /// A `.proto` file could not be parsed. Holds the name of the file and the error.
+    Parse(String, ParseError),
+}
+
+impl From<Error> for DescriptorError {
+    #[inline]
+    fn from(error: Error) -> Self {
+        DescriptorError::Decode(error)
+    }
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptorError::Decode(e) => write!(f, "invalid descriptor: {}", e),
+            DescriptorError::DuplicateName(name) => write!(f, "duplicate name `{}`", name),
+            DescriptorError::UnresolvedType(name) => write!(f, "unresolved type `{}`", name),
+            DescriptorError::InvalidField(name) => write!(f, "invalid field `{}`", name),
+            DescriptorError::Parse(name, e) => write!(f, "invalid `{}`: {}", name, e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DescriptorError {}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::build::{EnumProto, FieldProto, FileProto, MessageProto};
+    use super::{DescriptorError, DescriptorPool, FieldType, Label, Syntax};
+    use crate::writer::MessageWriter;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use alloc::{format, vec};
+
+    fn encode_field(field: &FieldProto) -> Vec<u8> {
+        let mut w = MessageWriter::new();
+        w.add_string(1, &field.name);
+        if let Some(extendee) = &field.extendee {
+            w.add_string(2, extendee);
+        }
+        w.add_int32(3, field.number);
+        w.add_enum(4, field.label.unwrap_or(1));
+        if let Some(type_) = field.type_ {
+            w.add_enum(5, type_);
+        }
+        if let Some(type_name) = &field.type_name {
+            w.add_string(6, type_name);
+        }
+        if let Some(packed) = field.packed {
+            let mut options = w.open_message(8);
+            options.add_bool(2, packed);
+            options.close();
+        }
+        if let Some(oneof_index) = field.oneof_index {
+            w.add_int32(9, oneof_index);
+        }
+        w.into_vec()
+    }
+
+    fn encode_enum(proto: &EnumProto) -> Vec<u8> {
+        let mut w = MessageWriter::new();
+        w.add_string(1, &proto.name);
+        for (name, number) in &proto.value {
+            let mut value = w.open_message(2);
+            value.add_string(1, name);
+            value.add_int32(2, *number);
+            value.close();
+        }
+        w.into_vec()
+    }
+
+    fn encode_message(proto: &MessageProto) -> Vec<u8> {
+        let mut w = MessageWriter::new();
+        w.add_string(1, &proto.name);
+        for field in &proto.field {
+            w.add_bytes(2, &encode_field(field));
+        }
+        for message in &proto.nested_type {
+            w.add_bytes(3, &encode_message(message));
+        }
+        for enum_type in &proto.enum_type {
+            w.add_bytes(4, &encode_enum(enum_type));
+        }
+        for range in &proto.extension_range {
+            let mut r = w.open_message(5);
+            r.add_int32(1, range.start as i32);
+            r.add_int32(2, range.end as i32);
+            r.close();
+        }
+        for extension in &proto.extension {
+            w.add_bytes(6, &encode_field(extension));
+        }
+        if proto.map_entry {
+            let mut options = w.open_message(7);
+            options.add_bool(7, true);
+            options.close();
+        }
+        for name in &proto.oneof_decl {
+            let mut oneof = w.open_message(8);
+            oneof.add_string(1, name);
+            oneof.close();
+        }
+        w.into_vec()
+    }
+
+    // Encodes files as a `FileDescriptorSet`, as `protoc --descriptor_set_out` does.
+    pub(crate) fn encode_files(files: &[FileProto]) -> Vec<u8> {
+        let mut w = MessageWriter::new();
+        for file in files {
+            let mut f = w.open_message(1);
+            f.add_string(1, &file.name);
+            f.add_string(2, &file.package);
+            for dependency in &file.dependency {
+                f.add_string(3, dependency);
+            }
+            for message in &file.message_type {
+                f.add_bytes(4, &encode_message(message));
+            }
+            for enum_type in &file.enum_type {
+                f.add_bytes(5, &encode_enum(enum_type));
+            }
+            for extension in &file.extension {
+                f.add_bytes(7, &encode_field(extension));
+            }
+            f.add_string(12, &file.syntax);
+            f.close();
+        }
+        w.into_vec()
+    }
+
+    pub(crate) fn field(name: &str, number: i32, type_: i32, type_name: &str) -> FieldProto {
+        FieldProto {
+            name: name.to_string(),
+            number,
+            type_: Some(type_),
+            type_name: (!type_name.is_empty()).then(|| type_name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn repeated(field: FieldProto) -> FieldProto {
+        FieldProto {
+            label: Some(3),
+            ..field
+        }
+    }
+
+    fn enum_proto(name: &str, values: &[&str]) -> EnumProto {
+        EnumProto {
+            name: name.to_string(),
+            value: values
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.to_string(), i as i32))
+                .collect(),
+        }
+    }
+
+    // The descriptor of `examples/addressbook.proto` in package `tutorial`.
+    pub(crate) fn addressbook_file() -> FileProto {
+        let phone_number = MessageProto {
+            name: "PhoneNumber".to_string(),
+            field: vec![field("number", 1, 9, ""), field("type", 2, 14, "PhoneType")],
+            ..Default::default()
+        };
+        let person = MessageProto {
+            name: "Person".to_string(),
+            field: vec![
+                field("name", 1, 9, ""),
+                field("id", 2, 5, ""),
+                field("email", 3, 9, ""),
+                repeated(field("phones", 4, 11, ".tutorial.Person.PhoneNumber")),
+            ],
+            nested_type: vec![phone_number],
+            enum_type: vec![enum_proto("PhoneType", &["MOBILE", "HOME", "WORK"])],
+            ..Default::default()
+        };
+        let address_book = MessageProto {
+            name: "AddressBook".to_string(),
+            field: vec![repeated(field("people", 1, 11, "Person"))],
+            ..Default::default()
+        };
+        FileProto {
+            name: "addressbook.proto".to_string(),
+            package: "tutorial".to_string(),
+            message_type: vec![person, address_book],
+            syntax: "proto3".to_string(),
+            ..Default::default()
+        }
+    }
+
+    // A proto2 file exercising maps, oneofs, groups, packed fields and extensions:
+    //
+    //     package test;
+    //     import "addressbook.proto";
+    //     message Test {
+    //       map<string, int32> counts = 1;
+    //       oneof choice { string text = 2; tutorial.Person person = 3; }
+    //       repeated int32 packed = 4 [packed = true];
+    //       repeated sint64 unpacked = 5;
+    //       optional group Item = 6 { optional bytes data = 7; }
+    //       optional double ratio = 8;
+    //       optional Test child = 9;
+    //       extensions 100 to 199;
+    //     }
+    //     extend Test { optional string note = 100; }
+    pub(crate) fn test_file() -> FileProto {
+        let counts_entry = MessageProto {
+            name: "CountsEntry".to_string(),
+            field: vec![field("key", 1, 9, ""), field("value", 2, 5, "")],
+            map_entry: true,
+            ..Default::default()
+        };
+        let item = MessageProto {
+            name: "Item".to_string(),
+            field: vec![field("data", 7, 12, "")],
+            ..Default::default()
+        };
+        let test = MessageProto {
+            name: "Test".to_string(),
+            field: vec![
+                repeated(field("counts", 1, 11, "CountsEntry")),
+                FieldProto {
+                    oneof_index: Some(0),
+                    ..field("text", 2, 9, "")
+                },
+                FieldProto {
+                    oneof_index: Some(0),
+                    ..field("person", 3, 11, "tutorial.Person")
+                },
+                FieldProto {
+                    packed: Some(true),
+                    ..repeated(field("packed", 4, 5, ""))
+                },
+                repeated(field("unpacked", 5, 18, "")),
+                field("item", 6, 10, "Item"),
+                field("ratio", 8, 1, ""),
+                field("child", 9, 11, "Test"),
+            ],
+            nested_type: vec![counts_entry, item],
+            extension_range: core::iter::once(100..200).collect(),
+            oneof_decl: vec!["choice".to_string()],
+            ..Default::default()
+        };
+        FileProto {
+            name: "test.proto".to_string(),
+            package: "test".to_string(),
+            dependency: vec!["addressbook.proto".to_string()],
+            message_type: vec![test],
+            extension: vec![FieldProto {
+                extendee: Some(".test.Test".to_string()),
+                ..field("note", 100, 9, "")
+            }],
+            syntax: "proto2".to_string(),
+            ..Default::default()
+        }
+    }
+
+    // The well-known types of package `google.protobuf`, in one file.
+    pub(crate) fn well_known_file() -> FileProto {
+        let message = |name: &str, field: Vec<FieldProto>| MessageProto {
+            name: name.to_string(),
+            field,
+            ..Default::default()
+        };
+        let kind = |name: &str, number, type_, type_name| FieldProto {
+            oneof_index: Some(0),
+            ..field(name, number, type_, type_name)
+        };
+        let fields_entry = MessageProto {
+            map_entry: true,
+            ..message(
+                "FieldsEntry",
+                vec![field("key", 1, 9, ""), field("value", 2, 11, "Value")],
+            )
+        };
+        let mut message_type = vec![
+            message(
+                "Any",
+                vec![field("type_url", 1, 9, ""), field("value", 2, 12, "")],
+            ),
+            message(
+                "Timestamp",
+                vec![field("seconds", 1, 3, ""), field("nanos", 2, 5, "")],
+            ),
+            message(
+                "Duration",
+                vec![field("seconds", 1, 3, ""), field("nanos", 2, 5, "")],
+            ),
+            MessageProto {
+                nested_type: vec![fields_entry],
+                ..message(
+                    "Struct",
+                    vec![repeated(field("fields", 1, 11, "FieldsEntry"))],
+                )
+            },
+            MessageProto {
+                oneof_decl: vec!["kind".to_string()],
+                ..message(
+                    "Value",
+                    vec![
+                        kind("null_value", 1, 14, "NullValue"),
+                        kind("number_value", 2, 1, ""),
+                        kind("string_value", 3, 9, ""),
+                        kind("bool_value", 4, 8, ""),
+                        kind("struct_value", 5, 11, "Struct"),
+                        kind("list_value", 6, 11, "ListValue"),
+                    ],
+                )
+            },
+            message("ListValue", vec![repeated(field("values", 1, 11, "Value"))]),
+            message("FieldMask", vec![repeated(field("paths", 1, 9, ""))]),
+            message("Empty", vec![]),
+        ];
+        for (name, type_) in [
+            ("DoubleValue", 1),
+            ("FloatValue", 2),
+            ("Int64Value", 3),
+            ("UInt64Value", 4),
+            ("Int32Value", 5),
+            ("UInt32Value", 13),
+            ("BoolValue", 8),
+            ("StringValue", 9),
+            ("BytesValue", 12),
+        ] {
+            message_type.push(message(name, vec![field("value", 1, type_, "")]));
+        }
+        FileProto {
+            name: "google/protobuf/well_known.proto".to_string(),
+            package: "google.protobuf".to_string(),
+            message_type,
+            enum_type: vec![enum_proto("NullValue", &["NULL_VALUE"])],
+            syntax: "proto3".to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn test_pool() -> DescriptorPool {
+        DescriptorPool::decode(&encode_files(&[addressbook_file(), test_file()])).unwrap()
+    }
+
+    // The test pool with the well-known types.
+    pub(crate) fn json_pool() -> DescriptorPool {
+        let files = [addressbook_file(), test_file(), well_known_file()];
+        DescriptorPool::decode(&encode_files(&files)).unwrap()
+    }
+
+    #[test]
+    fn decode() {
+        let pool = test_pool();
+        assert_eq!(pool.files().len(), 2);
+        assert_eq!(pool.files()[0].syntax(), Syntax::Proto3);
+        assert_eq!(pool.files()[1].dependencies(), ["addressbook.proto"]);
+
+        let person = pool.find_message("tutorial.Person").unwrap();
+        assert_eq!(person.name(), "Person");
+        assert_eq!(person.fields().len(), 4);
+        let phones = person.field_by_name("phones").unwrap();
+        assert_eq!(phones.label(), Label::Repeated);
+        assert!(!phones.is_packed());
+        let FieldType::Message(phone_number) = phones.field_type() else {
+            panic!();
+        };
+        let phone_number = pool.message(phone_number);
+        assert_eq!(phone_number.full_name(), "tutorial.Person.PhoneNumber");
+        assert_eq!(phone_number.parent(), Some(person.id()));
+        let FieldType::Enum(phone_type) = phone_number.field(2).unwrap().field_type() else {
+            panic!();
+        };
+        let phone_type = pool.enum_type(phone_type);
+        assert_eq!(phone_type.full_name(), "tutorial.Person.PhoneType");
+        assert_eq!(phone_type.value(2).unwrap().name(), "WORK");
+        assert_eq!(phone_type.value_by_name("HOME").unwrap().number(), 1);
+        let address_book = pool.find_message(".tutorial.AddressBook").unwrap();
+        let people = address_book.field(1).unwrap();
+        assert_eq!(people.field_type(), FieldType::Message(person.id()));
+        assert_eq!(pool.files()[0].messages(), [person.id(), address_book.id()]);
+
+        let test = pool.find_message("test.Test").unwrap();
+        let counts = test.field_by_json_name("counts").unwrap();
+        assert!(counts.is_map());
+        let FieldType::Message(entry) = counts.field_type() else {
+            panic!();
+        };
+        assert!(pool.message(entry).is_map_entry());
+        assert_eq!(test.oneofs()[0].name(), "choice");
+        assert_eq!(test.oneofs()[0].fields(), [1, 2]);
+        assert_eq!(test.field(3).unwrap().oneof(), Some(0));
+        assert_eq!(
+            test.field(3).unwrap().field_type(),
+            FieldType::Message(person.id())
+        );
+        assert!(test.field(4).unwrap().is_packed());
+        assert!(!test.field(5).unwrap().is_packed());
+        assert!(matches!(
+            test.field(6).unwrap().field_type(),
+            FieldType::Group(_)
+        ));
+        assert_eq!(
+            test.field(9).unwrap().field_type(),
+            FieldType::Message(test.id())
+        );
+        assert_eq!(test.extension_ranges().len(), 1);
+        assert_eq!(test.extension_ranges()[0], 100..200);
+
+        let note = pool.find_extension("test.note").unwrap();
+        assert_eq!(note.extendee(), Some(test.id()));
+        assert_eq!(
+            pool.find_extension_by_number(test.id(), 100)
+                .unwrap()
+                .name(),
+            "note"
+        );
+        assert!(pool.find_message("Person").is_none());
+    }
+
+    #[test]
+    fn json_names() {
+        let mut file = addressbook_file();
+        file.message_type[0].field[0].name = "first_name_2".to_string();
+        let pool = DescriptorPool::decode(&encode_files(&[file])).unwrap();
+        let person = pool.find_message("tutorial.Person").unwrap();
+        assert_eq!(person.fields()[0].json_name(), "firstName2");
+    }
+
+    #[test]
+    fn errors() {
+        let mut file = addressbook_file();
+        file.message_type[1].field[0].type_name = Some("Nobody".to_string());
+        assert_eq!(
+            DescriptorPool::decode(&encode_files(&[file])).unwrap_err(),
+            DescriptorError::UnresolvedType("Nobody".to_string())
+        );
+
+        let mut file = addressbook_file();
+        file.message_type[1].name = "Person".to_string();
+        assert_eq!(
+            DescriptorPool::decode(&encode_files(&[file])).unwrap_err(),
+            DescriptorError::DuplicateName("tutorial.Person".to_string())
+        );
+
+        let mut file = addressbook_file();
+        file.message_type[0].field[1].number = 0;
+        let error = DescriptorPool::decode(&encode_files(&[file])).unwrap_err();
+        assert_eq!(
+            error,
+            DescriptorError::InvalidField("tutorial.Person.id".to_string())
+        );
+        assert_eq!(format!("{}", error), "invalid field `tutorial.Person.id`");
+
+        // A map entry without a value field.
+        let mut file = test_file();
+        file.message_type[0].nested_type[0].field.pop();
+        assert_eq!(
+            DescriptorPool::decode(&encode_files(&[addressbook_file(), file])).unwrap_err(),
+            DescriptorError::InvalidField("test.Test.counts".to_string())
+        );
+
+        // A file referring to a type of a file missing from the set.
+        let error = DescriptorPool::decode(&encode_files(&[test_file()])).unwrap_err();
+        assert_eq!(
+            error,
+            DescriptorError::UnresolvedType("tutorial.Person".to_string())
+        );
+
+        assert!(matches!(
+            DescriptorPool::decode(b"\x0a\x05"),
+            Err(DescriptorError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,772 @@
+use super::build::{to_json_name, EnumProto, FieldProto, FileProto, MessageProto};
+use crate::message::MAX_DEPTH;
+use crate::text::lex::{unescape, Lexer, Token};
+use crate::text::parse::parse_digits;
+use crate::text::{ParseError, ParseErrorKind};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::ops::RangeInclusive;
+
+// The valid field numbers.
+const FIELD_NUMBERS: RangeInclusive<i128> = 1..=536_870_911;
+
+// Parses the source of a `.proto` file named `name`. Type names are left as written, to be
+// resolved when the pool is built.
+pub(super) fn parse_file(name: &str, source: &str) -> Result<FileProto, ParseError> {
+    let mut parser = Parser {
+        lexer: Lexer::proto(source),
+        proto3: false,
+        depth: 0,
+    };
+    let mut file = FileProto {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    loop {
+        let (pos, token) = parser.lexer.next()?;
+        match token {
+            Token::End => return Ok(file),
+            Token::Symbol(';') => {}
+            Token::Ident("syntax") => {
+                parser.expect('=')?;
+                let (pos, syntax) = parser.parse_string()?;
+                if syntax != "proto2" && syntax != "proto3" {
+                    return Err(parser.lexer.error(ParseErrorKind::InvalidValue, pos));
+                }
+                parser.proto3 = syntax == "proto3";
+                file.syntax = syntax;
+                parser.expect(';')?;
+            }
+            Token::Ident("edition") => {
+                parser.expect('=')?;
+                parser.parse_string()?;
+                file.syntax = "editions".to_string();
+                parser.expect(';')?;
+            }
+            Token::Ident("package") => {
+                file.package = parser.parse_full_ident()?;
+                parser.expect(';')?;
+            }
+            Token::Ident("import") => {
+                parser.consume_ident("public")?;
+                parser.consume_ident("weak")?;
+                file.dependency.push(parser.parse_string()?.1);
+                parser.expect(';')?;
+            }
+            Token::Ident("option") => parser.parse_option()?,
+            Token::Ident("message") => file.message_type.push(parser.parse_message()?),
+            Token::Ident("enum") => file.enum_type.push(parser.parse_enum()?),
+            Token::Ident("extend") => {
+                parser.parse_extend(&mut file.extension, &mut file.message_type)?
+            }
+            Token::Ident("service") => {
+                parser.parse_ident()?;
+                parser.skip_block()?;
+            }
+            _ => return Err(parser.unexpected(pos, token)),
+        }
+    }
+}
+
+// Returns the type number of a scalar type name, as in `FieldDescriptorProto.Type`.
+fn scalar_type(name: &str) -> Option<i32> {
+    Some(match name {
+        "double" => 1,
+        "float" => 2,
+        "int64" => 3,
+        "uint64" => 4,
+        "int32" => 5,
+        "fixed64" => 6,
+        "fixed32" => 7,
+        "bool" => 8,
+        "string" => 9,
+        "bytes" => 12,
+        "uint32" => 13,
+        "sfixed32" => 15,
+        "sfixed64" => 16,
+        "sint32" => 17,
+        "sint64" => 18,
+        _ => return None,
+    })
+}
+
+// Escapes bytes as protoc does for the default value of a `bytes` field, with octal escapes for
+// bytes that are not printable ASCII.
+fn c_escape(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for &b in bytes {
+        match b {
+            b'\n' => s.push_str("\\n"),
+            b'\r' => s.push_str("\\r"),
+            b'\t' => s.push_str("\\t"),
+            b'"' | b'\'' | b'\\' => {
+                s.push('\\');
+                s.push(b as char);
+            }
+            0x20..=0x7e => s.push(b as char),
+            _ => s.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    s
+}
+
+struct Parser<'t> {
+    lexer: Lexer<'t>,
+    proto3: bool,
+    depth: u32,
+}
+
+impl<'t> Parser<'t> {
+    fn unexpected(&self, pos: usize, token: Token<'_>) -> ParseError {
+        match token {
+            Token::End => self.lexer.error(ParseErrorKind::UnexpectedEnd, pos),
+            _ => self.lexer.error(ParseErrorKind::UnexpectedToken, pos),
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        if token != Token::Symbol(symbol) {
+            return Err(self.unexpected(pos, token));
+        }
+        Ok(())
+    }
+
+    // Consumes `symbol` if it is the next token. Returns `true` if it was.
+    fn consume(&mut self, symbol: char) -> Result<bool, ParseError> {
+        if self.lexer.peek()?.1 != Token::Symbol(symbol) {
+            return Ok(false);
+        }
+        self.lexer.next()?;
+        Ok(true)
+    }
+
+    // Consumes the identifier `ident` if it is the next token. Returns `true` if it was.
+    fn consume_ident(&mut self, ident: &str) -> Result<bool, ParseError> {
+        if self.lexer.peek()?.1 != Token::Ident(ident) {
+            return Ok(false);
+        }
+        self.lexer.next()?;
+        Ok(true)
+    }
+
+    fn parse_ident(&mut self) -> Result<&'t str, ParseError> {
+        match self.lexer.next()? {
+            (_, Token::Ident(ident)) => Ok(ident),
+            (pos, token) => Err(self.unexpected(pos, token)),
+        }
+    }
+
+    // Parses a dotted name, such as `foo.Bar` or `.foo.Bar`.
+    fn parse_full_ident(&mut self) -> Result<String, ParseError> {
+        let mut name = String::new();
+        if self.consume('.')? {
+            name.push('.');
+        }
+        name.push_str(self.parse_ident()?);
+        while self.consume('.')? {
+            name.push('.');
+            name.push_str(self.parse_ident()?);
+        }
+        Ok(name)
+    }
+
+    // Parses one or more adjacent strings, which are concatenated, as UTF-8.
+    fn parse_string(&mut self) -> Result<(usize, String), ParseError> {
+        let (pos, bytes) = self.parse_bytes()?;
+        let s = String::from_utf8(bytes)
+            .map_err(|_| self.lexer.error(ParseErrorKind::InvalidString, pos))?;
+        Ok((pos, s))
+    }
+
+    // Parses one or more adjacent strings, which are concatenated, as bytes.
+    fn parse_bytes(&mut self) -> Result<(usize, Vec<u8>), ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        let Token::Str(mut s) = token else {
+            return Err(self.unexpected(pos, token));
+        };
+        let mut bytes = Vec::new();
+        let mut start = pos;
+        loop {
+            unescape(s, &mut bytes).map_err(|offset| {
+                self.lexer
+                    .error(ParseErrorKind::InvalidString, start + offset)
+            })?;
+            match self.lexer.peek()? {
+                (next, Token::Str(next_s)) => {
+                    self.lexer.next()?;
+                    (start, s) = (next, next_s);
+                }
+                _ => break,
+            }
+        }
+        Ok((pos, bytes))
+    }
+
+    // Parses an integer with an optional minus sign.
+    fn parse_int(&mut self) -> Result<(usize, i128), ParseError> {
+        let negative = self.consume('-')?;
+        let (pos, token) = self.lexer.next()?;
+        let Token::Number(digits) = token else {
+            return Err(self.unexpected(pos, token));
+        };
+        let value = parse_digits(digits)
+            .ok_or_else(|| self.lexer.error(ParseErrorKind::InvalidValue, pos))?;
+        let value = i128::from(value);
+        Ok((pos, if negative { -value } else { value }))
+    }
+
+    // Parses a field number or an enum value number in `range`.
+    fn parse_number(&mut self, range: RangeInclusive<i128>) -> Result<i32, ParseError> {
+        let (pos, value) = self.parse_int()?;
+        if !range.contains(&value) {
+            return Err(self.lexer.error(ParseErrorKind::InvalidValue, pos));
+        }
+        Ok(value as i32)
+    }
+
+    // Parses the value of an option, and returns it as text. Aggregate values are skipped.
+    // Parses the value of an option. Strings are returned as they are unescaped, which need not be
+    // UTF-8.
+    fn parse_constant(&mut self) -> Result<(usize, Vec<u8>), ParseError> {
+        let (pos, token) = self.lexer.peek()?;
+        let value = match token {
+            Token::Str(_) => return self.parse_bytes(),
+            Token::Symbol('{') => {
+                self.skip_block()?;
+                String::new()
+            }
+            _ => {
+                let sign = if self.consume('-')? {
+                    "-"
+                } else {
+                    self.consume('+')?;
+                    ""
+                };
+                match self.lexer.next()? {
+                    (_, Token::Ident(ident)) => format!("{sign}{ident}"),
+                    (_, Token::Number(number)) => match parse_digits(number) {
+                        Some(value) => format!("{sign}{value}"),
+                        None => format!("{sign}{number}"),
+                    },
+                    (pos, token) => return Err(self.unexpected(pos, token)),
+                }
+            }
+        };
+        Ok((pos, value.into_bytes()))
+    }
+
+    // Parses the name of an option, such as `java_package` or `(my.option).field`.
+    fn parse_option_name(&mut self) -> Result<String, ParseError> {
+        let mut name = String::new();
+        loop {
+            if self.consume('(')? {
+                name.push('(');
+                name.push_str(&self.parse_full_ident()?);
+                name.push(')');
+                self.expect(')')?;
+            } else {
+                name.push_str(self.parse_ident()?);
+            }
+            if !self.consume('.')? {
+                return Ok(name);
+            }
+            name.push('.');
+        }
+    }
+
+    // Parses an option statement after `option`. Options are not kept.
+    fn parse_option(&mut self) -> Result<(), ParseError> {
+        self.parse_option_name()?;
+        self.expect('=')?;
+        self.parse_constant()?;
+        self.expect(';')
+    }
+
+    // Skips a block in braces, such as a service or an aggregate option value.
+    fn skip_block(&mut self) -> Result<(), ParseError> {
+        self.expect('{')?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.lexer.next()? {
+                (_, Token::Symbol('{')) => depth += 1,
+                (_, Token::Symbol('}')) => depth -= 1,
+                (pos, Token::End) => return Err(self.unexpected(pos, Token::End)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Skips the rest of a statement, such as `reserved`.
+    fn skip_statement(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.lexer.next()? {
+                (_, Token::Symbol(';')) => return Ok(()),
+                (pos, token @ (Token::End | Token::Symbol('{' | '}'))) => {
+                    return Err(self.unexpected(pos, token))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_message(&mut self) -> Result<MessageProto, ParseError> {
+        let mut message = MessageProto {
+            name: self.parse_ident()?.to_string(),
+            ..Default::default()
+        };
+        self.parse_message_body(&mut message)?;
+        Ok(message)
+    }
+
+    fn parse_message_body(&mut self, message: &mut MessageProto) -> Result<(), ParseError> {
+        let (pos, _) = self.lexer.peek()?;
+        if self.depth == MAX_DEPTH {
+            return Err(self.lexer.error(ParseErrorKind::DepthLimitExceeded, pos));
+        }
+        self.depth += 1;
+        self.expect('{')?;
+        // The indexes of proto3 `optional` fields, which are given oneofs of their own after the
+        // declared oneofs.
+        let mut optional = Vec::new();
+        loop {
+            let (pos, token) = self.lexer.peek()?;
+            match token {
+                Token::Symbol('}') => {
+                    self.lexer.next()?;
+                    break;
+                }
+                Token::Symbol(';') => {
+                    self.lexer.next()?;
+                }
+                Token::Ident(
+                    keyword @ ("message" | "enum" | "extend" | "option" | "oneof" | "reserved"
+                    | "extensions"),
+                ) => {
+                    self.lexer.next()?;
+                    match keyword {
+                        "message" => message.nested_type.push(self.parse_message()?),
+                        "enum" => message.enum_type.push(self.parse_enum()?),
+                        "extend" => {
+                            self.parse_extend(&mut message.extension, &mut message.nested_type)?
+                        }
+                        "option" => self.parse_option()?,
+                        "oneof" => self.parse_oneof(message)?,
+                        "reserved" => self.skip_statement()?,
+                        _ => self.parse_extensions(message)?,
+                    }
+                }
+                Token::Ident(_) => {
+                    let (field, proto3_optional) =
+                        self.parse_field(None, &mut message.nested_type)?;
+                    if proto3_optional {
+                        optional.push(message.field.len());
+                    }
+                    message.field.push(field);
+                }
+                _ => return Err(self.unexpected(pos, token)),
+            }
+        }
+        for index in optional {
+            let field = &mut message.field[index];
+            field.oneof_index = Some(message.oneof_decl.len() as i32);
+            message.oneof_decl.push(format!("_{}", field.name));
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    // Parses a oneof after `oneof`, adding its fields to `message`.
+    fn parse_oneof(&mut self, message: &mut MessageProto) -> Result<(), ParseError> {
+        let index = message.oneof_decl.len() as i32;
+        message.oneof_decl.push(self.parse_ident()?.to_string());
+        self.expect('{')?;
+        loop {
+            if self.consume('}')? {
+                return Ok(());
+            }
+            if self.consume(';')? {
+                continue;
+            }
+            if self.consume_ident("option")? {
+                self.parse_option()?;
+                continue;
+            }
+            let (field, _) = self.parse_field(Some(index), &mut message.nested_type)?;
+            message.field.push(field);
+        }
+    }
+
+    // Parses extension ranges after `extensions`, such as `100 to 199, 1000 to max`.
+    fn parse_extensions(&mut self, message: &mut MessageProto) -> Result<(), ParseError> {
+        loop {
+            let start = self.parse_number(FIELD_NUMBERS)?;
+            let end = if !self.consume_ident("to")? {
+                start
+            } else if self.consume_ident("max")? {
+                *FIELD_NUMBERS.end() as i32
+            } else {
+                self.parse_number(FIELD_NUMBERS)?
+            };
+            message.extension_range.push(start as u64..end as u64 + 1);
+            if !self.consume(',')? {
+                break;
+            }
+        }
+        if self.lexer.peek()?.1 == Token::Symbol('[') {
+            self.parse_field_options(&mut FieldProto::default())?;
+        }
+        self.expect(';')
+    }
+
+    // Parses an `extend` block after `extend`. The types of any groups are added to `types`.
+    fn parse_extend(
+        &mut self,
+        extensions: &mut Vec<FieldProto>,
+        types: &mut Vec<MessageProto>,
+    ) -> Result<(), ParseError> {
+        let extendee = self.parse_full_ident()?;
+        self.expect('{')?;
+        loop {
+            if self.consume('}')? {
+                return Ok(());
+            }
+            if self.consume(';')? {
+                continue;
+            }
+            let (field, _) = self.parse_field(None, types)?;
+            extensions.push(FieldProto {
+                extendee: Some(extendee.clone()),
+                ..field
+            });
+        }
+    }
+
+    // Parses a field of a message, oneof or `extend` block. The types of maps and groups are added
+    // to `types`. Returns the field, and whether it is a proto3 `optional` field.
+    fn parse_field(
+        &mut self,
+        oneof: Option<i32>,
+        types: &mut Vec<MessageProto>,
+    ) -> Result<(FieldProto, bool), ParseError> {
+        let mut field = FieldProto {
+            oneof_index: oneof,
+            ..Default::default()
+        };
+        let mut optional = false;
+        if oneof.is_none() {
+            match self.lexer.peek()?.1 {
+                Token::Ident("optional") => optional = true,
+                Token::Ident("required") => field.label = Some(2),
+                Token::Ident("repeated") => field.label = Some(3),
+                _ => {}
+            }
+            if optional || field.label.is_some() {
+                self.lexer.next()?;
+            }
+        }
+        let (pos, _) = self.lexer.peek()?;
+        let type_name = self.parse_full_ident()?;
+        let mut group = None;
+        if type_name == "group" {
+            let name = self.parse_ident()?;
+            field.name = name.to_ascii_lowercase();
+            field.type_ = Some(10);
+            field.type_name = Some(name.to_string());
+            group = Some(MessageProto {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        } else if type_name == "map" && field.label.is_none() && self.consume('<')? {
+            let key_type = self.parse_ident()?;
+            let key_type = scalar_type(key_type)
+                .filter(|&key_type| !matches!(key_type, 1 | 2 | 12))
+                .ok_or_else(|| self.lexer.error(ParseErrorKind::InvalidValue, pos))?;
+            self.expect(',')?;
+            let value_type = self.parse_full_ident()?;
+            self.expect('>')?;
+            field.name = self.parse_ident()?.to_string();
+            let mut entry_name = to_json_name(&field.name);
+            if let Some(first) = entry_name.get_mut(..1) {
+                first.make_ascii_uppercase();
+            }
+            entry_name.push_str("Entry");
+            let value = match scalar_type(&value_type) {
+                Some(value_type) => FieldProto {
+                    type_: Some(value_type),
+                    ..Default::default()
+                },
+                None => FieldProto {
+                    type_name: Some(value_type),
+                    ..Default::default()
+                },
+            };
+            types.push(MessageProto {
+                name: entry_name.clone(),
+                field: vec![
+                    FieldProto {
+                        name: "key".to_string(),
+                        number: 1,
+                        type_: Some(key_type),
+                        ..Default::default()
+                    },
+                    FieldProto {
+                        name: "value".to_string(),
+                        number: 2,
+                        ..value
+                    },
+                ],
+                map_entry: true,
+                ..Default::default()
+            });
+            field.label = Some(3);
+            field.type_ = Some(11);
+            field.type_name = Some(entry_name);
+        } else {
+            field.name = self.parse_ident()?.to_string();
+            match scalar_type(&type_name) {
+                Some(type_) => field.type_ = Some(type_),
+                None => field.type_name = Some(type_name),
+            }
+        }
+        self.expect('=')?;
+        field.number = self.parse_number(FIELD_NUMBERS)?;
+        if self.lexer.peek()?.1 == Token::Symbol('[') {
+            self.parse_field_options(&mut field)?;
+        }
+        match group {
+            Some(mut group) => {
+                self.parse_message_body(&mut group)?;
+                types.push(group);
+            }
+            None => self.expect(';')?,
+        }
+        Ok((field, optional && self.proto3))
+    }
+
+    // Parses the options of a field in brackets. Only `packed`, `default` and `json_name` are kept.
+    fn parse_field_options(&mut self, field: &mut FieldProto) -> Result<(), ParseError> {
+        self.expect('[')?;
+        loop {
+            let name = self.parse_option_name()?;
+            self.expect('=')?;
+            let (pos, value) = self.parse_constant()?;
+            let string = |value| {
+                String::from_utf8(value)
+                    .map_err(|_| self.lexer.error(ParseErrorKind::InvalidString, pos))
+            };
+            match name.as_str() {
+                "packed" => {
+                    field.packed = Some(match &value[..] {
+                        b"true" => true,
+                        b"false" => false,
+                        _ => return Err(self.lexer.error(ParseErrorKind::InvalidValue, pos)),
+                    })
+                }
+                // As in `FieldDescriptorProto`, the default of a `bytes` field is C-escaped.
+                "default" if field.type_ == Some(12) => {
+                    field.default_value = Some(c_escape(&value))
+                }
+                "default" => field.default_value = Some(string(value)?),
+                "json_name" => field.json_name = Some(string(value)?),
+                _ => {}
+            }
+            if !self.consume(',')? {
+                break;
+            }
+        }
+        self.expect(']')
+    }
+
+    fn parse_enum(&mut self) -> Result<EnumProto, ParseError> {
+        let mut enum_type = EnumProto {
+            name: self.parse_ident()?.to_string(),
+            ..Default::default()
+        };
+        self.expect('{')?;
+        loop {
+            match self.lexer.next()? {
+                (_, Token::Symbol('}')) => return Ok(enum_type),
+                (_, Token::Symbol(';')) => {}
+                (_, Token::Ident("option")) => self.parse_option()?,
+                (_, Token::Ident("reserved")) => self.skip_statement()?,
+                (_, Token::Ident(name)) => {
+                    self.expect('=')?;
+                    let number = self.parse_number(i32::MIN.into()..=i32::MAX.into())?;
+                    if self.lexer.peek()?.1 == Token::Symbol('[') {
+                        self.parse_field_options(&mut FieldProto::default())?;
+                    }
+                    self.expect(';')?;
+                    enum_type.value.push((name.to_string(), number));
+                }
+                (pos, token) => return Err(self.unexpected(pos, token)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::test_pool;
+    use super::super::{DescriptorError, DescriptorPool, FieldType, Syntax};
+    use crate::message::Message;
+    use crate::text::{print, ParseErrorKind};
+    use crate::writer::MessageWriter;
+    use alloc::format;
+    use alloc::string::String;
+
+    #[test]
+    fn parse_address_book() {
+        let source = include_str!("../../examples/addressbook.proto");
+        let pool = DescriptorPool::parse(&[("addressbook.proto", source)]).unwrap();
+        assert_eq!(pool.files()[0].syntax(), Syntax::Proto3);
+        let mut w = MessageWriter::new();
+        let mut person = w.open_message(1);
+        person.add_string(1, "Alice");
+        person.add_int32(2, 1);
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-4321");
+        phone.add_enum(2, 1);
+        phone.close();
+        person.close();
+        let mut out = String::new();
+        let address_book = pool.find_message("AddressBook").unwrap();
+        print(&mut out, &pool, address_book, Message::new(w.as_bytes())).unwrap();
+        assert_eq!(
+            out,
+            "people {\n  name: \"Alice\"\n  id: 1\n  phones {\n    number: \"555-4321\"\n    type: HOME\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn parse_files() {
+        let addressbook = r#"
+            syntax = "proto3";
+            package tutorial;
+
+            message Person {
+              string name = 1;
+              int32 id = 2;  // Unique ID number for this person.
+              string email = 3;
+              enum PhoneType { MOBILE = 0; HOME = 1; WORK = 2; }
+              message PhoneNumber {
+                string number = 1;
+                PhoneType type = 2;
+              }
+              repeated .tutorial.Person.PhoneNumber phones = 4;
+            }
+
+            message AddressBook { repeated Person people = 1; }
+        "#;
+        let test = r#"
+            /* A proto2 file exercising maps, oneofs, groups, packed fields and
+               extensions. */
+            package test;
+            import "addressbook.proto";
+            option java_package = "com.example.test";
+            message Test {
+              map<string, int32> counts = 1;
+              oneof choice { string text = 2; tutorial.Person person = 3; }
+              repeated int32 packed = 4 [packed = true];
+              repeated sint64 unpacked = 5 [(my.option) = { a: 1 }];
+              optional group Item = 6 { optional bytes data = 7; }
+              optional double ratio = 8;
+              optional Test child = 9;
+              reserved 10 to 20, 30;
+              extensions 100 to 199;
+            }
+            extend Test { optional string note = 100; }
+            service Tests { rpc Get(Test) returns (Test); }
+        "#;
+        let files = [("addressbook.proto", addressbook), ("test.proto", test)];
+        let pool = DescriptorPool::parse(&files).unwrap();
+        assert_eq!(format!("{pool:?}"), format!("{:?}", test_pool()));
+
+        let source = r#"
+            syntax = "proto3";
+            message M {
+              optional int64 id = 1 [json_name = "ID"];
+              oneof kind { bool flag = 2; }
+              map<int32, M> children = 3;
+              repeated E values = 4;
+              enum E { option allow_alias = true; A = 0; B = -1 [deprecated = true]; C = 0; }
+              extensions 1000 to max;
+            }
+        "#;
+        let pool = DescriptorPool::parse(&[("m.proto", source)]).unwrap();
+        let m = pool.find_message("M").unwrap();
+        let id = m.field(1).unwrap();
+        assert_eq!(id.json_name(), "ID");
+        assert_eq!(id.oneof(), Some(1));
+        assert_eq!(m.oneofs()[1].name(), "_id");
+        assert!(m.field(3).unwrap().is_map());
+        let entry = pool.find_message("M.ChildrenEntry").unwrap();
+        assert_eq!(
+            entry.field(2).unwrap().field_type(),
+            FieldType::Message(m.id())
+        );
+        assert!(m.field(4).unwrap().is_packed());
+        let e = pool.find_enum("M.E").unwrap();
+        assert_eq!(e.value_by_name("B").unwrap().number(), -1);
+        assert_eq!(m.extension_ranges()[0], 1000..536_870_912);
+
+        // Defaults of `bytes` fields need not be UTF-8, and are kept C-escaped.
+        let source = r#"
+            message D {
+              optional bytes b = 1 [default = "\377a\"\n" "\x01"];
+              optional string s = 2 [default = "\303\251"];
+            }
+        "#;
+        let pool = DescriptorPool::parse(&[("d.proto", source)]).unwrap();
+        let d = pool.find_message("D").unwrap();
+        assert_eq!(
+            d.field(1).unwrap().default_value(),
+            Some(r#"\377a\"\n\001"#)
+        );
+        assert_eq!(d.field(2).unwrap().default_value(), Some("\u{e9}"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |source| match DescriptorPool::parse(&[("a.proto", source)]) {
+            Err(DescriptorError::Parse(name, error)) => {
+                assert_eq!(name, "a.proto");
+                (error.kind(), error.line(), error.column())
+            }
+            result => panic!("{result:?}"),
+        };
+        use ParseErrorKind::*;
+        assert_eq!(
+            error("message M {\n  int32 a = 1\n}"),
+            (UnexpectedToken, 3, 1)
+        );
+        assert_eq!(error("message M { int32 a = 0; }"), (InvalidValue, 1, 23));
+        assert_eq!(
+            error("message M { int32 a = 536870912; }"),
+            (InvalidValue, 1, 23)
+        );
+        assert_eq!(
+            error("message M { map<float, int32> m = 1; }"),
+            (InvalidValue, 1, 13)
+        );
+        assert_eq!(error("syntax = \"proto4\";"), (InvalidValue, 1, 10));
+        assert_eq!(error("import \"a.proto;"), (InvalidString, 1, 8));
+        assert_eq!(
+            error("message M { optional string s = 1 [default = \"\\377\"]; }"),
+            (InvalidString, 1, 46)
+        );
+        assert_eq!(error("enum E { A = 1;"), (UnexpectedEnd, 1, 16));
+        assert_eq!(error("message M { /* a = 1; }"), (UnexpectedEnd, 1, 24));
+        assert_eq!(error("service S { rpc"), (UnexpectedEnd, 1, 16));
+        assert_eq!(error("enum E { A = 2147483648; }"), (InvalidValue, 1, 14));
+        let deep = "message M {".repeat(101) + &"}".repeat(101);
+        assert_eq!(error(&deep).0, DepthLimitExceeded);
+
+        assert_eq!(
+            DescriptorPool::parse(&[("a.proto", "message M { N n = 1; }")]).unwrap_err(),
+            DescriptorError::UnresolvedType("N".into())
+        );
+    }
+}
@@ -0,0 +1,573 @@
+use crate::descriptor::{DescriptorPool, FieldDescriptor, FieldType, MessageDescriptor};
+use crate::field::{Field, FieldValue};
+use crate::message::{Message, MAX_DEPTH};
+use crate::types::{
+    Bool, Double, Enum, Fixed32, Fixed64, Float, Int32, Int64, PackedScalar, ProtoScalar, Sfixed32,
+    Sfixed64, Sint32, Sint64, Uint32, Uint64,
+};
+use crate::Error;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// A message decoded with a descriptor.
+///
+/// Fields are decoded according to their declared type. Values of repeated fields are collected
+/// into a [`Value::List`] whether they are encoded packed or not, and map fields are collected
+/// into a [`Value::Map`]. Fields that are not declared, or whose wire type does not match the
+/// declared type, are kept as unknown fields.
+#[derive(Clone, Debug)]
+pub struct DynamicMessage<'a, 'd> {
+    descriptor: &'d MessageDescriptor,
+    // Sorted by field number.
+    fields: Vec<(&'d FieldDescriptor, Value<'a, 'd>)>,
+    unknown_fields: Vec<Field<'a>>,
+    // Positions of the entries of map fields by field number and key.
+    map_keys: BTreeMap<(u64, MapKey<'a>), usize>,
+    // The message last merged into this one, with the options and limits its fields were decoded
+    // with.
+    source: Message<'a>,
+}
+
+// The key of a map entry, ordered so that entries can be found by key.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum MapKey<'a> {
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    String(&'a str),
+}
+
+impl<'a> MapKey<'a> {
+    // Map keys are checked to have an integral, bool or string type when the pool is built.
+    fn new(key: &Value<'a, '_>) -> Self {
+        match *key {
+            Value::Bool(v) => MapKey::Bool(v),
+            Value::I32(v) => MapKey::Int(v.into()),
+            Value::I64(v) => MapKey::Int(v),
+            Value::U32(v) => MapKey::Uint(v.into()),
+            Value::U64(v) => MapKey::Uint(v),
+            Value::String(v) => MapKey::String(v),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// A value of a field of a [`DynamicMessage`].
+#[derive(Clone, Debug)]
+pub enum Value<'a, 'd> {
+    /// A `bool` value.
+    Bool(bool),
+    /// An `int32`, `sint32` or `sfixed32` value.
+    I32(i32),
+    /// An `int64`, `sint64` or `sfixed64` value.
+    I64(i64),
+    /// A `uint32` or `fixed32` value.
+    U32(u32),
+    /// A `uint64` or `fixed64` value.
+    U64(u64),
+    /// A `float` value.
+    F32(f32),
+    /// A `double` value.
+    F64(f64),
+    /// A `string` value.
+    String(&'a str),
+    /// A `bytes` value.
+    Bytes(&'a [u8]),
+    /// The number of an enum value.
+    Enum(i32),
+    /// A message or group value.
+    Message(DynamicMessage<'a, 'd>),
+    /// The values of a repeated field.
+    List(Vec<Value<'a, 'd>>),
+    /// The entries of a map field as key/value pairs, in the order the keys first appeared.
+    Map(Vec<(Value<'a, 'd>, Value<'a, 'd>)>),
+}
+
+impl<'a, 'd> DynamicMessage<'a, 'd> {
+    /// Decodes `message` as a message of the type `descriptor`.
+    ///
+    /// Types referenced by `descriptor`, and extensions, are looked up in `pool`. If a decoding
+    /// error occurs, `Err` is returned. Messages nested more than 100 deep return an error of kind
+    /// [`DepthLimitExceeded`](crate::ErrorKind::DepthLimitExceeded), even if the limits of
+    /// `message` allow them.
+    pub fn decode(
+        pool: &'d DescriptorPool,
+        descriptor: &'d MessageDescriptor,
+        message: Message<'a>,
+    ) -> Result<Self, Error> {
+        let mut dynamic = DynamicMessage::empty(descriptor);
+        dynamic.merge(pool, message)?;
+        Ok(dynamic)
+    }
+
+    // Returns a message of the type `descriptor` with no fields.
+    pub(crate) fn empty(descriptor: &'d MessageDescriptor) -> Self {
+        DynamicMessage {
+            descriptor,
+            fields: Vec::new(),
+            unknown_fields: Vec::new(),
+            map_keys: BTreeMap::new(),
+            source: Message::new(&[]),
+        }
+    }
+
+    /// Decodes `message` and merges it into this message.
+    ///
+    /// As when a message is parsed from concatenated encodings, singular fields are replaced,
+    /// except message fields, which are merged. Repeated and map fields are appended to. Setting
+    /// a member of a oneof clears the other members.
+    pub fn merge(&mut self, pool: &'d DescriptorPool, message: Message<'a>) -> Result<(), Error> {
+        let mut message = message;
+        message.options = message.options.groups(true);
+        let limits = &mut message.options.limits;
+        limits.max_depth = limits.max_depth.min(MAX_DEPTH);
+        self.source = message;
+        for field in message.fields() {
+            let field = field?;
+            let descriptor = match self.descriptor.field(field.number) {
+                Some(descriptor) => descriptor,
+                None => match pool.find_extension_by_number(self.descriptor.id(), field.number) {
+                    Some(descriptor) => descriptor,
+                    None => {
+                        self.unknown_fields.push(field);
+                        continue;
+                    }
+                },
+            };
+            if !self.merge_field(pool, descriptor, &field.value)? {
+                self.unknown_fields.push(field);
+            }
+        }
+        Ok(())
+    }
+
+    // Returns `false` if the wire type of `value` does not match the declared type.
+    fn merge_field(
+        &mut self,
+        pool: &'d DescriptorPool,
+        descriptor: &'d FieldDescriptor,
+        value: &FieldValue<'a>,
+    ) -> Result<bool, Error> {
+        let field_type = descriptor.field_type();
+        let wire_type = wire_type(value);
+        let packed = descriptor.is_repeated()
+            && field_type.is_packable()
+            && matches!(value, FieldValue::LengthDelimited(_));
+        if wire_type != field_type.wire_type() && !packed {
+            return Ok(false);
+        }
+        // Setting a member of a oneof clears the other members.
+        if let Some(oneof) = descriptor.oneof() {
+            self.fields
+                .retain(|(d, _)| d.oneof() != Some(oneof) || d.number() == descriptor.number());
+        }
+        let index = match self
+            .fields
+            .binary_search_by_key(&descriptor.number(), |(d, _)| d.number())
+        {
+            Ok(index) => index,
+            Err(index) => {
+                let empty = if descriptor.is_map() {
+                    Value::Map(Vec::new())
+                } else if descriptor.is_repeated() {
+                    Value::List(Vec::new())
+                } else {
+                    self.fields
+                        .insert(index, (descriptor, Value::decode(pool, field_type, value)?));
+                    return Ok(true);
+                };
+                self.fields.insert(index, (descriptor, empty));
+                index
+            }
+        };
+        match &mut self.fields[index].1 {
+            Value::Map(entries) => {
+                let FieldType::Message(entry) = field_type else {
+                    unreachable!()
+                };
+                let entry =
+                    DynamicMessage::decode(pool, pool.message(entry), value.get_message()?)?;
+                let (key, value) = entry.into_map_entry(pool);
+                let map_key = (descriptor.number(), MapKey::new(&key));
+                match self.map_keys.get(&map_key) {
+                    Some(&index) => entries[index].1 = value,
+                    None => {
+                        self.map_keys.insert(map_key, entries.len());
+                        entries.push((key, value));
+                    }
+                }
+            }
+            Value::List(values) => {
+                if field_type.is_packable() {
+                    decode_repeated(field_type, value, values)?;
+                } else {
+                    values.push(Value::decode(pool, field_type, value)?);
+                }
+            }
+            Value::Message(message) => match value {
+                FieldValue::Group(group) => message.merge(pool, *group)?,
+                _ => message.merge(pool, value.get_message()?)?,
+            },
+            existing => *existing = Value::decode(pool, field_type, value)?,
+        }
+        Ok(true)
+    }
+
+    // Splits a map entry into its key and value, using defaults for missing fields. Entry types
+    // are checked to declare both fields when the pool is built.
+    fn into_map_entry(mut self, pool: &'d DescriptorPool) -> (Value<'a, 'd>, Value<'a, 'd>) {
+        let mut take = |number| {
+            let descriptor = self.descriptor.field(number).unwrap();
+            match self.fields.iter().position(|(d, _)| d.number() == number) {
+                Some(index) => self.fields.swap_remove(index).1,
+                None => Value::default_for(pool, descriptor),
+            }
+        };
+        let key = take(1);
+        let value = take(2);
+        (key, value)
+    }
+
+    /// Returns the type of the message.
+    #[inline]
+    pub fn descriptor(&self) -> &'d MessageDescriptor {
+        self.descriptor
+    }
+
+    /// Returns the fields present in the message, including extensions, ordered by field number.
+    #[inline]
+    pub fn fields(&self) -> impl Iterator<Item = (&'d FieldDescriptor, &Value<'a, 'd>)> + '_ {
+        self.fields
+            .iter()
+            .map(|(descriptor, value)| (*descriptor, value))
+    }
+
+    /// Returns the value of the field with the given number, or `None` if it is not present.
+    pub fn get(&self, number: u64) -> Option<&Value<'a, 'd>> {
+        self.fields
+            .binary_search_by_key(&number, |(d, _)| d.number())
+            .ok()
+            .map(|index| &self.fields[index].1)
+    }
+
+    /// Returns the value of the field with the given name, or `None` if it is not present.
+    ///
+    /// Extensions are named by their fully-qualified name.
+    pub fn get_by_name(&self, name: &str) -> Option<&Value<'a, 'd>> {
+        self.fields
+            .iter()
+            .find(|(d, _)| match d.extendee() {
+                Some(_) => d.full_name() == name,
+                None => d.name() == name,
+            })
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the fields that are not declared by the message type, in the order they appear.
+    #[inline]
+    pub fn unknown_fields(&self) -> &[Field<'a>] {
+        &self.unknown_fields
+    }
+
+    // Returns the message last merged into this message.
+    pub(crate) fn source(&self) -> Message<'a> {
+        self.source
+    }
+}
+
+/// Messages are equal if they have the same type and equal field values. Unknown fields are not
+/// compared.
+impl PartialEq for DynamicMessage<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self.descriptor, other.descriptor)
+            && self.fields.len() == other.fields.len()
+            && self
+                .fields
+                .iter()
+                .zip(&other.fields)
+                .all(|((a, x), (b, y))| a.number() == b.number() && x == y)
+    }
+}
+
+impl<'a, 'd> Value<'a, 'd> {
+    pub(crate) fn decode(
+        pool: &'d DescriptorPool,
+        field_type: FieldType,
+        value: &FieldValue<'a>,
+    ) -> Result<Self, Error> {
+        Ok(match field_type {
+            FieldType::Double => Value::F64(value.get_double()?),
+            FieldType::Float => Value::F32(value.get_float()?),
+            FieldType::Int64 => Value::I64(value.get_int64()?),
+            FieldType::Uint64 => Value::U64(value.get_uint64()?),
+            FieldType::Int32 => Value::I32(value.get_int32()?),
+            FieldType::Fixed64 => Value::U64(value.get_fixed64()?),
+            FieldType::Fixed32 => Value::U32(value.get_fixed32()?),
+            FieldType::Bool => Value::Bool(value.get_bool()?),
+            FieldType::String => Value::String(value.get_string()?),
+            FieldType::Group(id) => Value::Message(DynamicMessage::decode(
+                pool,
+                pool.message(id),
+                value.get_group()?,
+            )?),
+            FieldType::Message(id) => Value::Message(DynamicMessage::decode(
+                pool,
+                pool.message(id),
+                value.get_message()?,
+            )?),
+            FieldType::Bytes => Value::Bytes(value.get_bytes()?),
+            FieldType::Uint32 => Value::U32(value.get_uint32()?),
+            FieldType::Enum(_) => Value::Enum(value.get_enum()?),
+            FieldType::Sfixed32 => Value::I32(value.get_sfixed32()?),
+            FieldType::Sfixed64 => Value::I64(value.get_sfixed64()?),
+            FieldType::Sint32 => Value::I32(value.get_sint32()?),
+            FieldType::Sint64 => Value::I64(value.get_sint64()?),
+        })
+    }
+
+    // Returns the default value of a singular field.
+    pub(crate) fn default_for(pool: &'d DescriptorPool, descriptor: &FieldDescriptor) -> Self {
+        match descriptor.field_type() {
+            FieldType::Double => Value::F64(0.0),
+            FieldType::Float => Value::F32(0.0),
+            FieldType::Int64 | FieldType::Sfixed64 | FieldType::Sint64 => Value::I64(0),
+            FieldType::Uint64 | FieldType::Fixed64 => Value::U64(0),
+            FieldType::Int32 | FieldType::Sfixed32 | FieldType::Sint32 => Value::I32(0),
+            FieldType::Uint32 | FieldType::Fixed32 => Value::U32(0),
+            FieldType::Bool => Value::Bool(false),
+            FieldType::String => Value::String(""),
+            FieldType::Bytes => Value::Bytes(&[]),
+            FieldType::Enum(id) => {
+                let values = pool.enum_type(id).values();
+                Value::Enum(values.first().map_or(0, |value| value.number()))
+            }
+            FieldType::Group(id) | FieldType::Message(id) => {
+                Value::Message(DynamicMessage::empty(pool.message(id)))
+            }
+        }
+    }
+}
+
+impl PartialEq for Value<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::I32(a), Value::I32(b)) => a == b,
+            (Value::I64(a), Value::I64(b)) => a == b,
+            (Value::U32(a), Value::U32(b)) => a == b,
+            (Value::U64(a), Value::U64(b)) => a == b,
+            (Value::F32(a), Value::F32(b)) => a == b,
+            (Value::F64(a), Value::F64(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::Enum(a), Value::Enum(b)) => a == b,
+            (Value::Message(a), Value::Message(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+pub(crate) fn wire_type(value: &FieldValue<'_>) -> u8 {
+    match value {
+        FieldValue::Varint(_) => 0,
+        FieldValue::Fixed64(_) => 1,
+        FieldValue::LengthDelimited(_) => 2,
+        FieldValue::StartGroup(_) | FieldValue::Group(_) => 3,
+        FieldValue::EndGroup(_) => 4,
+        FieldValue::Fixed32(_) => 5,
+    }
+}
+
+// Appends the values of a packed or unpacked repeated field of a packable type.
+pub(crate) fn decode_repeated<'a, 'd>(
+    field_type: FieldType,
+    value: &FieldValue<'a>,
+    values: &mut Vec<Value<'a, 'd>>,
+) -> Result<(), Error> {
+    fn extend<'a, 'd, T: PackedScalar>(
+        value: &FieldValue<'a>,
+        values: &mut Vec<Value<'a, 'd>>,
+        f: fn(<T as ProtoScalar>::Value<'a>) -> Value<'a, 'd>,
+    ) -> Result<(), Error> {
+        for v in value.get_repeated::<T>()? {
+            values.push(f(v?));
+        }
+        Ok(())
+    }
+
+    match field_type {
+        FieldType::Double => extend::<Double>(value, values, Value::F64),
+        FieldType::Float => extend::<Float>(value, values, Value::F32),
+        FieldType::Int64 => extend::<Int64>(value, values, Value::I64),
+        FieldType::Uint64 => extend::<Uint64>(value, values, Value::U64),
+        FieldType::Int32 => extend::<Int32>(value, values, Value::I32),
+        FieldType::Fixed64 => extend::<Fixed64>(value, values, Value::U64),
+        FieldType::Fixed32 => extend::<Fixed32>(value, values, Value::U32),
+        FieldType::Bool => extend::<Bool>(value, values, Value::Bool),
+        FieldType::Uint32 => extend::<Uint32>(value, values, Value::U32),
+        FieldType::Enum(_) => extend::<Enum>(value, values, Value::Enum),
+        FieldType::Sfixed32 => extend::<Sfixed32>(value, values, Value::I32),
+        FieldType::Sfixed64 => extend::<Sfixed64>(value, values, Value::I64),
+        FieldType::Sint32 => extend::<Sint32>(value, values, Value::I32),
+        FieldType::Sint64 => extend::<Sint64>(value, values, Value::I64),
+        FieldType::String | FieldType::Bytes | FieldType::Message(_) | FieldType::Group(_) => {
+            unreachable!()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicMessage, Value};
+    use crate::descriptor::tests::test_pool;
+    use crate::message::{DecodeLimits, DecodeOptions, Message};
+    use crate::writer::MessageWriter;
+    use crate::{Error, ErrorKind};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn decode_address_book() {
+        let pool = test_pool();
+        let mut w = MessageWriter::new();
+        let mut person = w.open_message(1);
+        person.add_string(1, "Alice");
+        person.add_int32(2, 7);
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-1234");
+        phone.add_enum(2, 1);
+        phone.close();
+        person.add_string(99, "unknown");
+        person.close();
+        let buf = w.into_vec();
+
+        let descriptor = pool.find_message("tutorial.AddressBook").unwrap();
+        let message = DynamicMessage::decode(&pool, descriptor, Message::new(&buf)).unwrap();
+        let Some(Value::List(people)) = message.get_by_name("people") else {
+            panic!();
+        };
+        let Value::Message(person) = &people[0] else {
+            panic!();
+        };
+        assert_eq!(person.get(1), Some(&Value::String("Alice")));
+        assert_eq!(person.get_by_name("id"), Some(&Value::I32(7)));
+        assert!(person.get(3).is_none());
+        let Some(Value::List(phones)) = person.get(4) else {
+            panic!();
+        };
+        let Value::Message(phone) = &phones[0] else {
+            panic!();
+        };
+        assert_eq!(phone.get_by_name("type"), Some(&Value::Enum(1)));
+        assert_eq!(person.unknown_fields().len(), 1);
+        assert_eq!(person.unknown_fields()[0].number, 99);
+        let names = person.fields().map(|(field, _)| field.name());
+        assert!(names.eq(["name", "id", "phones"]));
+    }
+
+    #[test]
+    fn decode_test() {
+        let pool = test_pool();
+        let mut w = MessageWriter::new();
+        for (key, value) in [("a", 1), ("b", 2), ("a", 3)] {
+            let mut entry = w.open_message(1);
+            entry.add_string(1, key);
+            entry.add_int32(2, value);
+            entry.close();
+        }
+        let mut entry = w.open_message(1);
+        entry.add_string(1, "c");
+        entry.close();
+        w.add_packed_int32(4, [1, 2]);
+        w.add_int32(4, 3);
+        w.add_sint64(5, -1);
+        w.add_packed_sint64(5, [-2, -3]);
+        let mut child = w.open_message(9);
+        child.add_double(8, 0.5);
+        child.close();
+        let mut child = w.open_message(9);
+        child.add_string(2, "text");
+        child.close();
+        // A field with the wrong wire type.
+        w.add_fixed32(8, 1);
+        w.add_string(100, "note");
+        // optional group Item = 6 { optional bytes data = 7; }
+        let buf = [w.as_bytes(), b"\x33\x3a\x01x\x34"].concat();
+
+        let descriptor = pool.find_message("test.Test").unwrap();
+        let message = DynamicMessage::decode(&pool, descriptor, Message::new(&buf)).unwrap();
+        assert_eq!(
+            message.get(1),
+            Some(&Value::Map(vec![
+                (Value::String("a"), Value::I32(3)),
+                (Value::String("b"), Value::I32(2)),
+                (Value::String("c"), Value::I32(0)),
+            ]))
+        );
+        assert_eq!(
+            message.get(4),
+            Some(&Value::List(vec![
+                Value::I32(1),
+                Value::I32(2),
+                Value::I32(3)
+            ]))
+        );
+        assert_eq!(
+            message.get(5),
+            Some(&Value::List(vec![
+                Value::I64(-1),
+                Value::I64(-2),
+                Value::I64(-3)
+            ]))
+        );
+        let Some(Value::Message(child)) = message.get(9) else {
+            panic!();
+        };
+        assert_eq!(child.get(8), Some(&Value::F64(0.5)));
+        assert_eq!(child.get(2), Some(&Value::String("text")));
+        assert!(message.get(8).is_none());
+        assert_eq!(message.unknown_fields()[0].number, 8);
+        assert_eq!(
+            message.get_by_name("test.note"),
+            Some(&Value::String("note"))
+        );
+        let Some(Value::Message(item)) = message.get(6) else {
+            panic!();
+        };
+        assert_eq!(item.get(7), Some(&Value::Bytes(b"x")));
+
+        // A later member of a oneof replaces an earlier one.
+        let mut w = MessageWriter::new();
+        w.add_string(2, "text");
+        w.add_message(3, Message::new(b"\x0a\x05Alice"));
+        let message =
+            DynamicMessage::decode(&pool, descriptor, Message::new(w.as_bytes())).unwrap();
+        assert!(message.get(2).is_none());
+        assert!(message.get(3).is_some());
+        w.add_string(2, "text");
+        let message =
+            DynamicMessage::decode(&pool, descriptor, Message::new(w.as_bytes())).unwrap();
+        assert_eq!(message.get(2), Some(&Value::String("text")));
+        assert!(message.get(3).is_none());
+
+        // Decoding errors are reported.
+        let buf = b"\x4a\x02\x08";
+        assert!(DynamicMessage::decode(&pool, descriptor, Message::new(buf)).is_err());
+
+        // Messages nested too deeply are an error, whatever the limits of the message.
+        let mut buf = Vec::new();
+        for _ in 0..200 {
+            let mut w = MessageWriter::new();
+            w.add_bytes(9, &buf);
+            buf = w.into_vec();
+        }
+        let limits = DecodeLimits::new().max_depth(u32::MAX);
+        let message = Message::with_options(&buf, DecodeOptions::new().limits(limits));
+        assert_eq!(
+            DynamicMessage::decode(&pool, descriptor, message).err(),
+            Some(Error::new(ErrorKind::DepthLimitExceeded, 303))
+        );
+    }
+}
@@ -1,11 +1,26 @@
-use crate::Error;
+use crate::writer::OutOfSpace;
+use crate::{Error, ErrorKind};
 
-pub(crate) const VARINT_MAX_LEN: usize = 10;
+/// The maximum length of a varint in bytes.
+pub const VARINT_MAX_LEN: usize = 10;
+
+pub(crate) const WIRE_TYPE_VARINT: u8 = 0;
+pub(crate) const WIRE_TYPE_FIXED64: u8 = 1;
+pub(crate) const WIRE_TYPE_LENGTH_DELIMITED: u8 = 2;
+#[cfg(feature = "alloc")]
+pub(crate) const WIRE_TYPE_START_GROUP: u8 = 3;
+#[cfg(feature = "alloc")]
+pub(crate) const WIRE_TYPE_END_GROUP: u8 = 4;
+pub(crate) const WIRE_TYPE_FIXED32: u8 = 5;
 
 /// Decodes a varint from a slice, returning the remainder of the slice and the value.
+///
+/// If the slice ends before the varint does, the varint is longer than [`VARINT_MAX_LEN`] bytes,
+/// or the 10th byte is greater than `0x01` (the value overflows 64 bits), `Err` is returned. The
+/// offset of the error is relative to the start of `buf`.
 #[allow(clippy::get_first)]
 #[inline]
-pub(crate) fn read_varint(buf: &[u8]) -> Result<(&[u8], u64), Error> {
+pub fn read_varint(buf: &[u8]) -> Result<(&[u8], u64), Error> {
     if let Some(&byte) = buf.get(0) {
         if byte <= 0x7f {
             return Ok((&buf[1..], byte as u64));
@@ -31,26 +46,141 @@ fn read_varint_loop(buf: &[u8]) -> Result<(&[u8], u64), Error> {
         }
         index += 1;
     }
-    Err(Error)
+    Err(varint_error(buf))
+}
+
+/// Decodes a varint like [`read_varint`], but rejects varints encoded with more bytes than
+/// necessary.
+#[inline]
+pub(crate) fn read_varint_canonical(buf: &[u8]) -> Result<(&[u8], u64), Error> {
+    let (rest, value) = read_varint(buf)?;
+    let len = buf.len() - rest.len();
+    if len > 1 && buf[len - 1] == 0 {
+        return Err(Error::new(ErrorKind::NonCanonicalVarint, 0));
+    }
+    Ok((rest, value))
+}
+
+#[cold]
+fn varint_error(buf: &[u8]) -> Error {
+    if buf.len() < VARINT_MAX_LEN && buf.iter().all(|&byte| byte > 0x7f) {
+        Error::new(ErrorKind::TruncatedVarint, 0)
+    } else {
+        Error::new(ErrorKind::VarintOverflow, 0)
+    }
+}
+
+/// Decodes a varint from a slice as a 32-bit value, returning the remainder of the slice and the
+/// value.
+///
+/// Bits above the low 32 bits are discarded, so a sign-extended negative `int32` decodes to its
+/// two's complement representation. The same validation as [`read_varint`] applies.
+#[allow(clippy::get_first)]
+#[inline]
+pub fn read_varint32(buf: &[u8]) -> Result<(&[u8], u32), Error> {
+    if let Some(&byte) = buf.get(0) {
+        if byte <= 0x7f {
+            return Ok((&buf[1..], byte as u32));
+        }
+    }
+    read_varint32_loop(buf)
+}
+
+#[allow(clippy::int_plus_one)]
+fn read_varint32_loop(buf: &[u8]) -> Result<(&[u8], u32), Error> {
+    let mut index = 0;
+    let mut value = 0;
+    while index < VARINT_MAX_LEN && index + 1 <= buf.len() {
+        let byte = buf[index];
+        // Only the first five bytes contribute to the low 32 bits.
+        if index < 5 {
+            value |= ((byte & 0x7f) as u32) << (index * 7);
+        }
+        if byte <= 0x7f {
+            if index + 1 == VARINT_MAX_LEN && byte > 0x01 {
+                break;
+            }
+            return Ok((&buf[index + 1..], value));
+        }
+        index += 1;
+    }
+    Err(varint_error(buf))
+}
+
+/// Encodes a varint into a slice, returning the remainder of the slice.
+///
+/// If the slice is too short, [`OutOfSpace`] is returned and the slice is left unchanged.
+#[inline]
+pub fn write_varint(buf: &mut [u8], value: u64) -> Result<&mut [u8], OutOfSpace> {
+    let mut bytes = [0; VARINT_MAX_LEN];
+    let len = encode_varint(value, &mut bytes);
+    if buf.len() < len {
+        return Err(OutOfSpace);
+    }
+    let (head, tail) = buf.split_at_mut(len);
+    head.copy_from_slice(&bytes[..len]);
+    Ok(tail)
+}
+
+/// Encodes a varint into a buffer, returning the number of bytes written.
+#[inline]
+pub(crate) fn encode_varint(mut value: u64, buf: &mut [u8; VARINT_MAX_LEN]) -> usize {
+    let mut index = 0;
+    while value > 0x7f {
+        buf[index] = (value as u8) | 0x80;
+        value >>= 7;
+        index += 1;
+    }
+    buf[index] = value as u8;
+    index + 1
+}
+
+/// Returns the number of bytes needed to encode a varint.
+#[inline]
+pub(crate) fn varint_len(value: u64) -> usize {
+    // Each byte holds 7 bits. `value | 1` makes zero take one byte.
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
 }
 
-pub(crate) mod zigzag {
+/// [ZigZag] encoding of signed integers, as used by `sint32` and `sint64` fields.
+///
+/// [ZigZag]: https://developers.google.com/protocol-buffers/docs/encoding#signed-ints
+pub mod zigzag {
+    /// Encodes a 32-bit signed integer.
     #[inline]
-    pub(crate) fn decode_32(n: u32) -> i32 {
+    pub fn encode_32(n: i32) -> u32 {
+        ((n << 1) ^ (n >> 31)) as u32
+    }
+
+    /// Encodes a 64-bit signed integer.
+    #[inline]
+    pub fn encode_64(n: i64) -> u64 {
+        ((n << 1) ^ (n >> 63)) as u64
+    }
+
+    /// Decodes a 32-bit signed integer.
+    #[inline]
+    pub fn decode_32(n: u32) -> i32 {
         (n >> 1) as i32 ^ -((n & 1) as i32)
     }
 
+    /// Decodes a 64-bit signed integer.
     #[inline]
-    pub(crate) fn decode_64(n: u64) -> i64 {
+    pub fn decode_64(n: u64) -> i64 {
         (n >> 1) as i64 ^ -((n & 1) as i64)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::encode_varint;
     use super::read_varint;
+    use super::read_varint32;
+    use super::varint_len;
+    use super::write_varint;
     use super::zigzag;
-    use crate::Error;
+    use crate::writer::OutOfSpace;
+    use crate::{Error, ErrorKind};
     use core::ptr;
 
     #[test]
@@ -83,16 +213,17 @@ mod tests {
 
     #[test]
     fn read_varint_invalid() {
-        assert_eq!(read_varint(&b""[..]), Err(Error));
-        assert_eq!(read_varint(&b"\xf0\xab"[..]), Err(Error));
-        assert_eq!(read_varint(&b"\xf0\xab\xc9\x9a\xf8\xb2"[..]), Err(Error));
+        let truncated = Err(Error::new(ErrorKind::TruncatedVarint, 0));
+        assert_eq!(read_varint(&b""[..]), truncated);
+        assert_eq!(read_varint(&b"\xf0\xab"[..]), truncated);
+        assert_eq!(read_varint(&b"\xf0\xab\xc9\x9a\xf8\xb2"[..]), truncated);
     }
 
     #[test]
     fn read_varint_overflow() {
         assert_eq!(
             read_varint(&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x02"[..]),
-            Err(Error)
+            Err(Error::new(ErrorKind::VarintOverflow, 0))
         );
     }
 
@@ -100,10 +231,92 @@ mod tests {
     fn read_varint_too_many_bytes() {
         assert_eq!(
             read_varint(&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x80\x00"[..]),
-            Err(Error)
+            Err(Error::new(ErrorKind::VarintOverflow, 0))
+        );
+    }
+
+    #[test]
+    fn read_varint32_ok() {
+        for (input, (expected_value, len)) in [
+            (&b"\x00"[..], (0, 1)),
+            (&b"\x7f"[..], (127, 1)),
+            (&b"\xa2\x74"[..], (14882, 2)),
+            (&b"\xbe\xf7\x92\x84\x0b"[..], (2961488830, 5)),
+            (&b"\xbe\xf7\x92\x84\x1b"[..], (2961488830, 5)),
+            (
+                &b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"[..],
+                (0xffffffff, 10),
+            ),
+        ] {
+            let (buf, value) = read_varint32(input).unwrap();
+            assert!(ptr::eq(buf, &input[len..]));
+            assert_eq!(value, expected_value);
+        }
+    }
+
+    #[test]
+    fn read_varint32_invalid() {
+        let truncated = Err(Error::new(ErrorKind::TruncatedVarint, 0));
+        assert_eq!(read_varint32(&b""[..]), truncated);
+        assert_eq!(read_varint32(&b"\xf0\xab"[..]), truncated);
+        assert_eq!(
+            read_varint32(&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x02"[..]),
+            Err(Error::new(ErrorKind::VarintOverflow, 0))
+        );
+        assert_eq!(
+            read_varint32(&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x80\x00"[..]),
+            Err(Error::new(ErrorKind::VarintOverflow, 0))
         );
     }
 
+    #[test]
+    fn write_varint_ok() {
+        let mut buf = [0; 4];
+        let rest = write_varint(&mut buf, 14882).unwrap();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(&buf[..2], b"\xa2\x74");
+    }
+
+    #[test]
+    fn write_varint_out_of_space() {
+        let mut buf = [0; 9];
+        assert_eq!(write_varint(&mut buf, u64::MAX), Err(OutOfSpace));
+        assert_eq!(buf, [0; 9]);
+    }
+
+    #[test]
+    fn encode_varint_round_trip() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            14882,
+            2961488830,
+            41256202580718336,
+            0xffffffffffffffff,
+        ] {
+            let mut buf = [0; 10];
+            let len = encode_varint(value, &mut buf);
+            assert_eq!(varint_len(value), len);
+            let (rest, decoded) = read_varint(&buf[..len]).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(decoded, value);
+        }
+    }
+
+    #[test]
+    fn zigzag_encode() {
+        for n in [0, 1, -1, 2, -2, i32::MAX, i32::MIN] {
+            assert_eq!(zigzag::decode_32(zigzag::encode_32(n)), n);
+        }
+        for n in [0, 1, -1, 2, -2, i64::MAX, i64::MIN] {
+            assert_eq!(zigzag::decode_64(zigzag::encode_64(n)), n);
+        }
+        assert_eq!(zigzag::encode_32(-1), 1);
+        assert_eq!(zigzag::encode_64(1), 2);
+    }
+
     #[test]
     fn zigzag_decode_32() {
         assert_eq!(zigzag::decode_32(0), 0);
@@ -0,0 +1,119 @@
+use core::fmt;
+
+/// The error type.
+///
+/// An `Error` records what went wrong and the byte offset at which it went wrong. Offsets are
+/// relative to the start of the buffer the top-level [`Message`][crate::Message] was created
+/// from, including for errors found in nested messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+impl Error {
+    /// Creates a new [`Error`] of the given kind at the given byte offset.
+    #[inline]
+    pub fn new(kind: ErrorKind, offset: usize) -> Self {
+        Self { kind, offset }
+    }
+
+    /// Returns the kind of error.
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the byte offset at which the error occurred.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Shifts an offset relative to a sub-slice into an offset relative to the enclosing buffer.
+    #[inline]
+    pub(crate) fn offset_by(self, base: usize) -> Self {
+        Self {
+            kind: self.kind,
+            offset: base + self.offset,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The kind of an [`Error`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A varint extends past the end of the buffer.
+    TruncatedVarint,
+    /// A varint is longer than ten bytes or overflows 64 bits.
+    VarintOverflow,
+    /// A field has the illegal field number 0.
+    InvalidFieldNumber,
+    /// A field has an unknown wire type (6 or 7).
+    InvalidWireType,
+    /// A fixed-width value extends past the end of the buffer.
+    TruncatedFixed,
+    /// The length of a length-delimited value extends past the end of the buffer.
+    LengthOverrun,
+    /// A `string` value is not valid UTF-8.
+    InvalidUtf8,
+    /// The wire type of a field is not compatible with the requested type.
+    WireTypeMismatch,
+    /// A start group has no matching end group.
+    UnterminatedGroup,
+    /// An end group does not match the field number of the open group, or no group is open.
+    MismatchedEndGroup,
+    /// A field number is greater than the largest valid field number.
+    FieldNumberOutOfRange,
+    /// A field number is in the range reserved for the protobuf implementation.
+    ReservedFieldNumber,
+    /// A varint is encoded with more bytes than necessary.
+    NonCanonicalVarint,
+    /// A message is nested deeper than the [maximum depth][crate::message::DecodeLimits::max_depth].
+    DepthLimitExceeded,
+    /// A message has more fields than its
+    /// [field budget][crate::message::Message::with_field_budget], or than fit in the table of a
+    /// [`FieldIndex`][crate::index::FieldIndex].
+    FieldLimitExceeded,
+    /// A message is larger than the [maximum size][crate::message::DecodeLimits::max_size].
+    SizeLimitExceeded,
+    /// A value is out of range for the requested type.
+    ValueOutOfRange,
+    /// A query path has more segments than [`PATH_MAX_LEN`][crate::query::PATH_MAX_LEN].
+    PathTooLong,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::TruncatedVarint => "truncated varint",
+            ErrorKind::VarintOverflow => "varint overflow",
+            ErrorKind::InvalidFieldNumber => "invalid field number",
+            ErrorKind::InvalidWireType => "invalid wire type",
+            ErrorKind::TruncatedFixed => "truncated fixed-width value",
+            ErrorKind::LengthOverrun => "length exceeds buffer",
+            ErrorKind::InvalidUtf8 => "invalid UTF-8",
+            ErrorKind::WireTypeMismatch => "wire type mismatch",
+            ErrorKind::UnterminatedGroup => "unterminated group",
+            ErrorKind::MismatchedEndGroup => "mismatched end group",
+            ErrorKind::FieldNumberOutOfRange => "field number out of range",
+            ErrorKind::ReservedFieldNumber => "reserved field number",
+            ErrorKind::NonCanonicalVarint => "non-canonical varint",
+            ErrorKind::DepthLimitExceeded => "depth limit exceeded",
+            ErrorKind::FieldLimitExceeded => "field limit exceeded",
+            ErrorKind::SizeLimitExceeded => "size limit exceeded",
+            ErrorKind::ValueOutOfRange => "value out of range",
+            ErrorKind::PathTooLong => "query path too long",
+        })
+    }
+}
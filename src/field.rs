@@ -1,8 +1,11 @@
 use crate::encoding::read_varint;
 use crate::encoding::zigzag;
-use crate::message::Message;
-use crate::Error;
+use crate::message::{DecodeOptions, Message};
+use crate::types::{PackedScalar, ProtoScalar};
+use crate::{Error, ErrorKind};
+use core::cell::Cell;
 use core::iter::FusedIterator;
+use core::ops::Range;
 use core::str;
 
 /// Instance of a message field.
@@ -11,7 +14,7 @@ use core::str;
 /// of a message. See [Message Structure].
 ///
 /// [Message Structure]: https://developers.google.com/protocol-buffers/docs/encoding#structure
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Field<'a> {
     /// The field number.
     pub number: u64,
@@ -19,12 +22,68 @@ pub struct Field<'a> {
     pub value: FieldValue<'a>,
 }
 
+/// A field together with the location of its encoding.
+///
+/// Offsets are relative to the start of the buffer the top-level [`Message`] was created from.
+/// The encoding of a field is made up of the tag, the length prefix of length-delimited values
+/// and the payload. For a group decoded as a whole, the payload is the group body and the
+/// encoding includes the end group marker.
+#[derive(Debug)]
+pub struct RawField<'a> {
+    /// The decoded field.
+    pub field: Field<'a>,
+    pub(crate) raw: &'a [u8],
+    pub(crate) offset: usize,
+    pub(crate) tag_len: usize,
+    pub(crate) payload_start: usize,
+    pub(crate) payload_end: usize,
+}
+
+impl<'a> RawField<'a> {
+    /// Returns the bytes of the encoded field.
+    #[inline]
+    pub fn raw_bytes(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// Returns the byte range of the encoded field.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.raw.len()
+    }
+
+    /// Returns the byte range of the tag.
+    #[inline]
+    pub fn tag_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.tag_len
+    }
+
+    /// Returns the byte range of the length prefix, or `None` if the value is not
+    /// length-delimited.
+    #[inline]
+    pub fn length_range(&self) -> Option<Range<usize>> {
+        match self.field.value {
+            FieldValue::LengthDelimited(_) => Some(self.offset + self.tag_len..self.payload_start),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte range of the payload.
+    ///
+    /// The payload of a start or end group marker is empty.
+    #[inline]
+    pub fn payload_range(&self) -> Range<usize> {
+        self.payload_start..self.payload_end
+    }
+}
+
 /// Value of a field.
 ///
 /// Each enum variant corresponds to a [wire type][Message Structure].
 ///
 /// [Message Structure]: https://developers.google.com/protocol-buffers/docs/encoding#structure
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
 pub enum FieldValue<'a> {
     /// A varint value.
     Varint(Varint),
@@ -32,15 +91,32 @@ pub enum FieldValue<'a> {
     Fixed64(Fixed64),
     /// A length-delimited value.
     LengthDelimited(LengthDelimited<'a>),
-    /// Start of a group.
-    StartGroup,
-    /// End of a group.
-    EndGroup,
+    /// Start of a group, with the byte offset of its tag.
+    StartGroup(usize),
+    /// End of a group, with the byte offset of its tag.
+    EndGroup(usize),
     /// A non-varint 32-bit number.
     Fixed32(Fixed32),
+    /// A whole group.
+    ///
+    /// Groups are only returned as a whole if enabled with [`DecodeOptions::groups`].
+    Group(Message<'a>),
 }
 
 impl<'a> FieldValue<'a> {
+    #[cold]
+    fn mismatch(&self) -> Error {
+        let offset = match self {
+            FieldValue::Varint(f) => f.offset,
+            FieldValue::Fixed64(f) => f.offset,
+            FieldValue::LengthDelimited(f) => f.offset,
+            FieldValue::StartGroup(offset) | FieldValue::EndGroup(offset) => *offset,
+            FieldValue::Fixed32(f) => f.offset,
+            FieldValue::Group(m) => m.offset,
+        };
+        Error::new(ErrorKind::WireTypeMismatch, offset)
+    }
+
     /// Returns the value of a `bool` field.
     ///
     /// If the wire type is not compatible, `Err` is returned.
@@ -48,7 +124,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_bool(&self) -> Result<bool, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_bool()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -59,7 +135,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_enum(&self) -> Result<i32, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_enum()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -70,7 +146,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_int32(&self) -> Result<i32, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_int32()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -81,7 +157,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_int64(&self) -> Result<i64, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_int64()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -92,7 +168,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_sint32(&self) -> Result<i32, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_sint32()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -103,7 +179,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_sint64(&self) -> Result<i64, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_sint64()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -114,7 +190,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_uint32(&self) -> Result<u32, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_uint32()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -125,7 +201,67 @@ impl<'a> FieldValue<'a> {
     pub fn get_uint64(&self) -> Result<u64, Error> {
         match self {
             FieldValue::Varint(f) => Ok(f.get_uint64()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
+        }
+    }
+
+    /// Returns the value of a `bool` field, checking that it is in range.
+    ///
+    /// If the wire type is not compatible, or the value is out of range, `Err` is returned. See
+    /// [`Varint::try_get_bool`].
+    #[inline]
+    pub fn try_get_bool(&self) -> Result<bool, Error> {
+        match self {
+            FieldValue::Varint(f) => f.try_get_bool(),
+            _ => Err(self.mismatch()),
+        }
+    }
+
+    /// Returns the value of an `enum` field, checking that it is in range.
+    ///
+    /// If the wire type is not compatible, or the value is out of range, `Err` is returned. See
+    /// [`Varint::try_get_enum`].
+    #[inline]
+    pub fn try_get_enum(&self) -> Result<i32, Error> {
+        match self {
+            FieldValue::Varint(f) => f.try_get_enum(),
+            _ => Err(self.mismatch()),
+        }
+    }
+
+    /// Returns the value of an `int32` field, checking that it is in range.
+    ///
+    /// If the wire type is not compatible, or the value is out of range, `Err` is returned. See
+    /// [`Varint::try_get_int32`].
+    #[inline]
+    pub fn try_get_int32(&self) -> Result<i32, Error> {
+        match self {
+            FieldValue::Varint(f) => f.try_get_int32(),
+            _ => Err(self.mismatch()),
+        }
+    }
+
+    /// Returns the value of a `sint32` field, checking that it is in range.
+    ///
+    /// If the wire type is not compatible, or the value is out of range, `Err` is returned. See
+    /// [`Varint::try_get_sint32`].
+    #[inline]
+    pub fn try_get_sint32(&self) -> Result<i32, Error> {
+        match self {
+            FieldValue::Varint(f) => f.try_get_sint32(),
+            _ => Err(self.mismatch()),
+        }
+    }
+
+    /// Returns the value of an `uint32` field, checking that it is in range.
+    ///
+    /// If the wire type is not compatible, or the value is out of range, `Err` is returned. See
+    /// [`Varint::try_get_uint32`].
+    #[inline]
+    pub fn try_get_uint32(&self) -> Result<u32, Error> {
+        match self {
+            FieldValue::Varint(f) => f.try_get_uint32(),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -136,7 +272,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_fixed64(&self) -> Result<u64, Error> {
         match self {
             FieldValue::Fixed64(f) => Ok(f.get_fixed64()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -147,7 +283,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_sfixed64(&self) -> Result<i64, Error> {
         match self {
             FieldValue::Fixed64(f) => Ok(f.get_sfixed64()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -158,7 +294,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_double(&self) -> Result<f64, Error> {
         match self {
             FieldValue::Fixed64(f) => Ok(f.get_double()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -169,18 +305,32 @@ impl<'a> FieldValue<'a> {
     pub fn get_bytes(&self) -> Result<&'a [u8], Error> {
         match self {
             FieldValue::LengthDelimited(f) => Ok(f.get_bytes()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
     /// Returns the value of a `message` field.
     ///
+    /// A message encoded as a [`Group`][FieldValue::Group] is also accepted.
+    ///
     /// If the wire type is not compatible, `Err` is returned.
     #[inline]
     pub fn get_message(&self) -> Result<Message<'a>, Error> {
         match self {
             FieldValue::LengthDelimited(f) => Ok(f.get_message()),
-            _ => Err(Error),
+            FieldValue::Group(m) => Ok(*m),
+            _ => Err(self.mismatch()),
+        }
+    }
+
+    /// Returns the value of a `group` field.
+    ///
+    /// If the value is not a [`Group`][FieldValue::Group], `Err` is returned.
+    #[inline]
+    pub fn get_group(&self) -> Result<Message<'a>, Error> {
+        match self {
+            FieldValue::Group(m) => Ok(*m),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -191,7 +341,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_string(&self) -> Result<&'a str, Error> {
         match self {
             FieldValue::LengthDelimited(f) => f.get_string(),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -202,7 +352,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_fixed32(&self) -> Result<u32, Error> {
         match self {
             FieldValue::Fixed32(f) => Ok(f.get_fixed32()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -213,7 +363,7 @@ impl<'a> FieldValue<'a> {
     pub fn get_sfixed32(&self) -> Result<i32, Error> {
         match self {
             FieldValue::Fixed32(f) => Ok(f.get_sfixed32()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -224,10 +374,30 @@ impl<'a> FieldValue<'a> {
     pub fn get_float(&self) -> Result<f32, Error> {
         match self {
             FieldValue::Fixed32(f) => Ok(f.get_float()),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
+    /// Returns the value of a field of type `T`.
+    ///
+    /// `T` is one of the marker types in [`types`][crate::types], for example
+    /// `value.get::<Sint64>()`. If the wire type is not compatible, `Err` is returned.
+    #[inline]
+    pub fn get<T: ProtoScalar>(&self) -> Result<T::Value<'a>, Error> {
+        T::get(self)
+    }
+
+    /// Returns an iterator over values of a repeated field of type `T`.
+    ///
+    /// `T` is one of the marker types of the numeric types in [`types`][crate::types], for example
+    /// `value.get_repeated::<Fixed32>()`. If the wire type is not compatible, `Err` is returned.
+    #[inline]
+    pub fn get_repeated<T: PackedScalar>(
+        &self,
+    ) -> Result<Repeated<T::Value<'a>, T::Packed<'a>>, Error> {
+        T::get_repeated(self)
+    }
+
     /// Returns an iterator over values of a repeated `bool` field.
     ///
     /// If the wire type is not compatible, `Err` is returned.
@@ -236,7 +406,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_bool()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_bool())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -248,7 +418,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_enum()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_enum())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -260,7 +430,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_int32()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_int32())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -272,7 +442,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_int64()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_int64())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -284,7 +454,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_sint32()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_sint32())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -296,7 +466,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_sint64()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_sint64())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -308,7 +478,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_uint32()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_uint32())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -320,7 +490,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Varint(f) => Ok(Repeated::Value(Some(f.get_uint64()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_uint64())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -332,7 +502,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Fixed64(f) => Ok(Repeated::Value(Some(f.get_fixed64()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_fixed64())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -344,7 +514,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Fixed64(f) => Ok(Repeated::Value(Some(f.get_sfixed64()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_sfixed64())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -356,7 +526,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::Fixed64(f) => Ok(Repeated::Value(Some(f.get_double()))),
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_double())),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -368,7 +538,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_fixed32())),
             FieldValue::Fixed32(f) => Ok(Repeated::Value(Some(f.get_fixed32()))),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -380,7 +550,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_sfixed32())),
             FieldValue::Fixed32(f) => Ok(Repeated::Value(Some(f.get_sfixed32()))),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 
@@ -392,7 +562,7 @@ impl<'a> FieldValue<'a> {
         match self {
             FieldValue::LengthDelimited(f) => Ok(Repeated::Packed(f.get_packed_float())),
             FieldValue::Fixed32(f) => Ok(Repeated::Value(Some(f.get_float()))),
-            _ => Err(Error),
+            _ => Err(self.mismatch()),
         }
     }
 }
@@ -405,6 +575,7 @@ impl<'a> FieldValue<'a> {
 #[derive(Clone, Copy, Debug)]
 pub struct Fixed32 {
     pub(crate) bytes: [u8; 4],
+    pub(crate) offset: usize,
 }
 
 impl Fixed32 {
@@ -435,6 +606,7 @@ impl Fixed32 {
 #[derive(Clone, Copy, Debug)]
 pub struct Fixed64 {
     pub(crate) bytes: [u8; 8],
+    pub(crate) offset: usize,
 }
 
 impl Fixed64 {
@@ -461,6 +633,12 @@ impl Fixed64 {
 #[derive(Clone, Copy, Debug)]
 pub struct LengthDelimited<'a> {
     pub(crate) buf: &'a [u8],
+    pub(crate) offset: usize,
+    pub(crate) options: DecodeOptions,
+    // Nesting depth of the value as a message.
+    pub(crate) depth: u32,
+    // Number of fields left to decode, shared with the message holding the value.
+    pub(crate) budget: Option<&'a Cell<usize>>,
 }
 
 impl<'a> LengthDelimited<'a> {
@@ -473,7 +651,13 @@ impl<'a> LengthDelimited<'a> {
     /// Returns the value of a `message` field.
     #[inline]
     pub fn get_message(&self) -> Message<'a> {
-        Message::new(self.buf)
+        Message {
+            buf: self.buf,
+            offset: self.offset,
+            options: self.options,
+            depth: self.depth,
+            budget: self.budget,
+        }
     }
 
     /// Returns the value of a `string` field.
@@ -481,91 +665,134 @@ impl<'a> LengthDelimited<'a> {
     /// If the string is not valid UTF-8, `Err` is returned.
     #[inline]
     pub fn get_string(&self) -> Result<&'a str, Error> {
-        str::from_utf8(self.buf).map_err(|_| Error)
+        str::from_utf8(self.buf)
+            .map_err(|e| Error::new(ErrorKind::InvalidUtf8, self.offset + e.valid_up_to()))
     }
 
     /// Returns an iterator over packed repeated `bool`s.
     #[inline]
     pub fn get_packed_bool(&self) -> PackedBool<'a> {
-        PackedBool { buf: self.buf }
+        PackedBool {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `enum`s.
     #[inline]
     pub fn get_packed_enum(&self) -> PackedEnum<'a> {
-        PackedEnum { buf: self.buf }
+        PackedEnum {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `int32`s.
     #[inline]
     pub fn get_packed_int32(&self) -> PackedInt32<'a> {
-        PackedInt32 { buf: self.buf }
+        PackedInt32 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `int64`s.
     #[inline]
     pub fn get_packed_int64(&self) -> PackedInt64<'a> {
-        PackedInt64 { buf: self.buf }
+        PackedInt64 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `sint32`s.
     #[inline]
     pub fn get_packed_sint32(&self) -> PackedSint32<'a> {
-        PackedSint32 { buf: self.buf }
+        PackedSint32 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `sint64`s.
     #[inline]
     pub fn get_packed_sint64(&self) -> PackedSint64<'a> {
-        PackedSint64 { buf: self.buf }
+        PackedSint64 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `uint32`s.
     #[inline]
     pub fn get_packed_uint32(&self) -> PackedUint32<'a> {
-        PackedUint32 { buf: self.buf }
+        PackedUint32 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `uint64`s.
     #[inline]
     pub fn get_packed_uint64(&self) -> PackedUint64<'a> {
-        PackedUint64 { buf: self.buf }
+        PackedUint64 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `fixed64`s.
     #[inline]
     pub fn get_packed_fixed64(&self) -> PackedFixed64<'a> {
-        PackedFixed64 { buf: self.buf }
+        PackedFixed64 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `sfixed64`s.
     #[inline]
     pub fn get_packed_sfixed64(&self) -> PackedSfixed64<'a> {
-        PackedSfixed64 { buf: self.buf }
+        PackedSfixed64 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `double`s.
     #[inline]
     pub fn get_packed_double(&self) -> PackedDouble<'a> {
-        PackedDouble { buf: self.buf }
+        PackedDouble {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `fixed32`s.
     #[inline]
     pub fn get_packed_fixed32(&self) -> PackedFixed32<'a> {
-        PackedFixed32 { buf: self.buf }
+        PackedFixed32 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `sfixed32`s.
     #[inline]
     pub fn get_packed_sfixed32(&self) -> PackedSfixed32<'a> {
-        PackedSfixed32 { buf: self.buf }
+        PackedSfixed32 {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     /// Returns an iterator over packed repeated `float`s.
     #[inline]
     pub fn get_packed_float(&self) -> PackedFloat<'a> {
-        PackedFloat { buf: self.buf }
+        PackedFloat {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 }
 
@@ -578,6 +805,7 @@ impl<'a> LengthDelimited<'a> {
 #[derive(Clone, Copy, Debug)]
 pub struct Varint {
     pub(crate) value: u64,
+    pub(crate) offset: usize,
 }
 
 impl Varint {
@@ -628,6 +856,52 @@ impl Varint {
     pub fn get_uint64(&self) -> u64 {
         self.value
     }
+
+    #[cold]
+    fn out_of_range(&self) -> Error {
+        Error::new(ErrorKind::ValueOutOfRange, self.offset)
+    }
+
+    /// Returns the value of a `bool` field, checking that it is 0 or 1.
+    #[inline]
+    pub fn try_get_bool(&self) -> Result<bool, Error> {
+        match self.value {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(self.out_of_range()),
+        }
+    }
+
+    /// Returns the value of an `enum` field, checking that it is in range.
+    ///
+    /// Enum values are encoded like `int32` values. See [`try_get_int32`][Varint::try_get_int32].
+    #[inline]
+    pub fn try_get_enum(&self) -> Result<i32, Error> {
+        self.try_get_int32()
+    }
+
+    /// Returns the value of an `int32` field, checking that it is in range.
+    ///
+    /// Negative `int32` values are sign-extended to 64 bits when encoded, so the value must be in
+    /// the range of `i32` when read as an `i64`. A negative value encoded without sign extension
+    /// (as a 32-bit two's complement number) is out of range.
+    #[inline]
+    pub fn try_get_int32(&self) -> Result<i32, Error> {
+        i32::try_from(self.value as i64).map_err(|_| self.out_of_range())
+    }
+
+    /// Returns the value of a `sint32` field, checking that it is in range.
+    #[inline]
+    pub fn try_get_sint32(&self) -> Result<i32, Error> {
+        let value = u32::try_from(self.value).map_err(|_| self.out_of_range())?;
+        Ok(zigzag::decode_32(value))
+    }
+
+    /// Returns the value of a `uint32` field, checking that it is in range.
+    #[inline]
+    pub fn try_get_uint32(&self) -> Result<u32, Error> {
+        u32::try_from(self.value).map_err(|_| self.out_of_range())
+    }
 }
 
 macro_rules! impl_packed {
@@ -636,6 +910,7 @@ macro_rules! impl_packed {
         #[derive(Clone, Debug, Default)]
         pub struct $name<'a> {
             buf: &'a [u8],
+            offset: usize,
         }
 
         impl<'a> Iterator for $name<'a> {
@@ -647,10 +922,12 @@ macro_rules! impl_packed {
                 }
                 match read_varint(self.buf) {
                     Ok((buf, value)) => {
+                        let offset = self.offset;
+                        self.offset += self.buf.len() - buf.len();
                         self.buf = buf;
-                        Some(Ok(Varint { value }.$get_fn()))
+                        Some(Ok(Varint { value, offset }.$get_fn()))
                     }
-                    Err(e) => Some(Err(e)),
+                    Err(e) => Some(Err(e.offset_by(self.offset))),
                 }
             }
         }
@@ -662,6 +939,7 @@ macro_rules! impl_packed {
         #[derive(Clone, Debug, Default)]
         pub struct $name<'a> {
             buf: &'a [u8],
+            offset: usize,
         }
 
         impl<'a> Iterator for $name<'a> {
@@ -672,11 +950,13 @@ macro_rules! impl_packed {
                     return None;
                 }
                 if self.buf.len() < 8 {
-                    return Some(Err(Error));
+                    return Some(Err(Error::new(ErrorKind::TruncatedFixed, self.offset)));
                 }
                 let bytes = self.buf[..8].try_into().unwrap();
+                let offset = self.offset;
+                self.offset += 8;
                 self.buf = &self.buf[8..];
-                Some(Ok(Fixed64 { bytes }.$get_fn()))
+                Some(Ok(Fixed64 { bytes, offset }.$get_fn()))
             }
         }
 
@@ -687,6 +967,7 @@ macro_rules! impl_packed {
         #[derive(Clone, Debug, Default)]
         pub struct $name<'a> {
             buf: &'a [u8],
+            offset: usize,
         }
 
         impl<'a> Iterator for $name<'a> {
@@ -697,11 +978,13 @@ macro_rules! impl_packed {
                     return None;
                 }
                 if self.buf.len() < 4 {
-                    return Some(Err(Error));
+                    return Some(Err(Error::new(ErrorKind::TruncatedFixed, self.offset)));
                 }
                 let bytes = self.buf[..4].try_into().unwrap();
+                let offset = self.offset;
+                self.offset += 4;
                 self.buf = &self.buf[4..];
-                Some(Ok(Fixed32 { bytes }.$get_fn()))
+                Some(Ok(Fixed32 { bytes, offset }.$get_fn()))
             }
         }
 
@@ -883,6 +1166,7 @@ impl<T, P> FusedIterator for Repeated<T, P> where
 mod tests {
     use super::{FieldValue, PackedInt32, Repeated, Varint};
     use crate::message::Message;
+    use crate::{Error, ErrorKind};
 
     #[test]
     fn repeated_value() {
@@ -899,7 +1183,53 @@ mod tests {
         assert_eq!(field.number, 1);
         assert!(matches!(
             field.value,
-            FieldValue::Varint(Varint { value: 1 })
+            FieldValue::Varint(Varint { value: 1, .. })
         ));
     }
+
+    #[test]
+    fn varint_range_checks() {
+        let varint = |value| Varint { value, offset: 3 };
+        let out_of_range = Error::new(ErrorKind::ValueOutOfRange, 3);
+        assert_eq!(varint(1).try_get_bool(), Ok(true));
+        assert_eq!(varint(7).try_get_bool(), Err(out_of_range));
+        assert_eq!(varint(-1_i64 as u64).try_get_int32(), Ok(-1));
+        assert_eq!(varint(i32::MIN as u64).try_get_enum(), Ok(i32::MIN));
+        assert_eq!(varint(u32::MAX as u64).try_get_int32(), Err(out_of_range));
+        assert_eq!(varint(1 << 40).try_get_int32(), Err(out_of_range));
+        assert_eq!(varint(u32::MAX as u64).try_get_uint32(), Ok(u32::MAX));
+        assert_eq!(varint(1 << 32).try_get_uint32(), Err(out_of_range));
+        assert_eq!(varint(u32::MAX as u64).try_get_sint32(), Ok(i32::MIN));
+        assert_eq!(varint(1 << 32).try_get_sint32(), Err(out_of_range));
+
+        let value = FieldValue::Varint(varint(2));
+        assert_eq!(value.try_get_uint32(), Ok(2));
+        assert_eq!(value.try_get_bool(), Err(out_of_range));
+        let value = FieldValue::Fixed32(super::Fixed32 {
+            bytes: [0; 4],
+            offset: 3,
+        });
+        assert_eq!(
+            value.try_get_int32(),
+            Err(Error::new(ErrorKind::WireTypeMismatch, 3))
+        );
+    }
+
+    #[test]
+    fn group_marker_mismatch_offset() {
+        let message = Message::new(&b"\x08\x01\x13\x14"[..]);
+        let mut fields = message.fields();
+        fields.next().unwrap().unwrap();
+        let start = fields.next().unwrap().unwrap();
+        assert!(matches!(start.value, FieldValue::StartGroup(2)));
+        assert_eq!(
+            start.value.get_int32(),
+            Err(Error::new(ErrorKind::WireTypeMismatch, 2))
+        );
+        let end = fields.next().unwrap().unwrap();
+        assert_eq!(
+            end.value.get_bytes(),
+            Err(Error::new(ErrorKind::WireTypeMismatch, 3))
+        );
+    }
 }
@@ -0,0 +1,409 @@
+use crate::field::{Field, FieldValue};
+use crate::message::Message;
+use crate::{Error, ErrorKind};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::slice;
+
+// Marks a bucket that holds no run of entries.
+const NO_RUN: usize = usize::MAX;
+
+/// An entry of the table of a [`FieldIndex`].
+#[derive(Clone, Copy, Debug)]
+pub struct IndexEntry<'a> {
+    field: Option<(usize, Field<'a>)>,
+    // The number of entries with the same field number, on the first of them.
+    run: usize,
+    // The position of the run of entries stored in the bucket of this position, or `NO_RUN`.
+    bucket: usize,
+}
+
+impl<'a> IndexEntry<'a> {
+    /// An empty entry, used to initialize a table.
+    pub const EMPTY: Self = Self {
+        field: None,
+        run: 0,
+        bucket: NO_RUN,
+    };
+
+    #[inline]
+    fn new(offset: usize, field: Field<'a>) -> Self {
+        Self {
+            field: Some((offset, field)),
+            ..Self::EMPTY
+        }
+    }
+
+    // Entries are ordered by field number, then by position in the message.
+    #[inline]
+    fn key(&self) -> (u64, usize) {
+        self.field
+            .map_or((u64::MAX, usize::MAX), |(offset, field)| {
+                (field.number, offset)
+            })
+    }
+
+    #[inline]
+    fn number(&self) -> u64 {
+        self.key().0
+    }
+}
+
+impl Default for IndexEntry<'_> {
+    #[inline]
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+// Returns the bucket of `number` in a table of `len` entries. Numbers less than `len` are their own
+// bucket, and others are hashed.
+#[inline]
+fn bucket(number: u64, len: usize) -> usize {
+    match usize::try_from(number) {
+        Ok(number) if number < len => number,
+        _ => ((number.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) % len as u64) as usize,
+    }
+}
+
+// Records the length of each run of entries with the same field number, and stores each run in its
+// bucket unless another run got there first. The entries are sorted, so the runs of numbers that
+// are their own bucket come first and always get it.
+fn link(entries: &mut [IndexEntry<'_>]) {
+    let len = entries.len();
+    let mut start = 0;
+    while start < len {
+        let number = entries[start].number();
+        let end = start + entries[start..].partition_point(|e| e.number() == number);
+        entries[start].run = end - start;
+        let bucket = &mut entries[bucket(number, len)].bucket;
+        if *bucket == NO_RUN {
+            *bucket = start;
+        }
+        start = end;
+    }
+}
+
+// Returns the run of entries with the given field number. This is a single probe, unless another
+// run holds the bucket of the number, in which case it is a binary search.
+fn lookup<'a, 't>(entries: &'t [IndexEntry<'a>], number: u64) -> &'t [IndexEntry<'a>] {
+    if entries.is_empty() {
+        return &[];
+    }
+    let bucket = bucket(number, entries.len());
+    let start = entries[bucket].bucket;
+    if start != NO_RUN && entries[start].number() == number {
+        return &entries[start..start + entries[start].run];
+    }
+    if bucket as u64 == number {
+        return &[];
+    }
+    let start = entries.partition_point(|e| e.number() < number);
+    let len = entries[start..].partition_point(|e| e.number() == number);
+    &entries[start..start + len]
+}
+
+// Decodes all fields of `message` into `table` and indexes them. Returns the number of fields.
+fn fill<'a>(message: &Message<'a>, table: &mut [IndexEntry<'a>]) -> Result<usize, Error> {
+    let mut len = 0;
+    for field in message.raw_fields() {
+        let field = field?;
+        let Some(entry) = table.get_mut(len) else {
+            return Err(Error::new(ErrorKind::FieldLimitExceeded, field.offset));
+        };
+        *entry = IndexEntry::new(field.offset, field.field);
+        len += 1;
+    }
+    table[..len].sort_unstable_by_key(IndexEntry::key);
+    link(&mut table[..len]);
+    Ok(len)
+}
+
+/// An index of the fields of a message by field number.
+///
+/// The index is built in one pass over the message into a table supplied by the caller, and needs
+/// no allocation. The values returned are the same as those returned by [`Message::fields`], in the
+/// same order.
+///
+/// Lookups take constant time for field numbers less than the number of fields in the index, which
+/// index the table directly. Other numbers are hashed. A number whose slot is taken by another
+/// field number falls back to a binary search of the table, so no lookup takes more than
+/// logarithmic time.
+#[derive(Clone, Copy, Debug)]
+pub struct FieldIndex<'a, 't> {
+    entries: &'t [IndexEntry<'a>],
+}
+
+impl<'a, 't> FieldIndex<'a, 't> {
+    /// Builds an index of the fields of `message` in `table`.
+    ///
+    /// If a decoding error occurs, `Err` is returned. If the message has more fields than `table`
+    /// has entries, an error of kind [`ErrorKind::FieldLimitExceeded`] is returned.
+    pub fn new(message: &Message<'a>, table: &'t mut [IndexEntry<'a>]) -> Result<Self, Error> {
+        let len = fill(message, table)?;
+        Ok(Self {
+            entries: &table[..len],
+        })
+    }
+
+    /// Returns the number of fields in the index.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the index has no fields.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value of the first field with the given number.
+    #[inline]
+    pub fn get(&self, number: u64) -> Option<FieldValue<'a>> {
+        self.find_all(number).next()
+    }
+
+    /// Returns the value of the last field with the given number.
+    #[inline]
+    pub fn get_last(&self, number: u64) -> Option<FieldValue<'a>> {
+        self.find_all(number).next_back()
+    }
+
+    /// Returns an iterator over values of all fields with the given number.
+    pub fn find_all(&self, number: u64) -> Values<'a, 't> {
+        Values {
+            iter: lookup(self.entries, number).iter(),
+        }
+    }
+
+    /// Returns the number of fields with the given number.
+    #[inline]
+    pub fn count(&self, number: u64) -> usize {
+        self.find_all(number).len()
+    }
+
+    /// Returns `true` if the index has a field with the given number.
+    #[inline]
+    pub fn contains(&self, number: u64) -> bool {
+        self.count(number) > 0
+    }
+}
+
+/// An index of the fields of a message by field number, backed by a hash table.
+///
+/// This is the allocating counterpart of [`FieldIndex`], and its lookups take the same time.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct FieldMap<'a> {
+    entries: Vec<IndexEntry<'a>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> FieldMap<'a> {
+    /// Builds an index of the fields of `message`.
+    ///
+    /// If a decoding error occurs, `Err` is returned.
+    pub fn new(message: &Message<'a>) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        for field in message.raw_fields() {
+            let field = field?;
+            entries.push(IndexEntry::new(field.offset, field.field));
+        }
+        entries.sort_unstable_by_key(IndexEntry::key);
+        link(&mut entries);
+        Ok(Self { entries })
+    }
+
+    /// Returns the number of fields in the index.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the index has no fields.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value of the first field with the given number.
+    #[inline]
+    pub fn get(&self, number: u64) -> Option<FieldValue<'a>> {
+        self.find_all(number).next()
+    }
+
+    /// Returns the value of the last field with the given number.
+    #[inline]
+    pub fn get_last(&self, number: u64) -> Option<FieldValue<'a>> {
+        self.find_all(number).next_back()
+    }
+
+    /// Returns an iterator over values of all fields with the given number.
+    pub fn find_all(&self, number: u64) -> Values<'a, '_> {
+        Values {
+            iter: lookup(&self.entries, number).iter(),
+        }
+    }
+
+    /// Returns the number of fields with the given number.
+    #[inline]
+    pub fn count(&self, number: u64) -> usize {
+        lookup(&self.entries, number).len()
+    }
+
+    /// Returns `true` if the index has a field with the given number.
+    #[inline]
+    pub fn contains(&self, number: u64) -> bool {
+        self.count(number) > 0
+    }
+
+    /// Returns an iterator over the distinct field numbers in the index, in ascending order.
+    #[inline]
+    pub fn numbers(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entries
+            .chunk_by(|a, b| a.number() == b.number())
+            .map(|run| run[0].number())
+    }
+}
+
+/// An iterator over values of all fields with a given number in an index.
+///
+/// This struct is returned from the `find_all` methods of [`FieldIndex`] and `FieldMap`.
+#[derive(Clone, Debug)]
+pub struct Values<'a, 't> {
+    iter: slice::Iter<'t, IndexEntry<'a>>,
+}
+
+impl<'a> Iterator for Values<'a, '_> {
+    type Item = FieldValue<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().and_then(|e| e.field).map(|(_, f)| f.value)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Values<'_, '_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next_back()
+            .and_then(|e| e.field)
+            .map(|(_, f)| f.value)
+    }
+}
+
+impl ExactSizeIterator for Values<'_, '_> {}
+
+impl FusedIterator for Values<'_, '_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldIndex, IndexEntry};
+    use crate::message::{DecodeOptions, Message};
+    use crate::writer::SliceWriter;
+    use crate::{Error, ErrorKind};
+
+    // 2: 1, 1: "a", 2: 2, 3: group { 2: 3 }, 1: "b", 2: 4
+    const BUF: &[u8] = b"\x10\x01\x0a\x01a\x10\x02\x1b\x10\x03\x1c\x0a\x01b\x10\x04";
+
+    #[test]
+    fn field_index() {
+        let message = Message::new(BUF);
+        let mut table = [IndexEntry::EMPTY; 16];
+        let index = FieldIndex::new(&message, &mut table).unwrap();
+        assert_eq!(index.len(), 8);
+        let ints = index.find_all(2).map(|value| value.get_int32().unwrap());
+        assert!(ints.eq([1, 2, 3, 4]));
+        let strings = index.find_all(1).map(|value| value.get_string().unwrap());
+        assert!(strings.eq(["a", "b"]));
+        assert_eq!(index.get(2).unwrap().get_int32(), Ok(1));
+        assert_eq!(index.get_last(2).unwrap().get_int32(), Ok(4));
+        assert_eq!(index.count(3), 2);
+        assert!(!index.contains(4));
+        assert!(index.get(4).is_none());
+
+        // Lookups return the values returned by `Message::find_all`.
+        let options = DecodeOptions::new().groups(true);
+        let message = Message::with_options(BUF, options);
+        let index = FieldIndex::new(&message, &mut table).unwrap();
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.count(2), 3);
+        let group = index.get(3).unwrap().get_group().unwrap();
+        assert_eq!(group.get(2).unwrap().unwrap().get_int32(), Ok(3));
+
+        let mut table = [IndexEntry::EMPTY; 3];
+        assert_eq!(
+            FieldIndex::new(&message, &mut table).unwrap_err(),
+            Error::new(ErrorKind::FieldLimitExceeded, 7)
+        );
+        let message = Message::new(b"\x08\x01\x08");
+        assert_eq!(
+            FieldIndex::new(&message, &mut table).unwrap_err(),
+            Error::new(ErrorKind::TruncatedVarint, 3)
+        );
+    }
+
+    #[test]
+    fn field_index_lookups() {
+        // Small numbers index the table directly; the others are hashed, some into the same slot.
+        let numbers = [
+            1,
+            3,
+            3,
+            9,
+            100,
+            1 << 20,
+            100,
+            (1 << 29) - 1,
+            200,
+            300,
+            400,
+            500,
+        ];
+        let mut buf = [0; 128];
+        let mut writer = SliceWriter::new(&mut buf);
+        for (i, &number) in numbers.iter().enumerate() {
+            writer.add_uint64(number, i as u64).unwrap();
+        }
+        let message = Message::new(writer.as_bytes());
+        let mut table = [IndexEntry::EMPTY; 16];
+        // Filling the table twice checks that stale entries are ignored.
+        FieldIndex::new(&Message::new(BUF), &mut table).unwrap();
+        let index = FieldIndex::new(&message, &mut table).unwrap();
+        for number in numbers
+            .into_iter()
+            .chain([2, 4, 11, 12, 101, 1 << 21, u64::MAX])
+        {
+            let expected = message
+                .find_all(number)
+                .map(|value| value.unwrap().get_uint64());
+            let values = index.find_all(number).map(|value| value.get_uint64());
+            assert!(values.eq(expected), "field {number}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn field_map() {
+        use super::FieldMap;
+
+        let message = Message::new(BUF);
+        let map = FieldMap::new(&message).unwrap();
+        assert_eq!(map.len(), 8);
+        let ints = map.find_all(2).map(|value| value.get_int32().unwrap());
+        assert!(ints.eq([1, 2, 3, 4]));
+        assert_eq!(map.get_last(1).unwrap().get_string(), Ok("b"));
+        assert_eq!(map.count(3), 2);
+        assert!(!map.contains(4));
+        assert_eq!(map.find_all(4).len(), 0);
+        assert!(map.numbers().eq([1, 2, 3]));
+    }
+}
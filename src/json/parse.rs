@@ -0,0 +1,1017 @@
+use super::{
+    civil_from_days, days_from_civil, decode_base64, is_special, ANY, DURATION, DURATION_SECONDS,
+    FIELD_MASK, LIST_VALUE, NULL_VALUE, STRUCT, TIMESTAMP, TIMESTAMP_SECONDS, VALUE, WRAPPERS,
+};
+use crate::descriptor::{DescriptorPool, FieldDescriptor, FieldType, MessageDescriptor};
+use crate::message::MAX_DEPTH;
+use crate::text::parse::{write_scalars, Scalar};
+use crate::text::{ParseError, ParseErrorKind};
+use crate::writer::MessageWriter;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Parses a message in the proto3 JSON format, using a descriptor, and returns its encoding.
+///
+/// `text` is parsed as a message of the type `descriptor`, with referenced types and extensions
+/// looked up in `pool`. Fields may be named by their JSON name or their original name, and
+/// extensions by `"[pkg.extension]"`. 64-bit integers are accepted as numbers or strings, enum
+/// values by name or number, and `null` as an unset field. The well-known types accepted by
+/// [`print`](super::print) are parsed from their special forms.
+///
+/// Fields are encoded in the order they are given, with repeated fields of packable types packed
+/// if the field is packed. If the text is not valid, a [`ParseError`] is returned with the line
+/// and column at which parsing failed.
+pub fn parse(
+    pool: &DescriptorPool,
+    descriptor: &MessageDescriptor,
+    text: &str,
+) -> Result<Vec<u8>, ParseError> {
+    let mut parser = Parser {
+        pool,
+        lexer: Lexer { text, pos: 0 },
+        depth: 0,
+    };
+    let buf = parser.parse_message(descriptor)?;
+    let (pos, token) = parser.lexer.next()?;
+    if token != Token::End {
+        return Err(parser.error(ParseErrorKind::UnexpectedToken, pos));
+    }
+    Ok(buf)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Token<'t> {
+    // A quoted string, including the quotes, with escapes not yet processed.
+    Str(&'t str),
+    Number(&'t str),
+    True,
+    False,
+    Null,
+    Symbol(char),
+    End,
+}
+
+// A tokenizer of JSON. Tokens are returned with their byte position.
+#[derive(Clone, Copy, Debug)]
+struct Lexer<'t> {
+    text: &'t str,
+    pos: usize,
+}
+
+impl<'t> Lexer<'t> {
+    fn next(&mut self) -> Result<(usize, Token<'t>), ParseError> {
+        let rest = &self.text[self.pos..];
+        let trimmed = rest.trim_start_matches([' ', '\t', '\n', '\r']);
+        self.pos += rest.len() - trimmed.len();
+        let start = self.pos;
+        let error = |kind| ParseError::new(kind, self.text, start);
+        let Some(c) = trimmed.chars().next() else {
+            return Ok((start, Token::End));
+        };
+        let (len, token) = match c {
+            '"' => {
+                let len =
+                    string_len(trimmed).ok_or_else(|| error(ParseErrorKind::InvalidString))?;
+                (len, Token::Str(&trimmed[..len]))
+            }
+            '-' | '0'..='9' => {
+                let len =
+                    number_len(trimmed).ok_or_else(|| error(ParseErrorKind::UnexpectedToken))?;
+                (len, Token::Number(&trimmed[..len]))
+            }
+            'a'..='z' => {
+                let len = trimmed
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(trimmed.len());
+                let token = match &trimmed[..len] {
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    "null" => Token::Null,
+                    _ => return Err(error(ParseErrorKind::UnexpectedToken)),
+                };
+                (len, token)
+            }
+            '{' | '}' | '[' | ']' | ':' | ',' => (1, Token::Symbol(c)),
+            _ => return Err(error(ParseErrorKind::UnexpectedToken)),
+        };
+        self.pos += len;
+        Ok((start, token))
+    }
+
+    fn peek(&self) -> Result<(usize, Token<'t>), ParseError> {
+        let mut lexer = *self;
+        lexer.next()
+    }
+}
+
+// Returns the length of the string at the start of `s`, or `None` if it is not terminated or
+// contains a control character.
+fn string_len(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut i = 1;
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            b'\\' => i += 1,
+            b'"' => return Some(i + 1),
+            0..=0x1f => return None,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// Returns the length of the number at the start of `s`, or `None` if it is not a valid JSON number.
+fn number_len(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let digits = |i: usize| {
+        bytes[i.min(bytes.len())..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut i = usize::from(bytes.first() == Some(&b'-'));
+    match digits(i) {
+        0 => return None,
+        n if n > 1 && bytes[i] == b'0' => return None,
+        n => i += n,
+    }
+    if bytes.get(i) == Some(&b'.') {
+        match digits(i + 1) {
+            0 => return None,
+            n => i += 1 + n,
+        }
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        match digits(i) {
+            0 => return None,
+            n => i += n,
+        }
+    }
+    Some(i)
+}
+
+// Returns the contents of a string token, processing escapes. On an invalid escape, returns `Err`
+// with the byte offset of the escape in the token.
+fn unescape(token: &str) -> Result<String, usize> {
+    let body = &token[1..token.len() - 1];
+    let mut s = String::with_capacity(body.len());
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        let offset = i + 1;
+        let c = match chars.next().map(|(_, c)| c) {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let high = parse_unit(&mut chars).ok_or(offset)?;
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    if !chars.as_str().starts_with("\\u") {
+                        return Err(offset);
+                    }
+                    chars.nth(1);
+                    let low = parse_unit(&mut chars).ok_or(offset)?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(offset);
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or(offset)?
+            }
+            _ => return Err(offset),
+        };
+        s.push(c);
+    }
+    Ok(s)
+}
+
+// Parses the four hex digits of a `\u` escape.
+fn parse_unit(chars: &mut core::str::CharIndices<'_>) -> Option<u32> {
+    let digits = chars.as_str().get(..4)?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let unit = u32::from_str_radix(digits, 16).ok()?;
+    chars.nth(3);
+    Some(unit)
+}
+
+struct Parser<'t, 'd> {
+    pool: &'d DescriptorPool,
+    lexer: Lexer<'t>,
+    depth: u32,
+}
+
+impl<'d> Parser<'_, 'd> {
+    fn error(&self, kind: ParseErrorKind, pos: usize) -> ParseError {
+        ParseError::new(kind, self.lexer.text, pos)
+    }
+
+    fn unexpected(&self, pos: usize, token: Token<'_>) -> ParseError {
+        match token {
+            Token::End => self.error(ParseErrorKind::UnexpectedEnd, pos),
+            _ => self.error(ParseErrorKind::UnexpectedToken, pos),
+        }
+    }
+
+    // Returns the error for a token that is not a valid value of a field.
+    fn invalid(&self, pos: usize, token: Token<'_>) -> ParseError {
+        match token {
+            Token::Symbol(_) | Token::End => self.unexpected(pos, token),
+            _ => self.error(ParseErrorKind::InvalidValue, pos),
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        if token != Token::Symbol(symbol) {
+            return Err(self.unexpected(pos, token));
+        }
+        Ok(())
+    }
+
+    // Consumes `symbol` if it is the next token. Returns `true` if it was.
+    fn consume(&mut self, symbol: char) -> Result<bool, ParseError> {
+        let (_, token) = self.lexer.peek()?;
+        if token != Token::Symbol(symbol) {
+            return Ok(false);
+        }
+        self.lexer.next()?;
+        Ok(true)
+    }
+
+    // Parses the separator after a member or element. Returns `true` at the closing `end`.
+    fn parse_separator(&mut self, end: char) -> Result<bool, ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        match token {
+            Token::Symbol(',') => Ok(false),
+            Token::Symbol(c) if c == end => Ok(true),
+            _ => Err(self.unexpected(pos, token)),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<(usize, String), ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        let Token::Str(s) = token else {
+            return Err(self.invalid(pos, token));
+        };
+        let s = unescape(s)
+            .map_err(|offset| self.error(ParseErrorKind::InvalidString, pos + offset))?;
+        Ok((pos, s))
+    }
+
+    // Parses an object member name and the colon after it.
+    fn parse_name(&mut self) -> Result<(usize, String), ParseError> {
+        let (pos, token) = self.lexer.peek()?;
+        if !matches!(token, Token::Str(_)) {
+            return Err(self.unexpected(pos, token));
+        }
+        let name = self.parse_string()?;
+        self.expect(':')?;
+        Ok(name)
+    }
+
+    // Skips a value of any type.
+    fn skip_value(&mut self) -> Result<(), ParseError> {
+        let mut depth = 0usize;
+        loop {
+            let (pos, token) = self.lexer.next()?;
+            match token {
+                Token::Symbol('{' | '[') => depth += 1,
+                Token::Symbol('}' | ']') if depth > 0 => depth -= 1,
+                Token::Symbol(',' | ':') if depth > 0 => {}
+                Token::Str(_) | Token::Number(_) | Token::True | Token::False | Token::Null => {}
+                _ => return Err(self.unexpected(pos, token)),
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Parses a message value, and returns its encoded fields.
+    fn parse_message(&mut self, descriptor: &'d MessageDescriptor) -> Result<Vec<u8>, ParseError> {
+        let (pos, token) = self.lexer.peek()?;
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(ParseErrorKind::DepthLimitExceeded, pos));
+        }
+        self.depth += 1;
+        let mut w = MessageWriter::new();
+        match descriptor.full_name() {
+            ANY => self.parse_any(&mut w)?,
+            TIMESTAMP => {
+                let (pos, s) = self.parse_string()?;
+                let (seconds, nanos) = parse_timestamp(&s)
+                    .ok_or_else(|| self.error(ParseErrorKind::InvalidValue, pos))?;
+                write_seconds_and_nanos(&mut w, seconds, nanos);
+            }
+            DURATION => {
+                let (pos, s) = self.parse_string()?;
+                let (seconds, nanos) = parse_duration(&s)
+                    .ok_or_else(|| self.error(ParseErrorKind::InvalidValue, pos))?;
+                write_seconds_and_nanos(&mut w, seconds, nanos);
+            }
+            FIELD_MASK => {
+                let (_, s) = self.parse_string()?;
+                for path in s.split(',').filter(|path| !path.is_empty()) {
+                    w.add_string(1, &to_snake_case(path));
+                }
+            }
+            VALUE => self.parse_value(descriptor, &mut w)?,
+            STRUCT | LIST_VALUE => self.parse_field(&mut w, descriptor.field(1).unwrap())?,
+            name if WRAPPERS.contains(&name) => {
+                self.parse_field(&mut w, descriptor.field(1).unwrap())?
+            }
+            _ if token == Token::Symbol('{') => self.parse_fields(descriptor, &mut w, false)?,
+            _ => return Err(self.invalid(pos, token)),
+        }
+        self.depth -= 1;
+        Ok(w.into_vec())
+    }
+
+    // Parses an object of fields. If `any` is set, the `@type` member of an `Any` is skipped.
+    fn parse_fields(
+        &mut self,
+        descriptor: &'d MessageDescriptor,
+        w: &mut MessageWriter,
+        any: bool,
+    ) -> Result<(), ParseError> {
+        self.expect('{')?;
+        if self.consume('}')? {
+            return Ok(());
+        }
+        // The numbers of the fields and the oneofs given so far.
+        let mut seen = Vec::new();
+        let mut oneofs = Vec::new();
+        loop {
+            let (pos, name) = self.parse_name()?;
+            if any && name == "@type" {
+                self.parse_string()?;
+            } else {
+                let field = self
+                    .find_field(descriptor, &name)
+                    .ok_or_else(|| self.error(ParseErrorKind::UnknownField, pos))?;
+                let oneof = field.oneof();
+                if seen.contains(&field.number()) || oneof.is_some_and(|o| oneofs.contains(&o)) {
+                    return Err(self.error(ParseErrorKind::DuplicateField, pos));
+                }
+                if self.lexer.peek()?.1 == Token::Null && !self.accepts_null(field) {
+                    self.lexer.next()?;
+                } else {
+                    seen.push(field.number());
+                    oneofs.extend(oneof);
+                    self.parse_field(w, field)?;
+                }
+            }
+            if self.parse_separator('}')? {
+                return Ok(());
+            }
+        }
+    }
+
+    // Finds a field by JSON name or original name, or an extension by `[full_name]`.
+    fn find_field(
+        &self,
+        descriptor: &'d MessageDescriptor,
+        name: &str,
+    ) -> Option<&'d FieldDescriptor> {
+        if let Some(name) = name
+            .strip_prefix('[')
+            .and_then(|name| name.strip_suffix(']'))
+        {
+            return self
+                .pool
+                .find_extension(name)
+                .filter(|extension| extension.extendee() == Some(descriptor.id()));
+        }
+        descriptor
+            .field_by_json_name(name)
+            .or_else(|| descriptor.field_by_name(name))
+    }
+
+    // Returns `true` if `null` is a value of the field rather than meaning it is not set.
+    fn accepts_null(&self, field: &FieldDescriptor) -> bool {
+        match field.field_type() {
+            _ if field.is_repeated() => false,
+            FieldType::Message(id) => self.pool.message(id).full_name() == VALUE,
+            FieldType::Enum(id) => self.pool.enum_type(id).full_name() == NULL_VALUE,
+            _ => false,
+        }
+    }
+
+    // Parses the value of a field.
+    fn parse_field(
+        &mut self,
+        w: &mut MessageWriter,
+        field: &'d FieldDescriptor,
+    ) -> Result<(), ParseError> {
+        let field_type = field.field_type();
+        if field.is_map() {
+            let FieldType::Message(entry) = field_type else {
+                unreachable!()
+            };
+            let entry = self.pool.message(entry);
+            let (key_field, value_field) = (entry.field(1).unwrap(), entry.field(2).unwrap());
+            self.expect('{')?;
+            if self.consume('}')? {
+                return Ok(());
+            }
+            loop {
+                let (pos, key) = self.parse_name()?;
+                let key = match key_field.field_type() {
+                    FieldType::String => Some(Scalar::Bytes(key.into_bytes())),
+                    FieldType::Bool => match key.as_str() {
+                        "true" => Some(Scalar::Varint(1)),
+                        "false" => Some(Scalar::Varint(0)),
+                        _ => None,
+                    },
+                    key_type => key
+                        .parse()
+                        .ok()
+                        .and_then(|key| Scalar::from_int(key_type, key)),
+                };
+                let key = key.ok_or_else(|| self.error(ParseErrorKind::InvalidValue, pos))?;
+                let mut entry = MessageWriter::new();
+                write_scalars(&mut entry, key_field, &[key]);
+                self.parse_single(&mut entry, value_field)?;
+                w.add_bytes(field.number(), entry.as_bytes());
+                if self.parse_separator('}')? {
+                    return Ok(());
+                }
+            }
+        }
+        if !field.is_repeated() {
+            return self.parse_single(w, field);
+        }
+        let (pos, _) = self.lexer.peek()?;
+        self.expect('[')?;
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(ParseErrorKind::DepthLimitExceeded, pos));
+        }
+        let mut values = Vec::new();
+        if !self.consume(']')? {
+            loop {
+                match field_type {
+                    FieldType::Message(_) | FieldType::Group(_) => self.parse_single(w, field)?,
+                    _ => values.push(self.parse_scalar(field)?),
+                }
+                if self.parse_separator(']')? {
+                    break;
+                }
+            }
+        }
+        write_scalars(w, field, &values);
+        Ok(())
+    }
+
+    // Parses a single value of a field.
+    fn parse_single(
+        &mut self,
+        w: &mut MessageWriter,
+        field: &'d FieldDescriptor,
+    ) -> Result<(), ParseError> {
+        match field.field_type() {
+            FieldType::Message(id) => {
+                let fields = self.parse_message(self.pool.message(id))?;
+                w.add_bytes(field.number(), &fields);
+            }
+            FieldType::Group(id) => {
+                let fields = self.parse_message(self.pool.message(id))?;
+                w.add_group(field.number(), &fields);
+            }
+            _ => {
+                let value = self.parse_scalar(field)?;
+                write_scalars(w, field, &[value]);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_scalar(&mut self, field: &FieldDescriptor) -> Result<Scalar, ParseError> {
+        let (pos, token) = self.lexer.peek()?;
+        let string = match token {
+            Token::Str(_) => Some(self.parse_string()?.1),
+            _ => {
+                self.lexer.next()?;
+                None
+            }
+        };
+        let invalid = || self.invalid(pos, token);
+        Ok(match (field.field_type(), token, string) {
+            (FieldType::Bool, Token::True, _) => Scalar::Varint(1),
+            (FieldType::Bool, Token::False, _) => Scalar::Varint(0),
+            (FieldType::String, _, Some(s)) => Scalar::Bytes(s.into_bytes()),
+            (FieldType::Bytes, _, Some(s)) => Scalar::Bytes(decode_base64(&s).ok_or_else(invalid)?),
+            (FieldType::Enum(id), Token::Null, _)
+                if self.pool.enum_type(id).full_name() == NULL_VALUE =>
+            {
+                Scalar::Varint(0)
+            }
+            (FieldType::Enum(id), _, Some(name)) => {
+                let value = self
+                    .pool
+                    .enum_type(id)
+                    .value_by_name(&name)
+                    .ok_or_else(|| self.error(ParseErrorKind::UnknownEnumValue, pos))?;
+                Scalar::Varint(value.number() as u64)
+            }
+            (field_type @ (FieldType::Double | FieldType::Float), token, string) => {
+                let value = match (token, string.as_deref()) {
+                    (_, Some("NaN")) => Some(f64::NAN),
+                    (_, Some("Infinity")) => Some(f64::INFINITY),
+                    (_, Some("-Infinity")) => Some(f64::NEG_INFINITY),
+                    (Token::Number(s), _) | (_, Some(s)) => parse_float(s),
+                    _ => None,
+                };
+                let value: f64 = value.ok_or_else(invalid)?;
+                if field_type == FieldType::Double {
+                    Scalar::Fixed64(value.to_bits())
+                } else if value.is_finite() && value.abs() > f64::from(f32::MAX) {
+                    return Err(invalid());
+                } else {
+                    Scalar::Fixed32((value as f32).to_bits())
+                }
+            }
+            (field_type, token, string) => {
+                let value = match (token, string.as_deref()) {
+                    (Token::Number(s), _) | (_, Some(s)) => parse_integer(s),
+                    _ => None,
+                };
+                value
+                    .and_then(|value| Scalar::from_int(field_type, value))
+                    .ok_or_else(invalid)?
+            }
+        })
+    }
+
+    // Parses a `google.protobuf.Value` from any JSON value.
+    fn parse_value(
+        &mut self,
+        descriptor: &'d MessageDescriptor,
+        w: &mut MessageWriter,
+    ) -> Result<(), ParseError> {
+        let (pos, token) = self.lexer.peek()?;
+        let number = match token {
+            Token::Null => 1,
+            Token::Number(_) => 2,
+            Token::Str(_) => 3,
+            Token::True | Token::False => 4,
+            Token::Symbol('{') => 5,
+            Token::Symbol('[') => 6,
+            _ => return Err(self.unexpected(pos, token)),
+        };
+        let field = descriptor.field(number).unwrap();
+        if number == 2 {
+            // Numbers are read as doubles, whatever their form.
+            let Token::Number(s) = self.lexer.next()?.1 else {
+                unreachable!()
+            };
+            let value =
+                parse_float(s).ok_or_else(|| self.error(ParseErrorKind::InvalidValue, pos))?;
+            w.add_double(2, value);
+            return Ok(());
+        }
+        self.parse_single(w, field)
+    }
+
+    fn parse_any(&mut self, w: &mut MessageWriter) -> Result<(), ParseError> {
+        // Find the `@type` member, which may follow the fields.
+        let start = self.lexer;
+        let (pos, _) = self.lexer.peek()?;
+        self.expect('{')?;
+        let mut type_url = None;
+        if !self.consume('}')? {
+            loop {
+                let (_, name) = self.parse_name()?;
+                if name == "@type" {
+                    type_url = Some(self.parse_string()?);
+                    break;
+                }
+                self.skip_value()?;
+                if self.parse_separator('}')? {
+                    break;
+                }
+            }
+        }
+        self.lexer = start;
+        let Some((type_pos, type_url)) = type_url else {
+            // Only an empty `Any` may have no type.
+            self.expect('{')?;
+            if !self.consume('}')? {
+                return Err(self.error(ParseErrorKind::InvalidValue, pos));
+            }
+            return Ok(());
+        };
+        let type_name = type_url
+            .rsplit_once('/')
+            .map_or(type_url.as_str(), |(_, name)| name);
+        let value_type = self
+            .pool
+            .find_message(type_name)
+            .ok_or_else(|| self.error(ParseErrorKind::UnknownType, type_pos))?;
+        let value = if is_special(value_type.full_name()) {
+            // The value of a well-known type is the `value` member.
+            let mut value = None;
+            self.expect('{')?;
+            loop {
+                let (pos, name) = self.parse_name()?;
+                match name.as_str() {
+                    "@type" => {
+                        self.parse_string()?;
+                    }
+                    "value" if value.is_none() => value = Some(self.parse_message(value_type)?),
+                    "value" => return Err(self.error(ParseErrorKind::DuplicateField, pos)),
+                    _ => return Err(self.error(ParseErrorKind::UnknownField, pos)),
+                }
+                if self.parse_separator('}')? {
+                    break;
+                }
+            }
+            value.unwrap_or_default()
+        } else {
+            let mut value = MessageWriter::new();
+            self.parse_fields(value_type, &mut value, true)?;
+            value.into_vec()
+        };
+        w.add_string(1, &type_url);
+        w.add_bytes(2, &value);
+        Ok(())
+    }
+}
+
+// Parses a finite number in the JSON number syntax.
+fn parse_float(s: &str) -> Option<f64> {
+    if number_len(s) != Some(s.len()) {
+        return None;
+    }
+    s.parse().ok().filter(|value: &f64| value.is_finite())
+}
+
+// Parses an integer, which may be written with a fraction or exponent if its value is integral.
+fn parse_integer(s: &str) -> Option<i128> {
+    if number_len(s) != Some(s.len()) {
+        return None;
+    }
+    if let Ok(value) = s.parse() {
+        return Some(value);
+    }
+    let value = parse_float(s)?;
+    // Integers beyond 2^64 are out of range of every integer type. The cast truncates any
+    // fraction, so a value is integral if it survives the round trip.
+    let integral = value == (value as i128) as f64;
+    (integral && value.abs() < 18_446_744_073_709_551_616.0).then_some(value as i128)
+}
+
+fn write_seconds_and_nanos(w: &mut MessageWriter, seconds: i64, nanos: i32) {
+    if seconds != 0 {
+        w.add_int64(1, seconds);
+    }
+    if nanos != 0 {
+        w.add_int32(2, nanos);
+    }
+}
+
+// Parses the digits of a fraction of a second, after the decimal point, as nanoseconds.
+fn parse_nanos(digits: &str) -> Option<i32> {
+    if digits.is_empty() || digits.len() > 9 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos: i32 = digits.parse().ok()?;
+    Some(nanos * 10i32.pow(9 - digits.len() as u32))
+}
+
+// Parses an RFC 3339 timestamp, such as `1972-01-01T10:00:20.021-05:00`.
+fn parse_timestamp(s: &str) -> Option<(i64, i32)> {
+    fn number(s: &str, range: core::ops::Range<usize>) -> Option<u32> {
+        let digits = s.get(range)?;
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    }
+    let bytes = s.as_bytes();
+    let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+    if s.len() < 20
+        || !separators.iter().all(|&(i, c)| bytes[i] == c)
+        || !matches!(bytes[10], b'T' | b't')
+    {
+        return None;
+    }
+    let (year, month, day) = (number(s, 0..4)?, number(s, 5..7)?, number(s, 8..10)?);
+    let (hour, minute, second) = (number(s, 11..13)?, number(s, 14..16)?, number(s, 17..19)?);
+    let days = days_from_civil(year.into(), month, day);
+    if civil_from_days(days) != (year.into(), month, day) || hour > 23 || minute > 59 || second > 59
+    {
+        return None;
+    }
+    let mut rest = &s[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(|b| b.is_ascii_digit()).count();
+        nanos = parse_nanos(&fraction[..len])?;
+        rest = &fraction[len..];
+    }
+    let offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let (hours, minutes) = (number(rest, 1..3)?, number(rest, 4..6)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = i64::from(hours * 3600 + minutes * 60);
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+    let seconds = days * 86_400 + i64::from(hour * 3600 + minute * 60 + second) - offset;
+    TIMESTAMP_SECONDS
+        .contains(&seconds)
+        .then_some((seconds, nanos))
+}
+
+// Parses a duration in seconds with an `s` suffix, such as `-1.5s`.
+fn parse_duration(s: &str) -> Option<(i64, i32)> {
+    let s = s.strip_suffix('s')?;
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (seconds, nanos) = match s.split_once('.') {
+        Some((seconds, fraction)) => (seconds, parse_nanos(fraction)?),
+        None => (s, 0),
+    };
+    if seconds.is_empty() || !seconds.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let seconds: i64 = seconds.parse().ok()?;
+    if !DURATION_SECONDS.contains(&seconds) {
+        return None;
+    }
+    Some(if negative {
+        (-seconds, -nanos)
+    } else {
+        (seconds, nanos)
+    })
+}
+
+// Converts a `FieldMask` path from lowerCamelCase to snake_case.
+fn to_snake_case(path: &str) -> String {
+    let mut snake = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::descriptor::tests::{
+        addressbook_file, encode_files, field, json_pool, test_file, well_known_file,
+    };
+    use crate::descriptor::DescriptorPool;
+    use crate::json::print;
+    use crate::message::Message;
+    use crate::text::{ParseError, ParseErrorKind};
+    use crate::writer::MessageWriter;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    fn parse_json(pool: &DescriptorPool, name: &str, text: &str) -> Result<Vec<u8>, ParseError> {
+        parse(pool, pool.find_message(name).unwrap(), text)
+    }
+
+    // Parses `text` and prints the result back as JSON.
+    fn round_trip(pool: &DescriptorPool, name: &str, text: &str) -> String {
+        let buf = parse_json(pool, name, text).unwrap();
+        let mut out = String::new();
+        print(
+            &mut out,
+            pool,
+            pool.find_message(name).unwrap(),
+            Message::new(&buf),
+        )
+        .unwrap();
+        out
+    }
+
+    #[test]
+    fn parse_messages() {
+        let pool = json_pool();
+        let text = r#"
+            {
+              "people": [
+                {
+                  "name": "Alice \"A\" é😀",
+                  "id": "1",
+                  "email": "alice@example.com",
+                  "phones": [
+                    { "number": "555-4321", "type": "HOME" },
+                    { "number": "555-1234", "type": 2 }
+                  ]
+                },
+                { "name": "Bob", "id": 2e0, "phones": null }
+              ]
+            }
+        "#;
+        let mut w = MessageWriter::new();
+        let mut person = w.open_message(1);
+        person.add_string(1, "Alice \"A\" \u{e9}\u{1f600}");
+        person.add_int32(2, 1);
+        person.add_string(3, "alice@example.com");
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-4321");
+        phone.add_enum(2, 1);
+        phone.close();
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-1234");
+        phone.add_enum(2, 2);
+        phone.close();
+        person.close();
+        let mut person = w.open_message(1);
+        person.add_string(1, "Bob");
+        person.add_int32(2, 2);
+        person.close();
+        assert_eq!(
+            parse_json(&pool, "tutorial.AddressBook", text).unwrap(),
+            w.as_bytes()
+        );
+
+        let text = r#"{"counts":{"b":3,"a":2},"packed":[1,2,3],"unpacked":["-1"],"item":{"data":"AP8="},"ratio":0,"child":{"text":"x","ratio":1e300},"[test.note]":"note"}"#;
+        assert_eq!(round_trip(&pool, "test.Test", text), text);
+        // Quoted and unquoted 64-bit integers, and quoted floats.
+        assert_eq!(
+            round_trip(
+                &pool,
+                "test.Test",
+                r#"{"unpacked":[-1,"2",3.0],"ratio":"-Infinity","item":{"data":"-_8"}}"#
+            ),
+            r#"{"unpacked":["-1","2","3"],"item":{"data":"+/8="},"ratio":"-Infinity"}"#
+        );
+        assert_eq!(
+            parse_json(&pool, "test.Test", r#"{"packed":[1,-1]}"#).unwrap(),
+            b"\x22\x0b\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"
+        );
+
+        // Fields may be named by their JSON name or their original name.
+        let mut file = test_file();
+        file.message_type[0]
+            .field
+            .push(field("big_number", 10, 3, ""));
+        let files = [addressbook_file(), file, well_known_file()];
+        let pool = DescriptorPool::decode(&encode_files(&files)).unwrap();
+        for text in [r#"{"bigNumber":"-2"}"#, r#"{"big_number":-2}"#] {
+            assert_eq!(
+                parse_json(&pool, "test.Test", text).unwrap(),
+                b"\x50\xfe\xff\xff\xff\xff\xff\xff\xff\xff\x01"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_well_known_types() {
+        let pool = json_pool();
+        for (name, text, canonical) in [
+            (
+                "google.protobuf.Timestamp",
+                "\"1972-01-01T10:00:20.021-05:00\"",
+                "\"1972-01-01T15:00:20.021Z\"",
+            ),
+            (
+                "google.protobuf.Timestamp",
+                "\"2000-02-29t00:00:00.000000001z\"",
+                "\"2000-02-29T00:00:00.000000001Z\"",
+            ),
+            (
+                "google.protobuf.Timestamp",
+                "\"0001-01-01T00:00:00Z\"",
+                "\"0001-01-01T00:00:00Z\"",
+            ),
+            ("google.protobuf.Duration", "\"-1.5s\"", "\"-1.500s\""),
+            (
+                "google.protobuf.Duration",
+                "\"0.000000005s\"",
+                "\"0.000000005s\"",
+            ),
+            (
+                "google.protobuf.FieldMask",
+                "\"fooBar,a.bC\"",
+                "\"fooBar,a.bC\"",
+            ),
+            (
+                "google.protobuf.Struct",
+                r#"{"a":null,"b":[1,"x",true],"c":{}}"#,
+                r#"{"a":null,"b":[1,"x",true],"c":{}}"#,
+            ),
+            (
+                "google.protobuf.ListValue",
+                "[[], {\"x\": -2.5}]",
+                "[[],{\"x\":-2.5}]",
+            ),
+            ("google.protobuf.Value", "null", "null"),
+            ("google.protobuf.Int64Value", "\"-5\"", "\"-5\""),
+            ("google.protobuf.BoolValue", "true", "true"),
+            ("google.protobuf.BytesValue", "\"AP8\"", "\"AP8=\""),
+            (
+                "google.protobuf.Any",
+                r#"{"name":"Bob","@type":"type.googleapis.com/tutorial.Person"}"#,
+                r#"{"@type":"type.googleapis.com/tutorial.Person","name":"Bob"}"#,
+            ),
+            (
+                "google.protobuf.Any",
+                r#"{"@type":"type.googleapis.com/google.protobuf.Duration","value":"1s"}"#,
+                r#"{"@type":"type.googleapis.com/google.protobuf.Duration","value":"1s"}"#,
+            ),
+            ("google.protobuf.Any", "{}", "{}"),
+        ] {
+            assert_eq!(round_trip(&pool, name, text), canonical, "{text}");
+        }
+
+        for (name, text) in [
+            ("google.protobuf.Timestamp", "\"1970-02-30T00:00:00Z\""),
+            ("google.protobuf.Timestamp", "\"1970-01-01T00:00:00\""),
+            (
+                "google.protobuf.Timestamp",
+                "\"1970-01-01T00:00:00.0000000001Z\"",
+            ),
+            ("google.protobuf.Timestamp", "\"0000-12-31T23:59:59Z\""),
+            ("google.protobuf.Duration", "\"1\""),
+            ("google.protobuf.Duration", "\"315576000001s\""),
+            ("google.protobuf.Duration", "\".5s\""),
+            ("google.protobuf.FloatValue", "1e39"),
+            ("google.protobuf.Int32Value", "2147483648"),
+            ("google.protobuf.UInt64Value", "\"-1\""),
+            ("google.protobuf.Int32Value", "1.5"),
+            ("google.protobuf.Any", r#"{"name":"Bob"}"#),
+        ] {
+            let error = parse_json(&pool, name, text).unwrap_err();
+            assert_eq!(error.kind(), ParseErrorKind::InvalidValue, "{text}");
+        }
+        let error = parse_json(&pool, "google.protobuf.Any", r#"{"@type":"x/y.Z"}"#).unwrap_err();
+        assert_eq!(error.kind(), ParseErrorKind::UnknownType);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let pool = json_pool();
+        let error = |text| {
+            let error = parse_json(&pool, "test.Test", text).unwrap_err();
+            (error.kind(), error.line(), error.column())
+        };
+        use ParseErrorKind::*;
+        assert_eq!(error("{\n  \"nope\": 1\n}"), (UnknownField, 2, 3));
+        assert_eq!(
+            error(r#"{"text": "a", "person": {}}"#),
+            (DuplicateField, 1, 15)
+        );
+        assert_eq!(
+            error(r#"{"ratio": 1, "ratio": 2}"#),
+            (DuplicateField, 1, 14)
+        );
+        assert_eq!(error(r#"{"ratio": 1,}"#), (UnexpectedToken, 1, 13));
+        assert_eq!(error(r#"{"ratio": 01}"#), (UnexpectedToken, 1, 11));
+        assert_eq!(error(r#"{"ratio": true}"#), (InvalidValue, 1, 11));
+        assert_eq!(error(r#"{"text": "\x"}"#), (InvalidString, 1, 11));
+        assert_eq!(error("{\"text\": \"\t\"}"), (InvalidString, 1, 10));
+        assert_eq!(error(r#"{"counts": {"a": "b"}}"#), (InvalidValue, 1, 18));
+        assert_eq!(error(r#"{"[test.missing]": 1}"#), (UnknownField, 1, 2));
+        assert_eq!(error(r#"{"ratio": 1"#), (UnexpectedEnd, 1, 12));
+        assert_eq!(error(r#"{} {}"#), (UnexpectedToken, 1, 4));
+        let enum_error = parse_json(
+            &pool,
+            "tutorial.Person",
+            r#"{"phones": [{"type": "CELL"}]}"#,
+        )
+        .unwrap_err();
+        assert_eq!(enum_error.kind(), UnknownEnumValue);
+
+        let deep = "{\"child\":".repeat(100) + &"}".repeat(100);
+        assert_eq!(error(&deep).0, DepthLimitExceeded);
+        let shallow = "{\"child\":".repeat(99) + "{}" + &"}".repeat(99);
+        assert!(parse_json(&pool, "test.Test", &shallow).is_ok());
+    }
+}
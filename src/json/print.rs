@@ -0,0 +1,774 @@
+use super::{
+    civil_from_days, is_special, write_base64, write_escaped, write_nanos, ANY, DURATION,
+    DURATION_SECONDS, FIELD_MASK, LIST_VALUE, NULL_VALUE, STRUCT, TIMESTAMP, TIMESTAMP_SECONDS,
+    VALUE, WRAPPERS,
+};
+use crate::descriptor::{
+    to_json_name, DescriptorPool, FieldDescriptor, FieldType, MessageDescriptor, Syntax,
+};
+use crate::dynamic::{decode_repeated, wire_type, Value};
+use crate::field::FieldValue;
+use crate::index::{FieldMap, Values};
+use crate::message::{Message, MAX_DEPTH};
+use crate::text::PrintError;
+use crate::{Error, ErrorKind};
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Writes a message in the proto3 JSON format, using a descriptor.
+///
+/// `message` is decoded as a message of the type `descriptor`, with referenced types and
+/// extensions looked up in `pool`. Fields are read directly from the encoded message, without
+/// decoding it into a [`DynamicMessage`](crate::dynamic::DynamicMessage).
+///
+/// The output is compact, with fields named by their JSON name and written in declaration order,
+/// followed by extensions named `"[pkg.extension]"`. As in the canonical mapping, 64-bit integers
+/// are written as strings, bytes as base64, and enum values by name. Fields that are set to their
+/// default value and have no presence are omitted, as are unknown fields. The well-known types
+/// `Timestamp`, `Duration`, `Struct`, `Value`, `ListValue`, `FieldMask`, `Any` and the wrapper
+/// types are written in their special forms; their definitions must be in `pool`.
+///
+/// If the message cannot be decoded, or has messages nested more than 100 deep,
+/// [`PrintError::Decode`] is returned. If an `Any` has a type
+/// that is not in `pool`, [`PrintError::UnknownType`] is returned, and if a well-known type has
+/// a value with no JSON representation, such as a `Timestamp` after the year 9999,
+/// [`PrintError::InvalidValue`] is returned.
+pub fn print<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    pool: &DescriptorPool,
+    descriptor: &MessageDescriptor,
+    message: Message<'_>,
+) -> Result<(), PrintError> {
+    Printer {
+        out,
+        pool,
+        depth: 0,
+    }
+    .write_message(descriptor, message)
+}
+
+/// Writes a message in the proto3 JSON format to a [`std::io::Write`].
+///
+/// This is the same as [`print`], with errors other than I/O errors returned as errors of kind
+/// [`InvalidData`](std::io::ErrorKind::InvalidData).
+#[cfg(feature = "std")]
+pub fn write<W: std::io::Write + ?Sized>(
+    writer: &mut W,
+    pool: &DescriptorPool,
+    descriptor: &MessageDescriptor,
+    message: Message<'_>,
+) -> std::io::Result<()> {
+    struct Adapter<'w, W: ?Sized> {
+        writer: &'w mut W,
+        error: Option<std::io::Error>,
+    }
+
+    impl<W: std::io::Write + ?Sized> fmt::Write for Adapter<'_, W> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.writer.write_all(s.as_bytes()).map_err(|error| {
+                self.error = Some(error);
+                fmt::Error
+            })
+        }
+    }
+
+    let mut adapter = Adapter {
+        writer,
+        error: None,
+    };
+    match print(&mut adapter, pool, descriptor, message) {
+        Ok(()) => Ok(()),
+        Err(PrintError::Fmt) => Err(adapter.error.take().unwrap()),
+        Err(error) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, error)),
+    }
+}
+
+struct Printer<'w, 'd, W: ?Sized> {
+    out: &'w mut W,
+    pool: &'d DescriptorPool,
+    // Number of messages being written.
+    depth: u32,
+}
+
+impl<'d, W: fmt::Write + ?Sized> Printer<'_, 'd, W> {
+    fn write_message(
+        &mut self,
+        descriptor: &'d MessageDescriptor,
+        message: Message<'_>,
+    ) -> Result<(), PrintError> {
+        if self.depth == MAX_DEPTH {
+            let error = Error::new(ErrorKind::DepthLimitExceeded, message.offset);
+            return Err(PrintError::Decode(error));
+        }
+        self.depth += 1;
+        let result = self.write_message_value(descriptor, message);
+        self.depth -= 1;
+        result
+    }
+
+    fn write_message_value(
+        &mut self,
+        descriptor: &'d MessageDescriptor,
+        message: Message<'_>,
+    ) -> Result<(), PrintError> {
+        let mut message = message;
+        message.options = message.options.groups(true);
+        match descriptor.full_name() {
+            ANY => return self.write_any(message),
+            TIMESTAMP => return self.write_timestamp(message),
+            DURATION => return self.write_duration(message),
+            VALUE => return self.write_value(descriptor, message),
+            FIELD_MASK => return self.write_field_mask(message),
+            STRUCT | LIST_VALUE => {
+                // The single field of `Struct` or `ListValue` is written as an object or array,
+                // even if it is empty.
+                let field = descriptor.field(1).unwrap();
+                let map = FieldMap::new(&message)?;
+                if !self.write_field_value(field, map.find_all(1), false, None)? {
+                    self.out
+                        .write_str(if field.is_map() { "{}" } else { "[]" })?;
+                }
+                return Ok(());
+            }
+            name if WRAPPERS.contains(&name) => {
+                let field = descriptor.field(1).unwrap();
+                let map = FieldMap::new(&message)?;
+                if !self.write_field_value(field, map.find_all(1), false, None)? {
+                    let value = Value::default_for(self.pool, field);
+                    self.write_scalar(field.field_type(), &value)?;
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        self.out.write_char('{')?;
+        self.write_fields(descriptor, message, true)?;
+        self.out.write_char('}')?;
+        Ok(())
+    }
+
+    // Writes the fields of a message as members of an object. `first` is `true` if no member has
+    // been written yet.
+    fn write_fields(
+        &mut self,
+        descriptor: &'d MessageDescriptor,
+        message: Message<'_>,
+        mut first: bool,
+    ) -> Result<(), PrintError> {
+        let map = FieldMap::new(&message)?;
+        let proto3 = self.pool.files()[descriptor.file()].syntax() == Syntax::Proto3;
+        let fields = descriptor
+            .fields()
+            .iter()
+            .chain(
+                map.numbers()
+                    .filter_map(|number| match descriptor.field(number) {
+                        Some(_) => None,
+                        None => self.pool.find_extension_by_number(descriptor.id(), number),
+                    }),
+            );
+        for field in fields {
+            let presence = !proto3
+                || field.oneof().is_some()
+                || field.extendee().is_some()
+                || matches!(
+                    field.field_type(),
+                    FieldType::Message(_) | FieldType::Group(_)
+                );
+            let values = map.find_all(field.number());
+            self.write_field_value(field, values, presence, Some(&mut first))?;
+        }
+        Ok(())
+    }
+
+    // Writes the value of a field from its occurrences in a message, preceded by its name if
+    // `member` is set, where `member` is `true` if no member of the object has been written yet.
+    // Returns `false`, having written nothing, if the field is not present, or if it has no
+    // presence and is set to its default value.
+    fn write_field_value(
+        &mut self,
+        field: &'d FieldDescriptor,
+        values: Values<'_, '_>,
+        presence: bool,
+        member: Option<&mut bool>,
+    ) -> Result<bool, PrintError> {
+        let field_type = field.field_type();
+        let packed = field.is_repeated() && field_type.is_packable();
+        let mut values = values
+            .filter(|value| {
+                wire_type(value) == field_type.wire_type()
+                    || (packed && matches!(value, FieldValue::LengthDelimited(_)))
+            })
+            .peekable();
+        if values.peek().is_none() {
+            return Ok(false);
+        }
+        let value = match field_type {
+            _ if field.is_repeated() => None,
+            FieldType::Message(_) | FieldType::Group(_) => None,
+            _ => {
+                let value = Value::decode(self.pool, field_type, &values.next_back().unwrap())?;
+                if !presence && is_default(&value) {
+                    return Ok(false);
+                }
+                Some(value)
+            }
+        };
+        if let Some(first) = member {
+            if !*first {
+                self.out.write_char(',')?;
+            }
+            *first = false;
+            self.out.write_char('"')?;
+            match field.extendee() {
+                Some(_) => write!(self.out, "[{}]", field.full_name())?,
+                None => write_escaped(self.out, field.json_name())?,
+            }
+            self.out.write_str("\":")?;
+        }
+        if let Some(value) = value {
+            self.write_scalar(field_type, &value)?;
+        } else if field.is_map() {
+            self.write_map(field, values)?;
+        } else if field.is_repeated() {
+            self.out.write_char('[')?;
+            let mut first = true;
+            for value in values {
+                let mut elements = Vec::new();
+                if packed {
+                    decode_repeated(field_type, &value, &mut elements)?;
+                } else if let FieldType::Message(id) | FieldType::Group(id) = field_type {
+                    if !first {
+                        self.out.write_char(',')?;
+                    }
+                    first = false;
+                    self.write_message(self.pool.message(id), message_of(&value)?)?;
+                    continue;
+                } else {
+                    elements.push(Value::decode(self.pool, field_type, &value)?);
+                }
+                for element in &elements {
+                    if !first {
+                        self.out.write_char(',')?;
+                    }
+                    first = false;
+                    self.write_scalar(field_type, element)?;
+                }
+            }
+            self.out.write_char(']')?;
+        } else {
+            let (FieldType::Message(id) | FieldType::Group(id)) = field_type else {
+                unreachable!()
+            };
+            // Occurrences of a message field are merged, as if concatenated.
+            let first = values.next().unwrap();
+            if values.peek().is_none() {
+                self.write_message(self.pool.message(id), message_of(&first)?)?;
+            } else {
+                let mut buf = Vec::from(message_of(&first)?.buf);
+                for value in values {
+                    buf.extend_from_slice(message_of(&value)?.buf);
+                }
+                self.write_message(self.pool.message(id), Message::new(&buf))?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn write_map<'a>(
+        &mut self,
+        field: &'d FieldDescriptor,
+        values: impl Iterator<Item = FieldValue<'a>>,
+    ) -> Result<(), PrintError> {
+        let FieldType::Message(entry) = field.field_type() else {
+            unreachable!()
+        };
+        let entry = self.pool.message(entry);
+        let (key_field, value_field) = (entry.field(1).unwrap(), entry.field(2).unwrap());
+        // Entries by key, in the order the keys first appeared. Later entries replace earlier
+        // ones.
+        let mut entries: Vec<(String, FieldMap<'a>)> = Vec::new();
+        let mut positions: BTreeMap<String, usize> = BTreeMap::new();
+        for value in values {
+            let mut message = value.get_message()?;
+            message.options = message.options.groups(true);
+            let map = FieldMap::new(&message)?;
+            let key = match map.get_last(1) {
+                Some(key) => Value::decode(self.pool, key_field.field_type(), &key)?,
+                None => Value::default_for(self.pool, key_field),
+            };
+            let key = match key {
+                Value::String(s) => s.to_string(),
+                Value::Bool(v) => v.to_string(),
+                Value::I32(v) => v.to_string(),
+                Value::I64(v) => v.to_string(),
+                Value::U32(v) => v.to_string(),
+                Value::U64(v) => v.to_string(),
+                _ => unreachable!(),
+            };
+            match positions.get(&key) {
+                Some(&index) => entries[index].1 = map,
+                None => {
+                    positions.insert(key.clone(), entries.len());
+                    entries.push((key, map));
+                }
+            }
+        }
+        self.out.write_char('{')?;
+        for (i, (key, map)) in entries.iter().enumerate() {
+            if i > 0 {
+                self.out.write_char(',')?;
+            }
+            self.out.write_char('"')?;
+            write_escaped(self.out, key)?;
+            self.out.write_str("\":")?;
+            if !self.write_field_value(value_field, map.find_all(2), true, None)? {
+                match value_field.field_type() {
+                    FieldType::Message(id) => {
+                        self.write_message(self.pool.message(id), Message::new(&[]))?
+                    }
+                    field_type => {
+                        let value = Value::default_for(self.pool, value_field);
+                        self.write_scalar(field_type, &value)?;
+                    }
+                }
+            }
+        }
+        self.out.write_char('}')?;
+        Ok(())
+    }
+
+    fn write_scalar(&mut self, field_type: FieldType, value: &Value<'_, '_>) -> fmt::Result {
+        match value {
+            Value::Bool(v) => write!(self.out, "{}", v),
+            Value::I32(v) => write!(self.out, "{}", v),
+            Value::U32(v) => write!(self.out, "{}", v),
+            Value::I64(v) => write!(self.out, "\"{}\"", v),
+            Value::U64(v) => write!(self.out, "\"{}\"", v),
+            Value::F32(v) => write_float(self.out, f64::from(*v), v),
+            Value::F64(v) => write_float(self.out, *v, v),
+            Value::String(v) => {
+                self.out.write_char('"')?;
+                write_escaped(self.out, v)?;
+                self.out.write_char('"')
+            }
+            Value::Bytes(v) => {
+                self.out.write_char('"')?;
+                write_base64(self.out, v)?;
+                self.out.write_char('"')
+            }
+            Value::Enum(v) => {
+                let FieldType::Enum(id) = field_type else {
+                    unreachable!()
+                };
+                let enum_type = self.pool.enum_type(id);
+                if enum_type.full_name() == NULL_VALUE {
+                    return self.out.write_str("null");
+                }
+                match enum_type.value(*v) {
+                    Some(value) => write!(self.out, "\"{}\"", value.name()),
+                    None => write!(self.out, "{}", v),
+                }
+            }
+            Value::Message(_) | Value::List(_) | Value::Map(_) => unreachable!(),
+        }
+    }
+
+    fn write_any(&mut self, message: Message<'_>) -> Result<(), PrintError> {
+        let type_url = match message.get_last(1)? {
+            Some(value) => value.get_string()?,
+            None => "",
+        };
+        let value = match message.get_last(2)? {
+            Some(value) => value.get_bytes()?,
+            None => &[],
+        };
+        if type_url.is_empty() && value.is_empty() {
+            return Ok(self.out.write_str("{}")?);
+        }
+        let type_name = type_url.rsplit_once('/').map_or(type_url, |(_, name)| name);
+        let Some(value_type) = self.pool.find_message(type_name) else {
+            return Err(PrintError::UnknownType);
+        };
+        self.out.write_str("{\"@type\":\"")?;
+        write_escaped(self.out, type_url)?;
+        self.out.write_char('"')?;
+        if is_special(value_type.full_name()) {
+            self.out.write_str(",\"value\":")?;
+            self.write_message(value_type, Message::new(value))?;
+        } else {
+            let mut message = Message::new(value);
+            message.options = message.options.groups(true);
+            self.write_fields(value_type, message, false)?;
+        }
+        self.out.write_char('}')?;
+        Ok(())
+    }
+
+    fn write_timestamp(&mut self, message: Message<'_>) -> Result<(), PrintError> {
+        let (seconds, nanos) = seconds_and_nanos(message)?;
+        if !TIMESTAMP_SECONDS.contains(&seconds) || !(0..1_000_000_000).contains(&nanos) {
+            return Err(PrintError::InvalidValue);
+        }
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+        let time = seconds.rem_euclid(86_400);
+        write!(
+            self.out,
+            "\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )?;
+        write_nanos(self.out, nanos as u32)?;
+        self.out.write_str("Z\"")?;
+        Ok(())
+    }
+
+    fn write_duration(&mut self, message: Message<'_>) -> Result<(), PrintError> {
+        let (seconds, nanos) = seconds_and_nanos(message)?;
+        if !DURATION_SECONDS.contains(&seconds)
+            || !(-999_999_999..1_000_000_000).contains(&nanos)
+            || (seconds < 0 && nanos > 0)
+            || (seconds > 0 && nanos < 0)
+        {
+            return Err(PrintError::InvalidValue);
+        }
+        let sign = if seconds < 0 || nanos < 0 { "-" } else { "" };
+        write!(self.out, "\"{}{}", sign, seconds.unsigned_abs())?;
+        write_nanos(self.out, nanos.unsigned_abs())?;
+        self.out.write_str("s\"")?;
+        Ok(())
+    }
+
+    fn write_value(
+        &mut self,
+        descriptor: &'d MessageDescriptor,
+        message: Message<'_>,
+    ) -> Result<(), PrintError> {
+        // The last field of the `kind` oneof is the one that is set.
+        let mut kind = None;
+        for field in message.fields() {
+            let field = field?;
+            if let Some(descriptor) = descriptor.field(field.number) {
+                if wire_type(&field.value) == descriptor.field_type().wire_type() {
+                    kind = Some((descriptor, field.value));
+                }
+            }
+        }
+        let Some((field, value)) = kind else {
+            return Err(PrintError::InvalidValue);
+        };
+        match field.field_type() {
+            FieldType::Message(id) => {
+                self.write_message(self.pool.message(id), value.get_message()?)
+            }
+            field_type => {
+                let value = Value::decode(self.pool, field_type, &value)?;
+                if let Value::F64(v) = value {
+                    if !v.is_finite() {
+                        return Err(PrintError::InvalidValue);
+                    }
+                }
+                Ok(self.write_scalar(field_type, &value)?)
+            }
+        }
+    }
+
+    fn write_field_mask(&mut self, message: Message<'_>) -> Result<(), PrintError> {
+        self.out.write_char('"')?;
+        for (i, path) in message.find_all(1).enumerate() {
+            if i > 0 {
+                self.out.write_char(',')?;
+            }
+            write_escaped(self.out, &to_json_name(path?.get_string()?))?;
+        }
+        self.out.write_char('"')?;
+        Ok(())
+    }
+}
+
+// Returns the message of a message or group field.
+fn message_of<'a>(value: &FieldValue<'a>) -> Result<Message<'a>, Error> {
+    match value {
+        FieldValue::Group(group) => Ok(*group),
+        _ => value.get_message(),
+    }
+}
+
+// Reads the `seconds` and `nanos` fields of a `Timestamp` or `Duration`.
+fn seconds_and_nanos(message: Message<'_>) -> Result<(i64, i32), Error> {
+    let seconds = match message.get_last(1)? {
+        Some(value) => value.get_int64()?,
+        None => 0,
+    };
+    let nanos = match message.get_last(2)? {
+        Some(value) => value.get_int32()?,
+        None => 0,
+    };
+    Ok((seconds, nanos))
+}
+
+fn is_default(value: &Value<'_, '_>) -> bool {
+    match value {
+        Value::Bool(v) => !v,
+        Value::I32(v) | Value::Enum(v) => *v == 0,
+        Value::I64(v) => *v == 0,
+        Value::U32(v) => *v == 0,
+        Value::U64(v) => *v == 0,
+        // Negative zero is not the default.
+        Value::F32(v) => v.to_bits() == 0,
+        Value::F64(v) => v.to_bits() == 0,
+        Value::String(v) => v.is_empty(),
+        Value::Bytes(v) => v.is_empty(),
+        Value::Message(_) | Value::List(_) | Value::Map(_) => false,
+    }
+}
+
+// Writes a float or double as a JSON number, in exponential notation if it is very large or small,
+// or as a string if it is not finite.
+fn write_float<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    value: f64,
+    display: &(impl fmt::Display + fmt::LowerExp),
+) -> fmt::Result {
+    if value.is_nan() {
+        out.write_str("\"NaN\"")
+    } else if value.is_infinite() {
+        out.write_str(if value < 0.0 {
+            "\"-Infinity\""
+        } else {
+            "\"Infinity\""
+        })
+    } else if value != 0.0 && !(1e-6..1e21).contains(&value.abs()) {
+        write!(out, "{:e}", display)
+    } else {
+        write!(out, "{}", display)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::print;
+    use crate::descriptor::tests::json_pool;
+    use crate::descriptor::DescriptorPool;
+    use crate::message::{DecodeLimits, DecodeOptions, Message};
+    use crate::text::PrintError;
+    use crate::writer::MessageWriter;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    fn json(pool: &DescriptorPool, name: &str, buf: &[u8]) -> Result<String, PrintError> {
+        let mut out = String::new();
+        print(
+            &mut out,
+            pool,
+            pool.find_message(name).unwrap(),
+            Message::new(buf),
+        )?;
+        Ok(out)
+    }
+
+    #[test]
+    fn print_messages() {
+        let pool = json_pool();
+        let mut w = MessageWriter::new();
+        let mut person = w.open_message(1);
+        person.add_string(1, "Alice \"A\"");
+        person.add_int32(2, 0);
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-4321");
+        phone.add_enum(2, 1);
+        phone.close();
+        let mut phone = person.open_message(4);
+        phone.add_enum(2, 0);
+        phone.close();
+        person.close();
+        assert_eq!(
+            json(&pool, "tutorial.AddressBook", w.as_bytes()).unwrap(),
+            r#"{"people":[{"name":"Alice \"A\"","phones":[{"number":"555-4321","type":"HOME"},{}]}]}"#
+        );
+
+        let mut w = MessageWriter::new();
+        for (key, value) in [("b", 1), ("a", 2), ("b", 3)] {
+            let mut entry = w.open_message(1);
+            entry.add_string(1, key);
+            entry.add_int32(2, value);
+            entry.close();
+        }
+        w.add_packed_int32(4, [1, 2]);
+        w.add_int32(4, 3);
+        w.add_sint64(5, -1);
+        w.add_double(8, 0.0);
+        let mut child = w.open_message(9);
+        child.add_string(2, "x");
+        child.close();
+        let mut child = w.open_message(9);
+        child.add_double(8, 1e300);
+        child.close();
+        w.add_string(100, "note");
+        w.add_string(300, "unknown");
+        let mut buf = w.into_vec();
+        // Item { data: "\0\xff" }
+        buf.extend_from_slice(b"\x33\x3a\x02\x00\xff\x34");
+        assert_eq!(
+            json(&pool, "test.Test", &buf).unwrap(),
+            r#"{"counts":{"b":3,"a":2},"packed":[1,2,3],"unpacked":["-1"],"item":{"data":"AP8="},"ratio":0,"child":{"text":"x","ratio":1e300},"[test.note]":"note"}"#
+        );
+        assert_eq!(
+            json(&pool, "test.Test", b"\x08"),
+            Err(PrintError::Decode(crate::Error::new(
+                crate::ErrorKind::TruncatedVarint,
+                1
+            )))
+        );
+
+        // Messages nested too deeply are an error, whatever the limits of the message.
+        let mut buf = Vec::new();
+        for _ in 0..200 {
+            let mut w = MessageWriter::new();
+            w.add_bytes(9, &buf);
+            buf = w.into_vec();
+        }
+        let limits = DecodeLimits::new().max_depth(u32::MAX);
+        let message = Message::with_options(&buf, DecodeOptions::new().limits(limits));
+        let test = pool.find_message("test.Test").unwrap();
+        assert_eq!(
+            print(&mut String::new(), &pool, test, message),
+            Err(PrintError::Decode(crate::Error::new(
+                crate::ErrorKind::DepthLimitExceeded,
+                300
+            )))
+        );
+    }
+
+    fn seconds_and_nanos(seconds: i64, nanos: i32) -> MessageWriter {
+        let mut w = MessageWriter::new();
+        w.add_int64(1, seconds);
+        w.add_int32(2, nanos);
+        w
+    }
+
+    #[test]
+    fn print_well_known_types() {
+        let pool = json_pool();
+        let timestamp = |seconds, nanos| {
+            let w = seconds_and_nanos(seconds, nanos);
+            json(&pool, "google.protobuf.Timestamp", w.as_bytes())
+        };
+        assert_eq!(
+            timestamp(1, 5_000_000).unwrap(),
+            "\"1970-01-01T00:00:01.005Z\""
+        );
+        assert_eq!(timestamp(-1, 0).unwrap(), "\"1969-12-31T23:59:59Z\"");
+        assert_eq!(
+            timestamp(951_782_400, 1).unwrap(),
+            "\"2000-02-29T00:00:00.000000001Z\""
+        );
+        assert_eq!(
+            timestamp(253_402_300_799, 999_999_000).unwrap(),
+            "\"9999-12-31T23:59:59.999999Z\""
+        );
+        assert_eq!(timestamp(253_402_300_800, 0), Err(PrintError::InvalidValue));
+        let duration = |seconds, nanos| {
+            let w = seconds_and_nanos(seconds, nanos);
+            json(&pool, "google.protobuf.Duration", w.as_bytes())
+        };
+        assert_eq!(duration(-1, -500_000_000).unwrap(), "\"-1.500s\"");
+        assert_eq!(duration(0, -5).unwrap(), "\"-0.000000005s\"");
+        assert_eq!(duration(1, -5), Err(PrintError::InvalidValue));
+
+        let mut w = MessageWriter::new();
+        w.add_string(1, "foo_bar");
+        w.add_string(1, "a.b_c");
+        assert_eq!(
+            json(&pool, "google.protobuf.FieldMask", w.as_bytes()).unwrap(),
+            "\"fooBar,a.bC\""
+        );
+
+        // { "a": null, "b": [1, "x", true], "c": {} }
+        let mut list = MessageWriter::new();
+        let mut value = list.open_message(1);
+        value.add_double(2, 1.0);
+        value.close();
+        let mut value = list.open_message(1);
+        value.add_string(3, "x");
+        value.close();
+        let mut value = list.open_message(1);
+        value.add_bool(4, true);
+        value.close();
+        let mut w = MessageWriter::new();
+        for (key, number, bytes) in [("a", 1, &b""[..]), ("b", 6, list.as_bytes()), ("c", 5, b"")] {
+            let mut entry = w.open_message(1);
+            entry.add_string(1, key);
+            let mut value = entry.open_message(2);
+            match number {
+                1 => value.add_enum(1, 0),
+                _ => value.add_bytes(number, bytes),
+            }
+            value.close();
+            entry.close();
+        }
+        assert_eq!(
+            json(&pool, "google.protobuf.Struct", w.as_bytes()).unwrap(),
+            r#"{"a":null,"b":[1,"x",true],"c":{}}"#
+        );
+        assert_eq!(json(&pool, "google.protobuf.ListValue", b"").unwrap(), "[]");
+        assert_eq!(
+            json(&pool, "google.protobuf.Value", b""),
+            Err(PrintError::InvalidValue)
+        );
+
+        assert_eq!(
+            json(&pool, "google.protobuf.Int64Value", b"\x08\x05").unwrap(),
+            "\"5\""
+        );
+        assert_eq!(
+            json(&pool, "google.protobuf.BoolValue", b"").unwrap(),
+            "false"
+        );
+        let mut w = MessageWriter::new();
+        w.add_float(1, f32::NEG_INFINITY);
+        assert_eq!(
+            json(&pool, "google.protobuf.FloatValue", w.as_bytes()).unwrap(),
+            "\"-Infinity\""
+        );
+
+        let mut w = MessageWriter::new();
+        w.add_string(1, "type.googleapis.com/tutorial.Person");
+        w.add_bytes(2, b"\x0a\x05Alice");
+        assert_eq!(
+            json(&pool, "google.protobuf.Any", w.as_bytes()).unwrap(),
+            r#"{"@type":"type.googleapis.com/tutorial.Person","name":"Alice"}"#
+        );
+        let mut w = MessageWriter::new();
+        w.add_string(1, "type.googleapis.com/google.protobuf.Duration");
+        w.add_bytes(2, seconds_and_nanos(1, 0).as_bytes());
+        assert_eq!(
+            json(&pool, "google.protobuf.Any", w.as_bytes()).unwrap(),
+            r#"{"@type":"type.googleapis.com/google.protobuf.Duration","value":"1s"}"#
+        );
+        let mut w = MessageWriter::new();
+        w.add_string(1, "type.googleapis.com/Unknown");
+        assert_eq!(
+            json(&pool, "google.protobuf.Any", w.as_bytes()),
+            Err(PrintError::UnknownType)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn write() {
+        let pool = json_pool();
+        let descriptor = pool.find_message("tutorial.Person").unwrap();
+        let mut out = std::vec::Vec::new();
+        super::write(&mut out, &pool, descriptor, Message::new(b"\x10\x07")).unwrap();
+        assert_eq!(out, br#"{"id":7}"#);
+        let error = super::write(&mut out, &pool, descriptor, Message::new(b"\x10")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -1,4 +1,4 @@
-//! Protozero is a low-level [Protocol Buffers][Protocol Buffers] decoder.
+//! Protozero is a low-level [Protocol Buffers][Protocol Buffers] decoder and encoder.
 //!
 //! [Protocol Buffers]: https://developers.google.com/protocol-buffers/
 
@@ -7,14 +7,42 @@
 #![warn(unreachable_pub)]
 #![forbid(unsafe_code)]
 
-mod encoding;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+/// Protobuf schema descriptors.
+#[cfg(feature = "alloc")]
+pub mod descriptor;
+/// Dynamic messages decoded with a descriptor.
+#[cfg(feature = "alloc")]
+pub mod dynamic;
+/// Low-level wire format primitives.
+pub mod encoding;
+mod error;
 /// Protobuf fields and values.
 pub mod field;
+/// Field number indexes.
+pub mod index;
+/// The proto3 JSON mapping.
+#[cfg(feature = "alloc")]
+pub mod json;
 /// Protobuf message API.
 pub mod message;
+/// Field path queries.
+pub mod query;
+/// Encoded size calculation.
+pub mod size;
+/// Protobuf text formats.
+pub mod text;
+/// Marker types for protobuf field types.
+pub mod types;
+/// Protobuf message writer API.
+pub mod writer;
 
-/// The error type.
-#[derive(Debug, Eq, PartialEq)]
-pub struct Error;
-
+pub use error::{Error, ErrorKind};
 pub use message::Message;
+#[cfg(feature = "alloc")]
+pub use writer::MessageWriter;
+pub use writer::SliceWriter;
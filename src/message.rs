@@ -1,32 +1,417 @@
-use crate::encoding::read_varint;
-use crate::field::{Field, FieldValue, Fixed32, Fixed64, LengthDelimited, Varint};
-use crate::Error;
+use crate::encoding::{read_varint, read_varint_canonical};
+use crate::field::{Field, FieldValue, Fixed32, Fixed64, LengthDelimited, RawField, Varint};
+use crate::query::{Query, QueryPath};
+use crate::{Error, ErrorKind};
+use core::cell::Cell;
 use core::iter::FusedIterator;
 
+/// The largest valid field number.
+pub const FIELD_NUMBER_MAX: u64 = (1 << 29) - 1;
+
+/// Limits on the resources used to decode a message.
+///
+/// Limits guard against hostile input, such as deeply nested messages that would exhaust the
+/// stack of a recursive decoder. By default, messages may be nested up to 100 deep, as in the
+/// protobuf reference implementation, and nothing else is limited.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeLimits {
+    pub(crate) max_depth: u32,
+    max_size: usize,
+}
+
+impl DecodeLimits {
+    /// Creates the default limits, which only limit the nesting depth to 100.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            max_depth: 100,
+            max_size: usize::MAX,
+        }
+    }
+
+    /// Sets the maximum nesting depth of messages.
+    ///
+    /// The top-level message has depth 0. Decoding the fields of a message nested deeper returns
+    /// [`ErrorKind::DepthLimitExceeded`]. The default is 100.
+    #[inline]
+    pub const fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum size of a message in bytes.
+    ///
+    /// Decoding the fields of a larger message returns [`ErrorKind::SizeLimitExceeded`]. Nested
+    /// messages are contained in the top-level message, so this also bounds the total size.
+    #[inline]
+    pub const fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl Default for DecodeLimits {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The default nesting depth limit. Parsers and printers that recurse on their own stop at this
+// depth, whatever the limits of the message.
+pub(crate) const MAX_DEPTH: u32 = DecodeLimits::new().max_depth;
+
+/// Options controlling how a message is decoded.
+///
+/// Options apply to a [`Message`] and to every message nested within it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DecodeOptions {
+    groups: bool,
+    strict: bool,
+    pub(crate) limits: DecodeLimits,
+}
+
+impl DecodeOptions {
+    /// Creates the default options.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            groups: false,
+            strict: false,
+            limits: DecodeLimits::new(),
+        }
+    }
+
+    /// Sets whether groups are decoded as a whole.
+    ///
+    /// If enabled, a group is returned as a single [`FieldValue::Group`] holding the fields
+    /// between the start and the matching end of the group. A group without a matching end, or an
+    /// end group that does not close a group, is an error. Finding the end of a group walks the
+    /// groups nested in it, so groups nested within a message deeper than
+    /// [`DecodeLimits::max_depth`] are reported then. If disabled (the default), the
+    /// [`StartGroup`][FieldValue::StartGroup] and [`EndGroup`][FieldValue::EndGroup] markers are
+    /// returned as they are encountered.
+    #[inline]
+    pub const fn groups(mut self, enabled: bool) -> Self {
+        self.groups = enabled;
+        self
+    }
+
+    /// Sets whether the wire format is strictly validated.
+    ///
+    /// If enabled, the following are errors in addition to the errors reported by default:
+    ///
+    /// - Field numbers greater than [`FIELD_NUMBER_MAX`].
+    /// - Field numbers in the reserved range 19000 to 19999.
+    /// - Varint tags, values and lengths encoded with more bytes than necessary.
+    /// - An [`EndGroup`][FieldValue::EndGroup] without a preceding
+    ///   [`StartGroup`][FieldValue::StartGroup], or a start group that is not ended before the end
+    ///   of the message. If groups are not [decoded as a whole][DecodeOptions::groups], only the
+    ///   nesting depth is checked, not the field numbers of the markers.
+    #[inline]
+    pub const fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Sets the limits on the resources used to decode a message.
+    #[inline]
+    pub const fn limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
 /// A protobuf message.
 #[derive(Clone, Copy, Debug)]
 pub struct Message<'a> {
-    buf: &'a [u8],
+    pub(crate) buf: &'a [u8],
+    // Offset of `buf` in the buffer of the top-level message.
+    pub(crate) offset: usize,
+    pub(crate) options: DecodeOptions,
+    // Nesting depth. The top-level message has depth 0.
+    pub(crate) depth: u32,
+    // Number of fields left to decode, shared with every message nested in the top-level message.
+    pub(crate) budget: Option<&'a Cell<usize>>,
 }
 
 impl<'a> Message<'a> {
     /// Creates a new [`Message`] from a byte slice.
     #[inline]
     pub fn new(buf: &'a [u8]) -> Self {
-        Self { buf }
+        Self::with_options(buf, DecodeOptions::new())
+    }
+
+    /// Creates a new [`Message`] from a byte slice, decoded with the given options.
+    #[inline]
+    pub fn with_options(buf: &'a [u8], options: DecodeOptions) -> Self {
+        Self {
+            buf,
+            offset: 0,
+            options,
+            depth: 0,
+            budget: None,
+        }
+    }
+
+    /// Creates a new [`Message`] from a byte slice, decoded with the given options and a budget
+    /// of fields.
+    ///
+    /// `budget` holds the number of fields left to decode. It is shared by the message and every
+    /// message nested in it, and decremented for each field decoded from any of them, so it
+    /// bounds the total work of walking the whole message. A field is counted each time it is
+    /// decoded. When the budget is exhausted, decoding another field returns
+    /// [`ErrorKind::FieldLimitExceeded`].
+    #[inline]
+    pub fn with_field_budget(
+        buf: &'a [u8],
+        options: DecodeOptions,
+        budget: &'a Cell<usize>,
+    ) -> Self {
+        Self {
+            budget: Some(budget),
+            ..Self::with_options(buf, options)
+        }
+    }
+
+    /// Returns the options the message is decoded with.
+    #[inline]
+    pub fn options(&self) -> DecodeOptions {
+        self.options
     }
 
     /// Returns an iterator over fields of the message.
     #[inline]
     pub fn fields(&self) -> Fields<'a> {
-        Fields { buf: self.buf }
+        Fields {
+            buf: self.buf,
+            offset: self.offset,
+            options: self.options,
+            depth: self.depth,
+            count: 0,
+            budget: self.budget,
+            group_depth: 0,
+        }
+    }
+
+    /// Returns an iterator over fields of the message together with the location of their
+    /// encoding.
+    ///
+    /// This can be used to copy fields through verbatim.
+    #[inline]
+    pub fn raw_fields(&self) -> RawFields<'a> {
+        RawFields {
+            fields: self.fields(),
+        }
+    }
+
+    /// Returns the value of the first field with the given number.
+    ///
+    /// Protobuf parsers keep the last value of a non-repeated field. Use
+    /// [`get_last`][Message::get_last] to get that value.
+    ///
+    /// Fields are decoded up to the first match. If a decoding error occurs first, `Err` is
+    /// returned.
+    pub fn get(&self, number: u64) -> Result<Option<FieldValue<'a>>, Error> {
+        self.find_all(number).next().transpose()
+    }
+
+    /// Returns the value of the last field with the given number.
+    ///
+    /// This follows the protobuf rule that the last value of a non-repeated scalar field wins.
+    /// Note that repeated occurrences of a non-repeated `message` field are merged by protobuf
+    /// parsers, which this does not do.
+    ///
+    /// All fields are decoded. If a decoding error occurs, `Err` is returned.
+    pub fn get_last(&self, number: u64) -> Result<Option<FieldValue<'a>>, Error> {
+        let mut last = None;
+        for value in self.find_all(number) {
+            last = Some(value?);
+        }
+        Ok(last)
+    }
+
+    /// Returns an iterator over values of all fields with the given number.
+    #[inline]
+    pub fn find_all(&self, number: u64) -> FindAll<'a> {
+        FindAll {
+            fields: self.fields(),
+            number,
+            done: false,
+        }
+    }
+
+    /// Returns the number of fields with the given number.
+    ///
+    /// All fields are decoded. If a decoding error occurs, `Err` is returned.
+    pub fn count(&self, number: u64) -> Result<usize, Error> {
+        self.find_all(number)
+            .try_fold(0, |count, value| value.map(|_| count + 1))
+    }
+
+    /// Returns `true` if the message has a field with the given number.
+    ///
+    /// Fields are decoded up to the first match. If a decoding error occurs first, `Err` is
+    /// returned.
+    pub fn contains(&self, number: u64) -> Result<bool, Error> {
+        self.get(number).map(|value| value.is_some())
+    }
+
+    /// Returns an iterator over values of the fields selected by a path of field numbers.
+    ///
+    /// Every segment of the path but the last selects length-delimited fields, which are decoded
+    /// as nested messages. The path can be a slice of field numbers such as `&[1, 4, 1]`, a slice
+    /// of [`PathSegment`][crate::query::PathSegment]s with index selectors for repeated fields,
+    /// or a [`Path`][crate::query::Path] parsed from a string such as `"1[2].4.1"`.
+    ///
+    /// If decoding fails, the error reports the index of the path segment that failed. A path
+    /// with more than [`PATH_MAX_LEN`][crate::query::PATH_MAX_LEN] segments fails with
+    /// [`ErrorKind::PathTooLong`].
+    #[inline]
+    pub fn query<'p, P: QueryPath + ?Sized>(&self, path: &'p P) -> Query<'a, 'p, P> {
+        Query::new(*self, path)
+    }
+
+    /// Checks that the message is well-formed without decoding values.
+    ///
+    /// Every field is walked, checking tags, lengths and that groups are balanced, until the
+    /// buffer is exactly consumed. Groups are validated as they are walked, so each field is read
+    /// once. Length-delimited values are not validated; use
+    /// [`validate_with`][Message::validate_with] to validate sub-messages.
+    ///
+    /// The first failure is returned as an [`Error`] with the kind and byte offset of the failure.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.validate_with(|_| false)
+    }
+
+    /// Checks that the message is well-formed, recursing into sub-messages.
+    ///
+    /// This is like [`validate`][Message::validate], but `is_message` is called with the path of
+    /// every length-delimited field. If it returns `true`, the value is validated as a nested
+    /// message.
+    ///
+    /// Validation recurses once per nesting level of groups and sub-messages, up to
+    /// [`DecodeLimits::max_depth`]. Deeper nesting returns [`ErrorKind::DepthLimitExceeded`].
+    pub fn validate_with<F>(&self, mut is_message: F) -> Result<(), Error>
+    where
+        F: FnMut(&FieldPath<'_>) -> bool,
+    {
+        let mut message = *self;
+        message.options.groups = false;
+        validate(
+            &mut message.fields(),
+            self.depth,
+            None,
+            None,
+            &mut is_message,
+        )
+    }
+}
+
+// Validates the fields of a message at nesting depth `depth`. If `group` holds the field number
+// and offset of a start group, the fields are validated up to the matching end group instead.
+fn validate<F>(
+    fields: &mut Fields<'_>,
+    depth: u32,
+    group: Option<(u64, usize)>,
+    parent: Option<&FieldPath<'_>>,
+    is_message: &mut F,
+) -> Result<(), Error>
+where
+    F: FnMut(&FieldPath<'_>) -> bool,
+{
+    loop {
+        let offset = fields.offset;
+        let field = fields.try_next().map_err(|e| match (e.kind(), group) {
+            // In strict mode, the iterator reports open groups at the end of the buffer.
+            (ErrorKind::UnterminatedGroup, Some((_, start))) => {
+                Error::new(ErrorKind::UnterminatedGroup, start)
+            }
+            _ => e,
+        })?;
+        let Some(field) = field else {
+            return match group {
+                Some((_, start)) => Err(Error::new(ErrorKind::UnterminatedGroup, start)),
+                None => Ok(()),
+            };
+        };
+        let path = FieldPath {
+            number: field.number,
+            parent,
+        };
+        match field.value {
+            FieldValue::StartGroup(_) => {
+                if depth >= fields.options.limits.max_depth {
+                    return Err(Error::new(ErrorKind::DepthLimitExceeded, fields.offset));
+                }
+                let group = Some((field.number, offset));
+                validate(fields, depth + 1, group, Some(&path), is_message)?;
+            }
+            FieldValue::EndGroup(_) => match group {
+                Some((number, _)) if number == field.number => return Ok(()),
+                _ => return Err(Error::new(ErrorKind::MismatchedEndGroup, offset)),
+            },
+            FieldValue::LengthDelimited(value) if is_message(&path) => {
+                let mut message = value.get_message();
+                message.depth = depth.saturating_add(1);
+                validate(
+                    &mut message.fields(),
+                    message.depth,
+                    None,
+                    Some(&path),
+                    is_message,
+                )?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The path of a field from the top-level message.
+///
+/// This is passed to the callback of [`Message::validate_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct FieldPath<'p> {
+    number: u64,
+    parent: Option<&'p FieldPath<'p>>,
+}
+
+impl<'p> FieldPath<'p> {
+    /// Returns the number of the field.
+    #[inline]
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// Returns the path of the field (message or group) that holds the field, or `None` if the
+    /// field is in the top-level message.
+    #[inline]
+    pub fn parent(&self) -> Option<&'p FieldPath<'p>> {
+        self.parent
+    }
+
+    /// Returns the nesting depth of the field. Fields of the top-level message have depth 0.
+    pub fn depth(&self) -> usize {
+        self.parent.map_or(0, |parent| parent.depth() + 1)
+    }
+
+    /// Returns `true` if the field numbers of the path, starting from the top-level message, are
+    /// `numbers`.
+    pub fn matches(&self, numbers: &[u64]) -> bool {
+        match numbers.split_last() {
+            Some((&number, rest)) if number == self.number => match self.parent {
+                Some(parent) => parent.matches(rest),
+                None => rest.is_empty(),
+            },
+            _ => false,
+        }
     }
 }
 
 impl<'a> From<&'a [u8]> for Message<'a> {
     #[inline]
     fn from(buf: &'a [u8]) -> Self {
-        Self { buf }
+        Self::new(buf)
     }
 }
 
@@ -36,60 +421,215 @@ impl<'a> From<&'a [u8]> for Message<'a> {
 #[derive(Clone, Debug)]
 pub struct Fields<'a> {
     buf: &'a [u8],
+    offset: usize,
+    options: DecodeOptions,
+    // Nesting depth of the message.
+    depth: u32,
+    // Number of fields returned.
+    count: usize,
+    // Number of fields left to decode, shared with the other messages of the tree.
+    budget: Option<&'a Cell<usize>>,
+    // Depth of open group markers. Only tracked in strict mode.
+    group_depth: usize,
 }
 
 #[doc(hidden)]
 pub type MessageFields<'a> = Fields<'a>;
 
 impl<'a> Fields<'a> {
+    #[inline]
+    fn read_varint(&self, buf: &'a [u8], offset: usize) -> Result<(&'a [u8], u64), Error> {
+        if self.options.strict {
+            read_varint_canonical(buf)
+        } else {
+            read_varint(buf)
+        }
+        .map_err(|e| e.offset_by(offset))
+    }
+
+    #[cold]
+    fn check_message_limits(&self) -> Result<(), Error> {
+        let limits = &self.options.limits;
+        if self.depth > limits.max_depth {
+            return Err(Error::new(ErrorKind::DepthLimitExceeded, self.offset));
+        }
+        if self.buf.len() > limits.max_size {
+            return Err(Error::new(ErrorKind::SizeLimitExceeded, self.offset));
+        }
+        Ok(())
+    }
+
     fn try_next(&mut self) -> Result<Option<Field<'a>>, Error> {
+        if self.count == 0 {
+            self.check_message_limits()?;
+        }
         if self.buf.is_empty() {
+            if self.group_depth > 0 {
+                return Err(Error::new(ErrorKind::UnterminatedGroup, self.offset));
+            }
             return Ok(None);
         }
-        let (buf, tag) = read_varint(self.buf)?;
+        let offset = self.offset;
+        if self.budget.is_some_and(|budget| budget.get() == 0) {
+            return Err(Error::new(ErrorKind::FieldLimitExceeded, offset));
+        }
+        let (buf, tag) = self.read_varint(self.buf, offset)?;
         let number = tag >> 3;
         if number == 0 {
             // Field number 0 is illegal.
-            return Err(Error);
+            return Err(Error::new(ErrorKind::InvalidFieldNumber, offset));
+        }
+        if self.options.strict {
+            if number > FIELD_NUMBER_MAX {
+                return Err(Error::new(ErrorKind::FieldNumberOutOfRange, offset));
+            }
+            if (19000..=19999).contains(&number) {
+                return Err(Error::new(ErrorKind::ReservedFieldNumber, offset));
+            }
         }
         let wire_type = tag & 0x7;
+        let value_offset = offset + (self.buf.len() - buf.len());
         let (buf, value) = match wire_type {
             0 => {
-                let (buf, value) = read_varint(buf)?;
-                (buf, FieldValue::Varint(Varint { value }))
+                let (buf, value) = self.read_varint(buf, value_offset)?;
+                let offset = value_offset;
+                (buf, FieldValue::Varint(Varint { value, offset }))
             }
             1 => {
                 if buf.len() < 8 {
-                    return Err(Error);
+                    return Err(Error::new(ErrorKind::TruncatedFixed, value_offset));
                 }
                 let bytes = buf[..8].try_into().unwrap();
-                (&buf[8..], FieldValue::Fixed64(Fixed64 { bytes }))
+                let offset = value_offset;
+                (&buf[8..], FieldValue::Fixed64(Fixed64 { bytes, offset }))
             }
             2 => {
-                let (buf, len) = read_varint(buf)?;
-                let len = len.try_into().map_err(|_| Error)?;
-                if buf.len() < len {
-                    return Err(Error);
+                let (rest, len) = self.read_varint(buf, value_offset)?;
+                let overrun = Error::new(ErrorKind::LengthOverrun, value_offset);
+                let len = len.try_into().map_err(|_| overrun)?;
+                if rest.len() < len {
+                    return Err(overrun);
                 }
+                let offset = value_offset + (buf.len() - rest.len());
                 (
-                    &buf[len..],
-                    FieldValue::LengthDelimited(LengthDelimited { buf: &buf[..len] }),
+                    &rest[len..],
+                    FieldValue::LengthDelimited(LengthDelimited {
+                        buf: &rest[..len],
+                        offset,
+                        options: self.options,
+                        depth: self.depth.saturating_add(1),
+                        budget: self.budget,
+                    }),
                 )
             }
-            3 => (buf, FieldValue::StartGroup),
-            4 => (buf, FieldValue::EndGroup),
+            3 if self.options.groups => {
+                let (rest, body) = self.scan_group(buf, number, offset, value_offset)?;
+                let message = Message {
+                    buf: body,
+                    offset: value_offset,
+                    options: self.options,
+                    depth: self.depth.saturating_add(1),
+                    budget: self.budget,
+                };
+                (rest, FieldValue::Group(message))
+            }
+            3 => {
+                if self.options.strict {
+                    self.group_depth += 1;
+                }
+                (buf, FieldValue::StartGroup(offset))
+            }
+            // Matching end groups are consumed by `scan_group`.
+            4 if self.options.groups => {
+                return Err(Error::new(ErrorKind::MismatchedEndGroup, offset))
+            }
+            4 => {
+                if self.options.strict {
+                    if self.group_depth == 0 {
+                        return Err(Error::new(ErrorKind::MismatchedEndGroup, offset));
+                    }
+                    self.group_depth -= 1;
+                }
+                (buf, FieldValue::EndGroup(offset))
+            }
             5 => {
                 if buf.len() < 4 {
-                    return Err(Error);
+                    return Err(Error::new(ErrorKind::TruncatedFixed, value_offset));
                 }
                 let bytes = buf[..4].try_into().unwrap();
-                (&buf[4..], FieldValue::Fixed32(Fixed32 { bytes }))
+                let offset = value_offset;
+                (&buf[4..], FieldValue::Fixed32(Fixed32 { bytes, offset }))
             }
-            _ => return Err(Error),
+            _ => return Err(Error::new(ErrorKind::InvalidWireType, offset)),
         };
+        self.offset += self.buf.len() - buf.len();
         self.buf = buf;
+        self.count += 1;
+        if let Some(budget) = self.budget {
+            budget.set(budget.get() - 1);
+        }
         Ok(Some(Field { number, value }))
     }
+
+    // Finds the end of the group with field number `number` whose body starts at `buf`. Returns
+    // the remainder after the end group and the body of the group.
+    //
+    // Only the depth of nested groups is tracked. Nested groups are checked when they are decoded,
+    // so a group is scanned once for each group it is nested in. Groups nested in a message deeper
+    // than the depth limit are rejected here, which bounds the cost.
+    fn scan_group(
+        &self,
+        buf: &'a [u8],
+        number: u64,
+        start_offset: usize,
+        body_offset: usize,
+    ) -> Result<(&'a [u8], &'a [u8]), Error> {
+        let mut fields = Fields {
+            buf,
+            offset: body_offset,
+            options: DecodeOptions::new().strict(self.options.strict),
+            depth: 0,
+            count: 0,
+            budget: None,
+            // The end of this group is consumed as a marker.
+            group_depth: 1,
+        };
+        // The depth of the message holding the next field.
+        let mut depth = self.depth.saturating_add(1);
+        loop {
+            let end = fields.buf;
+            let end_offset = fields.offset;
+            let field = fields.try_next().map_err(|e| match e.kind() {
+                ErrorKind::UnterminatedGroup => Error::new(e.kind(), start_offset),
+                _ => e,
+            })?;
+            match field {
+                None => return Err(Error::new(ErrorKind::UnterminatedGroup, start_offset)),
+                Some(Field {
+                    value: FieldValue::StartGroup(_),
+                    ..
+                }) => {
+                    if depth > self.options.limits.max_depth {
+                        return Err(Error::new(ErrorKind::DepthLimitExceeded, end_offset));
+                    }
+                    depth = depth.saturating_add(1);
+                }
+                Some(Field {
+                    number: end_number,
+                    value: FieldValue::EndGroup(_),
+                }) => {
+                    if depth == self.depth.saturating_add(1) {
+                        if end_number != number {
+                            return Err(Error::new(ErrorKind::MismatchedEndGroup, end_offset));
+                        }
+                        return Ok((fields.buf, &buf[..buf.len() - end.len()]));
+                    }
+                    depth -= 1;
+                }
+                Some(_) => {}
+            }
+        }
+    }
 }
 
 impl<'a> Iterator for Fields<'a> {
@@ -101,3 +641,513 @@ impl<'a> Iterator for Fields<'a> {
 }
 
 impl FusedIterator for Fields<'_> {}
+
+/// An iterator over fields of a message together with the location of their encoding.
+///
+/// This struct is returned from the [`raw_fields`][Message::raw_fields] method of [`Message`].
+#[derive(Clone, Debug)]
+pub struct RawFields<'a> {
+    fields: Fields<'a>,
+}
+
+impl<'a> RawFields<'a> {
+    fn try_next(&mut self) -> Result<Option<RawField<'a>>, Error> {
+        let buf = self.fields.buf;
+        let offset = self.fields.offset;
+        let Some(field) = self.fields.try_next()? else {
+            return Ok(None);
+        };
+        let raw = &buf[..buf.len() - self.fields.buf.len()];
+        // The tag has already been decoded successfully.
+        let (rest, _) = read_varint(raw)?;
+        let tag_len = raw.len() - rest.len();
+        let end = offset + raw.len();
+        let (payload_start, payload_end) = match &field.value {
+            FieldValue::Varint(v) => (v.offset, end),
+            FieldValue::Fixed64(v) => (v.offset, end),
+            FieldValue::Fixed32(v) => (v.offset, end),
+            FieldValue::LengthDelimited(v) => (v.offset, v.offset + v.buf.len()),
+            FieldValue::Group(m) => (m.offset, m.offset + m.buf.len()),
+            FieldValue::StartGroup(_) | FieldValue::EndGroup(_) => (end, end),
+        };
+        Ok(Some(RawField {
+            field,
+            raw,
+            offset,
+            tag_len,
+            payload_start,
+            payload_end,
+        }))
+    }
+}
+
+impl<'a> Iterator for RawFields<'a> {
+    type Item = Result<RawField<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+}
+
+impl FusedIterator for RawFields<'_> {}
+
+/// An iterator over values of all fields of a message with a given number.
+///
+/// This struct is returned from the [`find_all`][Message::find_all] method of [`Message`]. After
+/// an error is returned, the iterator is exhausted.
+#[derive(Clone, Debug)]
+pub struct FindAll<'a> {
+    fields: Fields<'a>,
+    number: u64,
+    done: bool,
+}
+
+impl<'a> Iterator for FindAll<'a> {
+    type Item = Result<FieldValue<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.fields.try_next() {
+                Ok(Some(field)) if field.number == self.number => return Some(Ok(field.value)),
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl FusedIterator for FindAll<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeLimits, DecodeOptions, Message, FIELD_NUMBER_MAX};
+    use crate::field::FieldValue;
+    use crate::{Error, ErrorKind};
+    use core::cell::Cell;
+
+    fn first_error(buf: &[u8]) -> Error {
+        Message::new(buf)
+            .fields()
+            .find_map(|field| field.err())
+            .unwrap()
+    }
+
+    #[test]
+    fn error_kinds() {
+        let error = |kind, offset| Error::new(kind, offset);
+        assert_eq!(
+            first_error(b"\x08\x01\x08"),
+            error(ErrorKind::TruncatedVarint, 3)
+        );
+        assert_eq!(
+            first_error(b"\x08\x01\x00\x01"),
+            error(ErrorKind::InvalidFieldNumber, 2)
+        );
+        assert_eq!(
+            first_error(b"\x08\x01\x0e"),
+            error(ErrorKind::InvalidWireType, 2)
+        );
+        assert_eq!(
+            first_error(b"\x08\x01\x0d\x00\x00"),
+            error(ErrorKind::TruncatedFixed, 3)
+        );
+        assert_eq!(
+            first_error(b"\x08\x01\x12\x03\x00\x00"),
+            error(ErrorKind::LengthOverrun, 3)
+        );
+        assert_eq!(
+            first_error(b"\x08\xff\xff\xff\xff\xff\xff\xff\xff\xff\x7f"),
+            error(ErrorKind::VarintOverflow, 1)
+        );
+    }
+
+    #[test]
+    fn nested_error_offsets() {
+        // Field 1 wraps a message holding a string with an invalid UTF-8 byte, followed by a
+        // truncated varint.
+        let buf = b"\x08\x01\x0a\x06\x12\x02a\xff\x08\x80";
+        let mut fields = Message::new(buf).fields();
+        fields.next().unwrap().unwrap();
+        let message = fields.next().unwrap().unwrap().value.get_message().unwrap();
+        let mut fields = message.fields();
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!(
+            field.value.get_string(),
+            Err(Error::new(ErrorKind::InvalidUtf8, 7))
+        );
+        assert_eq!(
+            field.value.get_fixed32(),
+            Err(Error::new(ErrorKind::WireTypeMismatch, 6))
+        );
+        assert_eq!(
+            fields.next().unwrap().err(),
+            Some(Error::new(ErrorKind::TruncatedVarint, 9))
+        );
+    }
+
+    #[test]
+    fn raw_fields() {
+        // 1: 150, 2: "ab", 3: group { 4: 1 }, 5: 0x01020304 (fixed32)
+        let buf = b"\x08\x96\x01\x12\x02ab\x1b\x20\x01\x1c\x2d\x04\x03\x02\x01";
+        let mut fields = Message::new(buf).raw_fields();
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!(field.field.number, 1);
+        assert_eq!(field.raw_bytes(), b"\x08\x96\x01");
+        assert_eq!(field.range(), 0..3);
+        assert_eq!(field.tag_range(), 0..1);
+        assert_eq!(field.length_range(), None);
+        assert_eq!(field.payload_range(), 1..3);
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!(field.raw_bytes(), b"\x12\x02ab");
+        assert_eq!(field.tag_range(), 3..4);
+        assert_eq!(field.length_range(), Some(4..5));
+        assert_eq!(field.payload_range(), 5..7);
+        let field = fields.next().unwrap().unwrap();
+        assert!(matches!(field.field.value, FieldValue::StartGroup(_)));
+        assert_eq!(field.raw_bytes(), b"\x1b");
+        assert_eq!(field.payload_range(), 8..8);
+        fields.next().unwrap().unwrap();
+        fields.next().unwrap().unwrap();
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!(field.raw_bytes(), b"\x2d\x04\x03\x02\x01");
+        assert_eq!(field.payload_range(), 12..16);
+        assert!(fields.next().is_none());
+
+        let options = DecodeOptions::new().groups(true);
+        let mut fields = Message::with_options(buf, options).raw_fields().skip(2);
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!(field.raw_bytes(), b"\x1b\x20\x01\x1c");
+        assert_eq!(field.tag_range(), 7..8);
+        assert_eq!(field.payload_range(), 8..10);
+
+        // Copying all fields verbatim reproduces the message.
+        let mut copy = [0; 16];
+        let mut len = 0;
+        for field in Message::new(buf).raw_fields() {
+            let raw = field.unwrap().raw_bytes();
+            copy[len..len + raw.len()].copy_from_slice(raw);
+            len += raw.len();
+        }
+        assert_eq!(&copy[..len], buf);
+
+        let mut fields = Message::new(b"\x08\x01\x12\x03").raw_fields();
+        fields.next().unwrap().unwrap();
+        assert_eq!(
+            fields.next().unwrap().err(),
+            Some(Error::new(ErrorKind::LengthOverrun, 3))
+        );
+    }
+
+    #[test]
+    fn validate() {
+        // 1: { 2: "x" }, 3: group { 4: 1, 5: group {} }, 6: "y"
+        let buf = b"\x0a\x03\x12\x01x\x1b\x20\x01\x2b\x2c\x1c\x32\x01y";
+        let message = Message::new(buf);
+        assert_eq!(message.validate(), Ok(()));
+        let mut paths = 0;
+        let result = message.validate_with(|path| {
+            paths += 1;
+            assert!(path.matches(&[1]) || path.matches(&[1, 2]) || path.matches(&[6]));
+            path.matches(&[1])
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(paths, 3);
+
+        let validate = |buf| Message::new(buf).validate();
+        let error = |kind, offset| Err(Error::new(kind, offset));
+        assert_eq!(
+            validate(b"\x08\x01\x1b\x08\x01"),
+            error(ErrorKind::UnterminatedGroup, 2)
+        );
+        // The end of group 3 is reached while group 5 is open.
+        assert_eq!(
+            validate(b"\x08\x01\x1b\x2b\x1c\x2c"),
+            error(ErrorKind::MismatchedEndGroup, 4)
+        );
+        assert_eq!(
+            validate(b"\x08\x01\x0c"),
+            error(ErrorKind::MismatchedEndGroup, 2)
+        );
+        assert_eq!(
+            validate(b"\x08\x01\x0a\x05\x08"),
+            error(ErrorKind::LengthOverrun, 3)
+        );
+
+        // 1: { 2: <truncated varint> }
+        let message = Message::new(b"\x0a\x01\x10");
+        assert_eq!(message.validate(), Ok(()));
+        assert_eq!(
+            message.validate_with(|path| path.number() == 1),
+            error(ErrorKind::TruncatedVarint, 3)
+        );
+
+        // 1: { 1: { 1: {} } }
+        let limits = DecodeLimits::new().max_depth(1);
+        let message = Message::with_options(
+            b"\x0a\x04\x0a\x02\x0a\x00",
+            DecodeOptions::new().limits(limits),
+        );
+        assert_eq!(
+            message.validate_with(|_| true),
+            error(ErrorKind::DepthLimitExceeded, 4)
+        );
+
+        // Groups nested deeper than the default limit of 100 are rejected, not recursed into.
+        let mut buf = [0x0b; 80_000];
+        buf[40_000..].fill(0x0c);
+        assert_eq!(validate(&buf), error(ErrorKind::DepthLimitExceeded, 101));
+        assert_eq!(validate(&buf[39_900..40_100]), Ok(()));
+    }
+
+    #[test]
+    fn groups() {
+        // 1: group { 2: 150, 3: group { 4: 1 } }, 5: 1
+        let buf = b"\x0b\x10\x96\x01\x1b\x20\x01\x1c\x0c\x28\x01";
+        let options = DecodeOptions::new().groups(true);
+        let mut fields = Message::with_options(buf, options).fields();
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!(field.number, 1);
+        let group = field.value.get_group().unwrap();
+        assert_eq!(group.buf, b"\x10\x96\x01\x1b\x20\x01\x1c");
+        let mut inner = group.fields();
+        assert_eq!(inner.next().unwrap().unwrap().value.get_int32(), Ok(150));
+        let field = inner.next().unwrap().unwrap();
+        assert_eq!(field.number, 3);
+        let message = field.value.get_message().unwrap();
+        let field = message.fields().next().unwrap().unwrap();
+        assert_eq!((field.number, field.value.get_bool()), (4, Ok(true)));
+        assert!(inner.next().is_none());
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!((field.number, field.value.get_int32()), (5, Ok(1)));
+        assert!(fields.next().is_none());
+
+        // Without the option, the markers are returned.
+        let mut fields = Message::new(buf).fields();
+        let field = fields.next().unwrap().unwrap();
+        assert!(matches!(field.value, FieldValue::StartGroup(_)));
+    }
+
+    #[test]
+    fn group_errors() {
+        let options = DecodeOptions::new().groups(true);
+        let first_error = |buf| {
+            Message::with_options(buf, options)
+                .fields()
+                .find_map(|field| field.err())
+                .unwrap()
+        };
+        assert_eq!(
+            first_error(b"\x08\x01\x0b\x10\x01"),
+            Error::new(ErrorKind::UnterminatedGroup, 2)
+        );
+        assert_eq!(
+            first_error(b"\x0b\x10\x01\x14"),
+            Error::new(ErrorKind::MismatchedEndGroup, 3)
+        );
+        assert_eq!(
+            first_error(b"\x08\x01\x0c"),
+            Error::new(ErrorKind::MismatchedEndGroup, 2)
+        );
+        // The nested group is mismatched, which is found when it is decoded.
+        let buf = b"\x0b\x13\x1c\x0c";
+        let field = Message::with_options(buf, options)
+            .fields()
+            .next()
+            .unwrap()
+            .unwrap();
+        let group = field.value.get_group().unwrap();
+        assert_eq!(
+            group.fields().next().unwrap().err(),
+            Some(Error::new(ErrorKind::MismatchedEndGroup, 2))
+        );
+
+        // The scan for the end of a group follows the options of the message.
+        let strict = options.strict(true);
+        let buf = b"\x0b\x8c\x00";
+        assert_eq!(
+            Message::with_options(buf, strict)
+                .fields()
+                .next()
+                .unwrap()
+                .err(),
+            Some(Error::new(ErrorKind::NonCanonicalVarint, 1))
+        );
+        assert!(Message::with_options(buf, options)
+            .fields()
+            .next()
+            .unwrap()
+            .is_ok());
+        // 1: group { 1: group { 1: group {} } }
+        let buf = b"\x0b\x0b\x0b\x0c\x0c\x0c";
+        let limits = |max_depth| options.limits(DecodeLimits::new().max_depth(max_depth));
+        assert!(Message::with_options(buf, limits(2))
+            .fields()
+            .next()
+            .unwrap()
+            .is_ok());
+        assert_eq!(
+            Message::with_options(buf, limits(1))
+                .fields()
+                .next()
+                .unwrap()
+                .err(),
+            Some(Error::new(ErrorKind::DepthLimitExceeded, 2))
+        );
+    }
+
+    #[test]
+    fn strict() {
+        let strict = DecodeOptions::new().strict(true);
+        let first_error = |buf, options| {
+            Message::with_options(buf, options)
+                .fields()
+                .find_map(|field| field.err())
+        };
+        for (buf, kind, offset) in [
+            (
+                &b"\x80\x80\x80\x80\x10\x01"[..],
+                ErrorKind::FieldNumberOutOfRange,
+                0,
+            ),
+            (
+                &b"\x08\x01\xc0\xa8\x09\x01"[..],
+                ErrorKind::ReservedFieldNumber,
+                2,
+            ),
+            (&b"\x88\x00\x01"[..], ErrorKind::NonCanonicalVarint, 0),
+            (&b"\x08\x80\x00"[..], ErrorKind::NonCanonicalVarint, 1),
+            (&b"\x0a\x81\x00\x00"[..], ErrorKind::NonCanonicalVarint, 1),
+            (&b"\x08\x01\x0c"[..], ErrorKind::MismatchedEndGroup, 2),
+            (
+                &b"\x0b\x13\x14\x08\x01"[..],
+                ErrorKind::UnterminatedGroup,
+                5,
+            ),
+        ] {
+            assert_eq!(first_error(buf, strict), Some(Error::new(kind, offset)));
+            assert_eq!(first_error(buf, DecodeOptions::new()), None);
+        }
+
+        let buf = b"\xf8\xff\xff\xff\x0f\x01\x0b\x08\x80\x01\x0c";
+        let mut fields = Message::with_options(buf, strict).fields();
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!(field.number, FIELD_NUMBER_MAX);
+        assert_eq!(fields.count(), 3);
+    }
+
+    #[test]
+    fn limits() {
+        fn walk(message: Message<'_>) -> Result<usize, Error> {
+            let mut count = 0;
+            for field in message.fields() {
+                count += 1;
+                if let Ok(message) = field?.value.get_message() {
+                    count += walk(message)?;
+                }
+            }
+            Ok(count)
+        }
+
+        // Three levels of nesting: 1 { 1 { 1 { } } }
+        let buf = b"\x0a\x04\x0a\x02\x0a\x00";
+        let with_limits = |limits| Message::with_options(buf, DecodeOptions::new().limits(limits));
+        assert_eq!(walk(with_limits(DecodeLimits::new())), Ok(3));
+        assert_eq!(walk(with_limits(DecodeLimits::new().max_depth(3))), Ok(3));
+        assert_eq!(
+            walk(with_limits(DecodeLimits::new().max_depth(2))),
+            Err(Error::new(ErrorKind::DepthLimitExceeded, 6))
+        );
+        assert_eq!(
+            walk(with_limits(DecodeLimits::new().max_size(5))),
+            Err(Error::new(ErrorKind::SizeLimitExceeded, 0))
+        );
+
+        let buf = b"\x08\x01\x08\x02\x08\x03";
+        let budget = Cell::new(2);
+        let mut fields = Message::with_field_budget(buf, DecodeOptions::new(), &budget).fields();
+        assert!(fields.next().unwrap().is_ok());
+        assert!(fields.next().unwrap().is_ok());
+        assert_eq!(
+            fields.next().unwrap().err(),
+            Some(Error::new(ErrorKind::FieldLimitExceeded, 4))
+        );
+        assert_eq!(budget.get(), 0);
+
+        // The budget is shared with nested messages: 1 { 1: 1, 1: 2 }, 2: 1
+        let buf = b"\x0a\x04\x08\x01\x08\x02\x10\x01";
+        let walk_with = |fields| {
+            let budget = Cell::new(fields);
+            walk(Message::with_field_budget(
+                buf,
+                DecodeOptions::new(),
+                &budget,
+            ))
+        };
+        assert_eq!(walk_with(4), Ok(4));
+        assert_eq!(
+            walk_with(2),
+            Err(Error::new(ErrorKind::FieldLimitExceeded, 4))
+        );
+
+        // Sibling messages draw from the same budget: 50 of 1 { 1: 1, ..., 1: 40 }
+        let mut buf = [0; 50 * 82];
+        for message in buf.chunks_mut(82) {
+            message[..2].copy_from_slice(b"\x0a\x50");
+            for (field, value) in message[2..].chunks_mut(2).zip(1..) {
+                field.copy_from_slice(&[0x08, value]);
+            }
+        }
+        let budget = Cell::new(100);
+        let message = Message::with_field_budget(&buf, DecodeOptions::new(), &budget);
+        assert_eq!(
+            walk(message),
+            Err(Error::new(ErrorKind::FieldLimitExceeded, 200))
+        );
+    }
+
+    #[test]
+    fn lookup() {
+        // 1: 1, 2: "a", 1: 2, 1: 3
+        let message = Message::new(b"\x08\x01\x12\x01a\x08\x02\x08\x03");
+        assert_eq!(message.get(1).unwrap().unwrap().get_int32(), Ok(1));
+        assert_eq!(message.get_last(1).unwrap().unwrap().get_int32(), Ok(3));
+        assert_eq!(message.get_last(2).unwrap().unwrap().get_string(), Ok("a"));
+        assert!(message.get(3).unwrap().is_none());
+        assert!(message.get_last(3).unwrap().is_none());
+        assert_eq!(message.count(1), Ok(3));
+        assert_eq!(message.count(3), Ok(0));
+        assert_eq!(message.contains(2), Ok(true));
+        assert_eq!(message.contains(3), Ok(false));
+        let values = message
+            .find_all(1)
+            .map(|value| value.and_then(|value| value.get_int32()));
+        assert!(values.eq([Ok(1), Ok(2), Ok(3)]));
+    }
+
+    #[test]
+    fn lookup_errors() {
+        // 1: 1, then a truncated varint.
+        let message = Message::new(b"\x08\x01\x08");
+        let error = Error::new(ErrorKind::TruncatedVarint, 3);
+        assert_eq!(message.get(1).unwrap().unwrap().get_int32(), Ok(1));
+        assert_eq!(message.get_last(1).err(), Some(error));
+        assert_eq!(message.get(2).err(), Some(error));
+        assert_eq!(message.count(1), Err(error));
+        assert_eq!(message.contains(1), Ok(true));
+        assert_eq!(message.contains(2), Err(error));
+        let mut values = message.find_all(1);
+        assert!(values.next().unwrap().is_ok());
+        assert_eq!(values.next().unwrap().err(), Some(error));
+        assert!(values.next().is_none());
+    }
+}
@@ -0,0 +1,407 @@
+use crate::field::FieldValue;
+use crate::message::{Fields, Message};
+use crate::{Error, ErrorKind};
+use core::fmt;
+use core::iter::FusedIterator;
+use core::str::FromStr;
+
+/// The maximum number of segments in a query path.
+pub const PATH_MAX_LEN: usize = 16;
+
+/// A segment of a query path.
+///
+/// A segment selects the fields of a message with a given number. If `index` is set, only the
+/// occurrence with that zero-based index is selected.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PathSegment {
+    /// The field number.
+    pub number: u64,
+    /// The index of the occurrence of the field, or `None` for all occurrences.
+    pub index: Option<usize>,
+}
+
+impl PathSegment {
+    /// Creates a segment selecting all fields with the given number.
+    #[inline]
+    pub const fn new(number: u64) -> Self {
+        Self {
+            number,
+            index: None,
+        }
+    }
+
+    /// Creates a segment selecting the occurrence `index` of the field with the given number.
+    #[inline]
+    pub const fn at(number: u64, index: usize) -> Self {
+        Self {
+            number,
+            index: Some(index),
+        }
+    }
+}
+
+impl From<u64> for PathSegment {
+    #[inline]
+    fn from(number: u64) -> Self {
+        Self::new(number)
+    }
+}
+
+/// A sequence of [`PathSegment`]s that can be passed to [`Message::query`].
+pub trait QueryPath {
+    /// Returns the number of segments.
+    fn segment_count(&self) -> usize;
+
+    /// Returns the segment at `index`.
+    ///
+    /// `index` is less than [`segment_count`][QueryPath::segment_count].
+    fn segment(&self, index: usize) -> PathSegment;
+}
+
+impl QueryPath for [u64] {
+    #[inline]
+    fn segment_count(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn segment(&self, index: usize) -> PathSegment {
+        PathSegment::new(self[index])
+    }
+}
+
+impl<const N: usize> QueryPath for [u64; N] {
+    #[inline]
+    fn segment_count(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn segment(&self, index: usize) -> PathSegment {
+        PathSegment::new(self[index])
+    }
+}
+
+impl QueryPath for [PathSegment] {
+    #[inline]
+    fn segment_count(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn segment(&self, index: usize) -> PathSegment {
+        self[index]
+    }
+}
+
+impl<const N: usize> QueryPath for [PathSegment; N] {
+    #[inline]
+    fn segment_count(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn segment(&self, index: usize) -> PathSegment {
+        self[index]
+    }
+}
+
+/// A query path parsed from a string.
+///
+/// The syntax is a list of field numbers separated by `.`. Each field number may be followed by
+/// an index in square brackets. For example, `"1[2].4.1"` selects field 1 of every field 4 of
+/// the third field 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Path {
+    segments: [PathSegment; PATH_MAX_LEN],
+    len: usize,
+}
+
+impl Path {
+    /// Returns the segments of the path.
+    #[inline]
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments[..self.len]
+    }
+}
+
+impl FromStr for Path {
+    type Err = ParsePathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse_number<T: FromStr>(s: &str, position: usize) -> Result<T, ParsePathError> {
+            // `FromStr` for integers accepts a leading `+`.
+            if !s.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParsePathError { position });
+            }
+            s.parse().map_err(|_| ParsePathError { position })
+        }
+
+        let mut path = Path {
+            segments: [PathSegment::new(0); PATH_MAX_LEN],
+            len: 0,
+        };
+        let mut position = 0;
+        for part in s.split('.') {
+            if path.len == PATH_MAX_LEN {
+                return Err(ParsePathError { position });
+            }
+            let segment = match part.find('[') {
+                Some(open) => {
+                    let index = part[open + 1..].strip_suffix(']').ok_or(ParsePathError {
+                        position: position + part.len(),
+                    })?;
+                    PathSegment::at(
+                        parse_number(&part[..open], position)?,
+                        parse_number(index, position + open + 1)?,
+                    )
+                }
+                None => PathSegment::new(parse_number(part, position)?),
+            };
+            path.segments[path.len] = segment;
+            path.len += 1;
+            position += part.len() + 1;
+        }
+        Ok(path)
+    }
+}
+
+impl QueryPath for Path {
+    #[inline]
+    fn segment_count(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn segment(&self, index: usize) -> PathSegment {
+        self.segments()[index]
+    }
+}
+
+/// The error type returned when parsing a [`Path`] fails.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParsePathError {
+    position: usize,
+}
+
+impl ParsePathError {
+    /// Returns the byte position in the string at which parsing failed.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for ParsePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid field path at position {}", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePathError {}
+
+/// The error type returned by [`Query`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QueryError {
+    segment: usize,
+    error: Error,
+}
+
+impl QueryError {
+    /// Returns the index of the path segment at which the query failed.
+    #[inline]
+    pub fn segment(&self) -> usize {
+        self.segment
+    }
+
+    /// Returns the underlying decoding error.
+    #[inline]
+    pub fn error(&self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in path segment {}", self.error, self.segment)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for QueryError {}
+
+#[derive(Clone, Debug)]
+struct Level<'a> {
+    fields: Fields<'a>,
+    // Number of fields matching the segment number seen so far.
+    seen: usize,
+}
+
+/// An iterator over values of the fields selected by a query path.
+///
+/// This struct is returned from the [`query`][Message::query] method of [`Message`]. After an
+/// error is returned, the iterator is exhausted.
+#[derive(Clone, Debug)]
+pub struct Query<'a, 'p, P: ?Sized> {
+    path: &'p P,
+    levels: [Option<Level<'a>>; PATH_MAX_LEN],
+    depth: usize,
+    error: Option<QueryError>,
+}
+
+impl<'a, 'p, P: QueryPath + ?Sized> Query<'a, 'p, P> {
+    pub(crate) fn new(message: Message<'a>, path: &'p P) -> Self {
+        let mut query = Query {
+            path,
+            levels: [const { None }; PATH_MAX_LEN],
+            depth: 0,
+            error: None,
+        };
+        if path.segment_count() > PATH_MAX_LEN {
+            query.error = Some(QueryError {
+                segment: PATH_MAX_LEN,
+                error: Error::new(ErrorKind::PathTooLong, message.offset),
+            });
+        } else if path.segment_count() > 0 {
+            query.levels[0] = Some(Level {
+                fields: message.fields(),
+                seen: 0,
+            });
+            query.depth = 1;
+        }
+        query
+    }
+
+    fn fail(&mut self, segment: usize, error: Error) -> Option<Result<FieldValue<'a>, QueryError>> {
+        self.depth = 0;
+        Some(Err(QueryError { segment, error }))
+    }
+}
+
+impl<'a, P: QueryPath + ?Sized> Iterator for Query<'a, '_, P> {
+    type Item = Result<FieldValue<'a>, QueryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+        while self.depth > 0 {
+            let level = self.depth - 1;
+            let segment = self.path.segment(level);
+            let state = self.levels[level].as_mut().unwrap();
+            if segment.index.is_some_and(|index| state.seen > index) {
+                // The selected occurrence has been visited.
+                self.depth -= 1;
+                continue;
+            }
+            let field = match state.fields.next() {
+                Some(Ok(field)) => field,
+                Some(Err(e)) => return self.fail(level, e),
+                None => {
+                    self.depth -= 1;
+                    continue;
+                }
+            };
+            if field.number != segment.number {
+                continue;
+            }
+            let seen = state.seen;
+            state.seen += 1;
+            if segment.index.is_some_and(|index| index != seen) {
+                continue;
+            }
+            if level + 1 == self.path.segment_count() {
+                return Some(Ok(field.value));
+            }
+            let message = match field.value.get_message() {
+                Ok(message) => message,
+                Err(e) => return self.fail(level, e),
+            };
+            self.levels[level + 1] = Some(Level {
+                fields: message.fields(),
+                seen: 0,
+            });
+            self.depth += 1;
+        }
+        None
+    }
+}
+
+impl<P: QueryPath + ?Sized> FusedIterator for Query<'_, '_, P> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParsePathError, Path, PathSegment, QueryPath, PATH_MAX_LEN};
+    use crate::message::Message;
+    use crate::{Error, ErrorKind};
+
+    // people { name: "a" phones { number: "1" } phones { number: "2" } }
+    // people { name: "b" }
+    // people { name: "c" phones { number: "3" } }
+    const ADDRESS_BOOK: &[u8] = b"\x0a\x0d\x0a\x01a\x22\x03\x0a\x011\x22\x03\x0a\x012\
+        \x0a\x03\x0a\x01b\
+        \x0a\x08\x0a\x01c\x22\x03\x0a\x013";
+
+    fn strings<P: QueryPath + ?Sized>(path: &P) -> impl Iterator<Item = &'static str> + '_ {
+        Message::new(ADDRESS_BOOK)
+            .query(path)
+            .map(|value| value.unwrap().get_string().unwrap())
+    }
+
+    #[test]
+    fn query() {
+        assert!(strings(&[1, 1]).eq(["a", "b", "c"]));
+        assert!(strings(&[1, 4, 1]).eq(["1", "2", "3"]));
+        assert!(strings(&[PathSegment::at(1, 2), PathSegment::new(4), 1.into()]).eq(["3"]));
+        assert!(strings(&[PathSegment::new(1), PathSegment::at(4, 1), 1.into()]).eq(["2"]));
+        assert!(strings(&[PathSegment::at(1, 3), PathSegment::new(1)]).eq([""; 0]));
+        assert!(strings(&[7, 1]).eq([""; 0]));
+        assert!(strings(&[0_u64; 0]).eq([""; 0]));
+        let path: Path = "1[0].4[1].1".parse().unwrap();
+        assert!(strings(&path).eq(["2"]));
+    }
+
+    #[test]
+    fn query_errors() {
+        // 1: { 2: 150, 3: <truncated varint> }
+        let message = Message::new(b"\x0a\x04\x10\x96\x01\x18");
+        let mut query = message.query(&[1, 3]);
+        let error = query.next().unwrap().unwrap_err();
+        assert_eq!(error.segment(), 1);
+        assert_eq!(error.error(), Error::new(ErrorKind::TruncatedVarint, 6));
+        assert!(query.next().is_none());
+
+        let error = message.query(&[1, 2, 1]).next().unwrap().unwrap_err();
+        assert_eq!(error.segment(), 1);
+        assert_eq!(error.error().kind(), ErrorKind::WireTypeMismatch);
+
+        let error = message
+            .query(&[1; PATH_MAX_LEN + 1])
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.segment(), PATH_MAX_LEN);
+        assert_eq!(error.error(), Error::new(ErrorKind::PathTooLong, 0));
+    }
+
+    #[test]
+    fn parse_path() {
+        let path: Path = "1[2].4.1".parse().unwrap();
+        assert_eq!(
+            path.segments(),
+            [
+                PathSegment::at(1, 2),
+                PathSegment::new(4),
+                PathSegment::new(1)
+            ]
+        );
+        let error = |position| Err::<Path, _>(ParsePathError { position });
+        assert_eq!("".parse(), error(0));
+        assert_eq!("1..2".parse(), error(2));
+        assert_eq!("1.+2".parse(), error(2));
+        assert_eq!("1.2[3".parse(), error(5));
+        assert_eq!("1.2[x]".parse(), error(4));
+        assert_eq!("1.x[3]".parse(), error(2));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::encoding::{varint_len, zigzag};
+
+/// Returns the encoded size of a field tag.
+#[inline]
+pub fn tag(number: u64) -> usize {
+    varint_len(number << 3)
+}
+
+/// Returns the encoded size of a varint.
+#[inline]
+pub fn varint(value: u64) -> usize {
+    varint_len(value)
+}
+
+/// Returns the encoded size of an `int32` or `enum` value.
+///
+/// Negative values are sign-extended and always take ten bytes.
+#[inline]
+pub fn int32(value: i32) -> usize {
+    varint_len(value as i64 as u64)
+}
+
+/// Returns the encoded size of an `int64` value.
+#[inline]
+pub fn int64(value: i64) -> usize {
+    varint_len(value as u64)
+}
+
+/// Returns the encoded size of a `sint32` value.
+#[inline]
+pub fn sint32(value: i32) -> usize {
+    varint_len(zigzag::encode_32(value) as u64)
+}
+
+/// Returns the encoded size of a `sint64` value.
+#[inline]
+pub fn sint64(value: i64) -> usize {
+    varint_len(zigzag::encode_64(value))
+}
+
+/// Returns the encoded size of a length-delimited value of `len` bytes, including the length
+/// prefix.
+#[inline]
+pub fn length_delimited(len: usize) -> usize {
+    varint_len(len as u64) + len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{int32, length_delimited, sint32, sint64, tag, varint};
+
+    #[test]
+    fn sizes() {
+        assert_eq!(tag(1), 1);
+        assert_eq!(tag(15), 1);
+        assert_eq!(tag(16), 2);
+        assert_eq!(tag((1 << 29) - 1), 5);
+        assert_eq!(varint(0), 1);
+        assert_eq!(varint(127), 1);
+        assert_eq!(varint(128), 2);
+        assert_eq!(varint(u64::MAX), 10);
+        assert_eq!(int32(-1), 10);
+        assert_eq!(int32(i32::MAX), 5);
+        assert_eq!(sint32(-1), 1);
+        assert_eq!(sint32(i32::MIN), 5);
+        assert_eq!(sint64(i64::MIN), 10);
+        assert_eq!(length_delimited(0), 1);
+        assert_eq!(length_delimited(128), 130);
+    }
+}
@@ -0,0 +1,197 @@
+use super::{ParseError, ParseErrorKind};
+use alloc::vec::Vec;
+
+// A token of the protobuf text format.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Token<'t> {
+    Ident(&'t str),
+    // A number, not yet checked for validity.
+    Number(&'t str),
+    // A quoted string, including the quotes, with escapes not yet processed.
+    Str(&'t str),
+    Symbol(char),
+    End,
+}
+
+// A tokenizer of the protobuf text format, and of `.proto` files. Tokens are returned with their
+// byte position.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Lexer<'t> {
+    text: &'t str,
+    pos: usize,
+    // Whether comments are written as in `.proto` files rather than as in the text format.
+    proto: bool,
+}
+
+impl<'t> Lexer<'t> {
+    pub(crate) fn new(text: &'t str) -> Self {
+        Self {
+            text,
+            pos: 0,
+            proto: false,
+        }
+    }
+
+    // Creates a lexer of a `.proto` file.
+    pub(crate) fn proto(text: &'t str) -> Self {
+        Self {
+            text,
+            pos: 0,
+            proto: true,
+        }
+    }
+
+    pub(crate) fn error(&self, kind: ParseErrorKind, pos: usize) -> ParseError {
+        ParseError::new(kind, self.text, pos)
+    }
+
+    // Skips whitespace and comments: `#` comments in the text format, and `//` and `/* */`
+    // comments in `.proto` files.
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.text[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            let line_comment = if self.proto { "//" } else { "#" };
+            if trimmed.starts_with(line_comment) {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if self.proto && trimmed.starts_with("/*") {
+                self.pos += trimmed[2..].find("*/").map_or(trimmed.len(), |i| i + 4);
+            } else {
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Result<(usize, Token<'t>), ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let rest = &self.text[start..];
+        let Some(c) = rest.chars().next() else {
+            return Ok((start, Token::End));
+        };
+        let (len, token): (usize, fn(&'t str) -> Token<'t>) = if c.is_ascii_alphabetic() || c == '_'
+        {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            (len, Token::Ident)
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            (number_len(rest), Token::Number)
+        } else if c == '"' || c == '\'' {
+            let len = string_len(rest, c as u8)
+                .ok_or_else(|| self.error(ParseErrorKind::InvalidString, start))?;
+            (len, Token::Str)
+        } else {
+            self.pos += c.len_utf8();
+            return Ok((start, Token::Symbol(c)));
+        };
+        self.pos += len;
+        Ok((start, token(&rest[..len])))
+    }
+
+    pub(crate) fn peek(&self) -> Result<(usize, Token<'t>), ParseError> {
+        let mut lexer = *self;
+        lexer.next()
+    }
+}
+
+// Returns the length of the number at the start of `s`, including any suffix and exponent.
+fn number_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let hex = s.starts_with("0x") || s.starts_with("0X");
+    let mut len = 1;
+    while let Some(&b) = bytes.get(len) {
+        let sign = (b == b'+' || b == b'-') && !hex && matches!(bytes[len - 1], b'e' | b'E');
+        if !(b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || sign) {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+// Returns the length of the string quoted by `quote` at the start of `s`, or `None` if it is not
+// terminated on the same line.
+fn string_len(s: &str, quote: u8) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut i = 1;
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            b'\\' => i += 1,
+            b'\n' => return None,
+            _ if b == quote => return Some(i + 1),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// Appends the contents of a quoted string token to `out`, processing escapes. On an invalid
+// escape, returns `Err` with the byte offset of the escape in the token.
+pub(crate) fn unescape(token: &str, out: &mut Vec<u8>) -> Result<(), usize> {
+    let bytes = &token.as_bytes()[1..token.len() - 1];
+    let mut i = 0;
+    while let Some(&b) = bytes.get(i) {
+        i += 1;
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        // The offset of the backslash in `token`, after the opening quote.
+        let start = i;
+        let c = bytes[i];
+        i += 1;
+        match c {
+            b'a' => out.push(0x07),
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'v' => out.push(0x0b),
+            b'\\' | b'\'' | b'"' | b'?' => out.push(c),
+            b'0'..=b'7' => {
+                let mut value = u32::from(c - b'0');
+                while i < start + 3 {
+                    match bytes.get(i) {
+                        Some(&d @ b'0'..=b'7') => value = value * 8 + u32::from(d - b'0'),
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                out.push(u8::try_from(value).map_err(|_| start)?);
+            }
+            b'x' | b'X' => {
+                let value = hex_digits(bytes, &mut i, 1, 2).ok_or(start)?;
+                out.push(value as u8);
+            }
+            b'u' | b'U' => {
+                let len = if c == b'u' { 4 } else { 8 };
+                let value = hex_digits(bytes, &mut i, len, len).ok_or(start)?;
+                let c = char::from_u32(value).ok_or(start)?;
+                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            _ => return Err(start),
+        }
+    }
+    Ok(())
+}
+
+// Reads between `min` and `max` hexadecimal digits at `*i`.
+fn hex_digits(bytes: &[u8], i: &mut usize, min: usize, max: usize) -> Option<u32> {
+    let mut value = 0;
+    let mut len = 0;
+    while len < max {
+        let Some(digit) = bytes.get(*i).and_then(|&b| (b as char).to_digit(16)) else {
+            break;
+        };
+        value = value * 16 + digit;
+        *i += 1;
+        len += 1;
+    }
+    (len >= min).then_some(value)
+}
@@ -0,0 +1,199 @@
+#[cfg(feature = "alloc")]
+pub(crate) mod lex;
+#[cfg(feature = "alloc")]
+pub(crate) mod parse;
+#[cfg(feature = "alloc")]
+mod print;
+mod raw;
+
+#[cfg(feature = "alloc")]
+pub use parse::parse;
+#[cfg(feature = "alloc")]
+pub use print::print;
+pub use raw::decode_raw;
+
+use crate::Error;
+use core::fmt;
+
+/// The error type returned when printing a message fails.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PrintError {
+    /// The message could not be decoded.
+    Decode(Error),
+    /// The writer returned an error.
+    Fmt,
+    /// The type of a `google.protobuf.Any` value is not known.
+    UnknownType,
+    /// A value of a well-known type has no representation in the output format.
+    InvalidValue,
+}
+
+impl From<Error> for PrintError {
+    #[inline]
+    fn from(error: Error) -> Self {
+        PrintError::Decode(error)
+    }
+}
+
+impl From<fmt::Error> for PrintError {
+    #[inline]
+    fn from(_: fmt::Error) -> Self {
+        PrintError::Fmt
+    }
+}
+
+impl fmt::Display for PrintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrintError::Decode(e) => e.fmt(f),
+            PrintError::Fmt => f.write_str("formatter error"),
+            PrintError::UnknownType => f.write_str("unknown type"),
+            PrintError::InvalidValue => f.write_str("invalid value"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PrintError {}
+
+/// The error type returned when parsing text fails.
+///
+/// A `ParseError` records what went wrong and where, as a line and column. Both are counted from
+/// 1, and columns are counted in characters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    line: usize,
+    column: usize,
+}
+
+impl ParseError {
+    // Creates an error at the byte position `pos` of `text`.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn new(kind: ParseErrorKind, text: &str, pos: usize) -> Self {
+        let before = &text[..pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            kind,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// Returns the kind of error.
+    #[inline]
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Returns the line at which the error occurred.
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column at which the error occurred.
+    #[inline]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+/// The kind of a [`ParseError`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// The text ends in the middle of a message or value.
+    UnexpectedEnd,
+    /// A token is not valid at its position.
+    UnexpectedToken,
+    /// A string is not terminated on the line it starts on, or contains an invalid escape.
+    InvalidString,
+    /// A value is not valid for the type of its field, or is out of range.
+    InvalidValue,
+    /// A field name or extension name is not known.
+    UnknownField,
+    /// An enum value name is not known.
+    UnknownEnumValue,
+    /// The type of a `google.protobuf.Any` value is not known.
+    UnknownType,
+    /// A non-repeated field is given more than once, or more than one field of a oneof is given.
+    DuplicateField,
+    /// Messages are nested too deeply.
+    DepthLimitExceeded,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseErrorKind::UnexpectedEnd => "unexpected end of input",
+            ParseErrorKind::UnexpectedToken => "unexpected token",
+            ParseErrorKind::InvalidString => "invalid string",
+            ParseErrorKind::InvalidValue => "invalid value",
+            ParseErrorKind::UnknownField => "unknown field",
+            ParseErrorKind::UnknownEnumValue => "unknown enum value",
+            ParseErrorKind::UnknownType => "unknown type",
+            ParseErrorKind::DuplicateField => "duplicate field",
+            ParseErrorKind::DepthLimitExceeded => "depth limit exceeded",
+        })
+    }
+}
+
+// Writes `bytes` as the contents of a quoted string, escaped as in C. If `utf8` is set, `bytes` is
+// valid UTF-8 and non-ASCII characters are written as they are; otherwise they are escaped.
+pub(crate) fn write_escaped<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    bytes: &[u8],
+    utf8: bool,
+) -> fmt::Result {
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        let escape = match byte {
+            b'\n' => "\\n",
+            b'\r' => "\\r",
+            b'\t' => "\\t",
+            b'"' => "\\\"",
+            b'\'' => "\\'",
+            b'\\' => "\\\\",
+            0x20..=0x7e => continue,
+            0x80.. if utf8 => continue,
+            _ => "",
+        };
+        // `start..i` contains only whole characters, since escaped bytes are ASCII or `utf8` is
+        // not set and every non-ASCII byte is escaped.
+        write_str(out, &bytes[start..i])?;
+        if escape.is_empty() {
+            write!(out, "\\{:03o}", byte)?;
+        } else {
+            out.write_str(escape)?;
+        }
+        start = i + 1;
+    }
+    write_str(out, &bytes[start..])
+}
+
+fn write_str<W: fmt::Write + ?Sized>(out: &mut W, bytes: &[u8]) -> fmt::Result {
+    // Unescaped runs are ASCII, or UTF-8 if allowed by the caller.
+    out.write_str(core::str::from_utf8(bytes).map_err(|_| fmt::Error)?)
+}
+
+fn write_indent<W: fmt::Write + ?Sized>(out: &mut W, indent: usize) -> fmt::Result {
+    for _ in 0..indent {
+        out.write_str("  ")?;
+    }
+    Ok(())
+}
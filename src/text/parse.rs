@@ -0,0 +1,549 @@
+use super::lex::{unescape, Lexer, Token};
+use super::{ParseError, ParseErrorKind};
+use crate::descriptor::{DescriptorPool, FieldDescriptor, FieldType, MessageDescriptor};
+use crate::encoding::{zigzag, WIRE_TYPE_FIXED64, WIRE_TYPE_VARINT};
+use crate::message::MAX_DEPTH;
+use crate::writer::MessageWriter;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Parses a message in the protobuf text format, using a descriptor, and returns its encoding.
+///
+/// `text` is parsed as a message of the type `descriptor`, with referenced types and extensions
+/// looked up in `pool`. The syntax accepted is that written by [`print`](super::print), including
+/// `[pkg.extension]` names and expanded `google.protobuf.Any` values, as well as comments starting
+/// with `#`, `<...>` in place of `{...}`, list values such as `[1, 2]` for repeated fields,
+/// and optional `;` or `,` after each field.
+///
+/// Fields are encoded in the order they are given, with repeated fields of packable types packed
+/// if the field is packed. If the text is not valid, a [`ParseError`] is returned with the line
+/// and column at which parsing failed.
+pub fn parse(
+    pool: &DescriptorPool,
+    descriptor: &MessageDescriptor,
+    text: &str,
+) -> Result<Vec<u8>, ParseError> {
+    let mut parser = Parser {
+        pool,
+        lexer: Lexer::new(text),
+        depth: 0,
+    };
+    Ok(parser.parse_fields(descriptor, None)?.into_vec())
+}
+
+// The encoding of a scalar value.
+pub(crate) enum Scalar {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    Bytes(Vec<u8>),
+}
+
+impl Scalar {
+    // Encodes an integer as a value of a numeric or enum type. Returns `None` if it is out of
+    // range, or the type is not numeric.
+    pub(crate) fn from_int(field_type: FieldType, value: i128) -> Option<Self> {
+        Some(match field_type {
+            FieldType::Double => Scalar::Fixed64((value as f64).to_bits()),
+            FieldType::Float => Scalar::Fixed32((value as f32).to_bits()),
+            FieldType::Int64 => Scalar::Varint(i64::try_from(value).ok()? as u64),
+            FieldType::Uint64 => Scalar::Varint(u64::try_from(value).ok()?),
+            FieldType::Int32 | FieldType::Enum(_) => {
+                Scalar::Varint(i32::try_from(value).ok()? as u64)
+            }
+            FieldType::Fixed64 => Scalar::Fixed64(u64::try_from(value).ok()?),
+            FieldType::Fixed32 => Scalar::Fixed32(u32::try_from(value).ok()?),
+            FieldType::Uint32 => Scalar::Varint(u32::try_from(value).ok()?.into()),
+            FieldType::Sfixed32 => Scalar::Fixed32(i32::try_from(value).ok()? as u32),
+            FieldType::Sfixed64 => Scalar::Fixed64(i64::try_from(value).ok()? as u64),
+            FieldType::Sint32 => {
+                Scalar::Varint(zigzag::encode_32(i32::try_from(value).ok()?).into())
+            }
+            FieldType::Sint64 => Scalar::Varint(zigzag::encode_64(i64::try_from(value).ok()?)),
+            FieldType::Bool
+            | FieldType::String
+            | FieldType::Bytes
+            | FieldType::Message(_)
+            | FieldType::Group(_) => return None,
+        })
+    }
+
+    // Returns the bits of a numeric value.
+    fn bits(&self) -> u64 {
+        match self {
+            Scalar::Varint(v) | Scalar::Fixed64(v) => *v,
+            Scalar::Fixed32(v) => u64::from(*v),
+            Scalar::Bytes(_) => unreachable!(),
+        }
+    }
+}
+
+struct Parser<'t, 'd> {
+    pool: &'d DescriptorPool,
+    lexer: Lexer<'t>,
+    depth: u32,
+}
+
+impl<'t, 'd> Parser<'t, 'd> {
+    fn error(&self, kind: ParseErrorKind, pos: usize) -> ParseError {
+        self.lexer.error(kind, pos)
+    }
+
+    fn unexpected(&self, pos: usize, token: Token<'_>) -> ParseError {
+        match token {
+            Token::End => self.error(ParseErrorKind::UnexpectedEnd, pos),
+            _ => self.error(ParseErrorKind::UnexpectedToken, pos),
+        }
+    }
+
+    // Returns the error for a token that is not a valid value of a field.
+    fn invalid(&self, pos: usize, token: Token<'_>) -> ParseError {
+        match token {
+            Token::Ident(_) | Token::Number(_) | Token::Str(_) => {
+                self.error(ParseErrorKind::InvalidValue, pos)
+            }
+            _ => self.unexpected(pos, token),
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        if token != Token::Symbol(symbol) {
+            return Err(self.unexpected(pos, token));
+        }
+        Ok(())
+    }
+
+    // Consumes `symbol` if it is the next token. Returns `true` if it was.
+    fn consume(&mut self, symbol: char) -> Result<bool, ParseError> {
+        let (_, token) = self.lexer.peek()?;
+        if token != Token::Symbol(symbol) {
+            return Ok(false);
+        }
+        self.lexer.next()?;
+        Ok(true)
+    }
+
+    // Parses fields up to the symbol `end`, or to the end of the text if `end` is `None`.
+    fn parse_fields(
+        &mut self,
+        descriptor: &'d MessageDescriptor,
+        end: Option<char>,
+    ) -> Result<MessageWriter, ParseError> {
+        let mut w = MessageWriter::new();
+        // The numbers of non-repeated fields and the oneofs given so far.
+        let mut seen = Vec::new();
+        let mut oneofs = Vec::new();
+        loop {
+            let (pos, token) = self.lexer.next()?;
+            let field = match token {
+                Token::Symbol(c) if Some(c) == end => return Ok(w),
+                Token::End if end.is_none() => return Ok(w),
+                Token::Ident(name) => self
+                    .find_field(descriptor, name)
+                    .ok_or_else(|| self.error(ParseErrorKind::UnknownField, pos))?,
+                Token::Symbol('[') => {
+                    let name = self.parse_bracketed_name()?;
+                    if let Some((_, type_name)) = name.rsplit_once('/') {
+                        if descriptor.full_name() != "google.protobuf.Any" {
+                            return Err(self.error(ParseErrorKind::UnknownField, pos));
+                        }
+                        if seen.contains(&1) || seen.contains(&2) {
+                            return Err(self.error(ParseErrorKind::DuplicateField, pos));
+                        }
+                        let value = self
+                            .pool
+                            .find_message(type_name)
+                            .ok_or_else(|| self.error(ParseErrorKind::UnknownType, pos))?;
+                        self.consume(':')?;
+                        let fields = self.parse_message(value)?;
+                        w.add_string(1, &name);
+                        w.add_bytes(2, &fields);
+                        seen.extend([1, 2]);
+                        self.consume_separator()?;
+                        continue;
+                    }
+                    self.pool
+                        .find_extension(&name)
+                        .filter(|extension| extension.extendee() == Some(descriptor.id()))
+                        .ok_or_else(|| self.error(ParseErrorKind::UnknownField, pos))?
+                }
+                _ => return Err(self.unexpected(pos, token)),
+            };
+            if !field.is_repeated() {
+                let oneof = field.oneof();
+                if seen.contains(&field.number()) || oneof.is_some_and(|o| oneofs.contains(&o)) {
+                    return Err(self.error(ParseErrorKind::DuplicateField, pos));
+                }
+                seen.push(field.number());
+                oneofs.extend(oneof);
+            }
+            self.parse_field(&mut w, field)?;
+            self.consume_separator()?;
+        }
+    }
+
+    fn consume_separator(&mut self) -> Result<(), ParseError> {
+        if !self.consume(';')? {
+            self.consume(',')?;
+        }
+        Ok(())
+    }
+
+    // Finds a field by name. Groups are also found by the name of their type.
+    fn find_field(
+        &self,
+        descriptor: &'d MessageDescriptor,
+        name: &str,
+    ) -> Option<&'d FieldDescriptor> {
+        descriptor.field_by_name(name).or_else(|| {
+            descriptor
+                .fields()
+                .iter()
+                .find(|field| match field.field_type() {
+                    FieldType::Group(id) => self.pool.message(id).name() == name,
+                    _ => false,
+                })
+        })
+    }
+
+    // Parses an extension name or `Any` type URL after `[`, up to and including `]`.
+    fn parse_bracketed_name(&mut self) -> Result<String, ParseError> {
+        let mut name = String::new();
+        loop {
+            let (pos, token) = self.lexer.next()?;
+            match token {
+                Token::Ident(ident) => name.push_str(ident),
+                Token::Symbol(c @ ('.' | '/')) if !name.is_empty() => name.push(c),
+                Token::Symbol(']') if !name.is_empty() => return Ok(name),
+                _ => return Err(self.unexpected(pos, token)),
+            }
+        }
+    }
+
+    fn parse_field(
+        &mut self,
+        w: &mut MessageWriter,
+        field: &'d FieldDescriptor,
+    ) -> Result<(), ParseError> {
+        let field_type = field.field_type();
+        if let FieldType::Message(id) | FieldType::Group(id) = field_type {
+            let descriptor = self.pool.message(id);
+            self.consume(':')?;
+            let list = field.is_repeated() && self.consume('[')?;
+            if list && self.consume(']')? {
+                return Ok(());
+            }
+            loop {
+                let fields = self.parse_message(descriptor)?;
+                match field_type {
+                    FieldType::Group(_) => w.add_group(field.number(), &fields),
+                    _ => w.add_bytes(field.number(), &fields),
+                }
+                if !list || self.parse_list_separator()? {
+                    return Ok(());
+                }
+            }
+        }
+
+        self.expect(':')?;
+        let list = field.is_repeated() && self.consume('[')?;
+        let mut values = Vec::new();
+        if !(list && self.consume(']')?) {
+            loop {
+                values.push(self.parse_scalar(field)?);
+                if !list || self.parse_list_separator()? {
+                    break;
+                }
+            }
+        }
+        write_scalars(w, field, &values);
+        Ok(())
+    }
+
+    // Parses the separator after a value in a list. Returns `true` at the end of the list.
+    fn parse_list_separator(&mut self) -> Result<bool, ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        match token {
+            Token::Symbol(',') => Ok(false),
+            Token::Symbol(']') => Ok(true),
+            _ => Err(self.unexpected(pos, token)),
+        }
+    }
+
+    // Parses a message value in braces or angle brackets, and returns its encoded fields.
+    fn parse_message(&mut self, descriptor: &'d MessageDescriptor) -> Result<Vec<u8>, ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        let end = match token {
+            Token::Symbol('{') => '}',
+            Token::Symbol('<') => '>',
+            _ => return Err(self.unexpected(pos, token)),
+        };
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(ParseErrorKind::DepthLimitExceeded, pos));
+        }
+        self.depth += 1;
+        let fields = self.parse_fields(descriptor, Some(end))?;
+        self.depth -= 1;
+        Ok(fields.into_vec())
+    }
+
+    fn parse_scalar(&mut self, field: &FieldDescriptor) -> Result<Scalar, ParseError> {
+        let (pos, token) = self.lexer.peek()?;
+        Ok(match field.field_type() {
+            FieldType::Double => Scalar::Fixed64(self.parse_float()?.to_bits()),
+            FieldType::Float => Scalar::Fixed32((self.parse_float()? as f32).to_bits()),
+            FieldType::Bool => {
+                self.lexer.next()?;
+                match token {
+                    Token::Ident("true" | "True" | "t") | Token::Number("1") => Scalar::Varint(1),
+                    Token::Ident("false" | "False" | "f") | Token::Number("0") => Scalar::Varint(0),
+                    _ => return Err(self.invalid(pos, token)),
+                }
+            }
+            FieldType::String => {
+                let bytes = self.parse_string()?;
+                if core::str::from_utf8(&bytes).is_err() {
+                    return Err(self.error(ParseErrorKind::InvalidValue, pos));
+                }
+                Scalar::Bytes(bytes)
+            }
+            FieldType::Bytes => Scalar::Bytes(self.parse_string()?),
+            FieldType::Enum(id) if matches!(token, Token::Ident(_)) => {
+                let Token::Ident(name) = self.lexer.next()?.1 else {
+                    unreachable!()
+                };
+                let value = self
+                    .pool
+                    .enum_type(id)
+                    .value_by_name(name)
+                    .ok_or_else(|| self.error(ParseErrorKind::UnknownEnumValue, pos))?;
+                Scalar::Varint(value.number() as u64)
+            }
+            field_type => Scalar::from_int(field_type, self.parse_int()?)
+                .ok_or_else(|| self.error(ParseErrorKind::InvalidValue, pos))?,
+        })
+    }
+
+    // Parses an optional minus sign and the token after it.
+    fn parse_sign(&mut self) -> Result<(usize, bool, Token<'t>), ParseError> {
+        let (pos, token) = self.lexer.next()?;
+        if token != Token::Symbol('-') {
+            return Ok((pos, false, token));
+        }
+        Ok((pos, true, self.lexer.next()?.1))
+    }
+
+    // Parses a decimal, hexadecimal or octal integer.
+    fn parse_int(&mut self) -> Result<i128, ParseError> {
+        let (pos, negative, token) = self.parse_sign()?;
+        let Token::Number(digits) = token else {
+            return Err(self.invalid(pos, token));
+        };
+        let magnitude = parse_digits(digits)
+            .map(i128::from)
+            .ok_or_else(|| self.error(ParseErrorKind::InvalidValue, pos))?;
+        Ok(if negative { -magnitude } else { magnitude })
+    }
+
+    // Parses a floating-point number, an integer, or `inf` or `nan` in any case.
+    fn parse_float(&mut self) -> Result<f64, ParseError> {
+        let (pos, negative, token) = self.parse_sign()?;
+        let value = match token {
+            Token::Number(digits) => parse_digits(digits)
+                .map(|value| value as f64)
+                .or_else(|| {
+                    let digits = digits.strip_suffix(['f', 'F']).unwrap_or(digits);
+                    digits.parse().ok()
+                })
+                .ok_or_else(|| self.error(ParseErrorKind::InvalidValue, pos))?,
+            Token::Ident(name)
+                if name.eq_ignore_ascii_case("inf") || name.eq_ignore_ascii_case("infinity") =>
+            {
+                f64::INFINITY
+            }
+            Token::Ident(name) if name.eq_ignore_ascii_case("nan") => f64::NAN,
+            _ => return Err(self.invalid(pos, token)),
+        };
+        Ok(if negative { -value } else { value })
+    }
+
+    // Parses one or more adjacent strings, and returns their concatenated contents.
+    fn parse_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let mut bytes = Vec::new();
+        let (pos, token) = self.lexer.next()?;
+        let Token::Str(mut s) = token else {
+            return Err(self.invalid(pos, token));
+        };
+        let mut pos = pos;
+        loop {
+            unescape(s, &mut bytes)
+                .map_err(|offset| self.error(ParseErrorKind::InvalidString, pos + offset))?;
+            match self.lexer.peek()? {
+                (next, Token::Str(next_s)) => {
+                    self.lexer.next()?;
+                    pos = next;
+                    s = next_s;
+                }
+                _ => return Ok(bytes),
+            }
+        }
+    }
+}
+
+// Writes values of a field, packed if the field is packed.
+pub(crate) fn write_scalars(w: &mut MessageWriter, field: &FieldDescriptor, values: &[Scalar]) {
+    let number = field.number();
+    if field.is_packed() {
+        let bits = values.iter().map(Scalar::bits);
+        match field.field_type().wire_type() {
+            WIRE_TYPE_VARINT => w.add_packed_uint64(number, bits),
+            WIRE_TYPE_FIXED64 => w.add_packed_fixed64(number, bits),
+            _ => w.add_packed_fixed32(number, bits.map(|v| v as u32)),
+        }
+        return;
+    }
+    for value in values {
+        match value {
+            Scalar::Varint(v) => w.add_uint64(number, *v),
+            Scalar::Fixed64(v) => w.add_fixed64(number, *v),
+            Scalar::Fixed32(v) => w.add_fixed32(number, *v),
+            Scalar::Bytes(v) => w.add_bytes(number, v),
+        }
+    }
+}
+
+// Parses a decimal, hexadecimal or octal integer.
+pub(crate) fn parse_digits(digits: &str) -> Option<u64> {
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16).ok()
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8).ok()
+    } else {
+        digits.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::descriptor::tests::{addressbook_file, encode_files, test_pool, well_known_file};
+    use crate::descriptor::DescriptorPool;
+    use crate::message::Message;
+    use crate::text::{print, ParseErrorKind};
+    use crate::writer::MessageWriter;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn parse_address_book() {
+        let pool = test_pool();
+        let address_book = pool.find_message("tutorial.AddressBook").unwrap();
+        let text = r#"
+            # A person.
+            people {
+              name: "Alice" ' Smith'
+              id: 0x10
+              phones: [{ number: "555-4321", type: HOME }, < type: 2 >]
+            }
+            people: {}
+        "#;
+        let buf = parse(&pool, address_book, text).unwrap();
+
+        let mut person = MessageWriter::new();
+        person.add_string(1, "Alice Smith");
+        person.add_int32(2, 16);
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-4321");
+        phone.add_enum(2, 1);
+        phone.close();
+        let mut phone = person.open_message(4);
+        phone.add_enum(2, 2);
+        phone.close();
+        let mut w = MessageWriter::new();
+        w.add_message(1, Message::new(person.as_bytes()));
+        w.add_bytes(1, b"");
+        assert_eq!(buf, w.into_vec());
+
+        let people: Vec<_> = Message::new(&buf)
+            .fields()
+            .map(|field| field.unwrap().number)
+            .collect();
+        assert_eq!(people, [1, 1]);
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let pool = test_pool();
+        let test = pool.find_message("test.Test").unwrap();
+        let text = "counts {\n  key: \"a\"\n  value: -1\n}\n\
+                    text: \"\u{e9}\\n\\\"\"\n\
+                    packed: 1\npacked: 2\npacked: 3\nunpacked: -1\nunpacked: 2\n\
+                    Item {\n  data: \"\\000\\377\"\n}\n\
+                    ratio: -inf\n\
+                    child {\n  ratio: 0.25\n}\n\
+                    [test.note]: \"note\"\n";
+        let input = text.replace("packed: 1\npacked: 2\n", "packed: [1, 2]\n");
+        let buf = parse(&pool, test, &input).unwrap();
+        let mut out = String::new();
+        print(&mut out, &pool, test, Message::new(&buf)).unwrap();
+        assert_eq!(out, text);
+
+        let files = [addressbook_file(), well_known_file()];
+        let pool = DescriptorPool::decode(&encode_files(&files)).unwrap();
+        let any = pool.find_message("google.protobuf.Any").unwrap();
+        let text = "[type.googleapis.com/tutorial.Person] {\n  name: \"Alice\"\n}\n";
+        let buf = parse(&pool, any, text).unwrap();
+        out.clear();
+        print(&mut out, &pool, any, Message::new(&buf)).unwrap();
+        assert_eq!(out, text);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let pool = test_pool();
+        let test = pool.find_message("test.Test").unwrap();
+        let error = |text| {
+            let error = parse(&pool, test, text).unwrap_err();
+            (error.kind(), error.line(), error.column())
+        };
+        assert_eq!(
+            error("ratio: 1\nnope: 2"),
+            (ParseErrorKind::UnknownField, 2, 1)
+        );
+        assert_eq!(
+            error("child {\n  ratio: 1"),
+            (ParseErrorKind::UnexpectedEnd, 2, 11)
+        );
+        assert_eq!(error("ratio 1"), (ParseErrorKind::UnexpectedToken, 1, 7));
+        assert_eq!(
+            error("packed: 2147483648"),
+            (ParseErrorKind::InvalidValue, 1, 9)
+        );
+        assert_eq!(
+            error("text: \"\u{e9}\\q\""),
+            (ParseErrorKind::InvalidString, 1, 9)
+        );
+        assert_eq!(error("text: \"a"), (ParseErrorKind::InvalidString, 1, 7));
+        assert_eq!(
+            error("text: \"a\" person {}"),
+            (ParseErrorKind::DuplicateField, 1, 11)
+        );
+        assert_eq!(
+            error("person { phones { type: CELL } }"),
+            (ParseErrorKind::UnknownEnumValue, 1, 25)
+        );
+        assert_eq!(
+            error("[test.other]: 1"),
+            (ParseErrorKind::UnknownField, 1, 1)
+        );
+        let deep = "child {".repeat(101);
+        assert_eq!(
+            error(&deep),
+            (ParseErrorKind::DepthLimitExceeded, 1, 7 * 100 + 7)
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use super::{raw, write_escaped, write_indent, PrintError};
+use crate::descriptor::{DescriptorPool, FieldDescriptor, FieldType, MessageDescriptor};
+use crate::dynamic::{DynamicMessage, Value};
+use crate::field::FieldValue;
+use crate::message::Message;
+use core::fmt;
+
+/// Writes a message in the protobuf text format, using a descriptor.
+///
+/// `message` is decoded as a message of the type `descriptor`, with referenced types and
+/// extensions looked up in `pool`. Each field is written on its own line, with nested messages
+/// indented in braces:
+///
+/// ```text
+/// name: "Alice"
+/// phones {
+///   number: "555-4321"
+///   type: HOME
+/// }
+/// ```
+///
+/// Fields are written in field number order, with extensions named by their fully-qualified name
+/// in brackets. A `google.protobuf.Any` whose type is found in `pool` is expanded as
+/// `[type_url] { ... }`. Unknown fields are written last, as by [`decode_raw`](super::decode_raw).
+///
+/// If the message cannot be decoded, or has messages nested more than 100 deep,
+/// [`PrintError::Decode`] is returned.
+pub fn print<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    pool: &DescriptorPool,
+    descriptor: &MessageDescriptor,
+    message: Message<'_>,
+) -> Result<(), PrintError> {
+    let message = DynamicMessage::decode(pool, descriptor, message)?;
+    Printer { out, pool }.write_message(&message, 0)
+}
+
+struct Printer<'w, 'd, W: ?Sized> {
+    out: &'w mut W,
+    pool: &'d DescriptorPool,
+}
+
+impl<W: fmt::Write + ?Sized> Printer<'_, '_, W> {
+    fn write_message(
+        &mut self,
+        message: &DynamicMessage<'_, '_>,
+        depth: usize,
+    ) -> Result<(), PrintError> {
+        if self.write_any(message, depth)? {
+            return Ok(());
+        }
+        for (field, value) in message.fields() {
+            match value {
+                Value::List(values) => {
+                    for value in values {
+                        self.write_field(field, value, depth)?;
+                    }
+                }
+                Value::Map(entries) => {
+                    let FieldType::Message(entry) = field.field_type() else {
+                        unreachable!()
+                    };
+                    let entry = self.pool.message(entry);
+                    for (key, value) in entries {
+                        self.write_name(field, depth)?;
+                        self.out.write_str(" {\n")?;
+                        // Map entry types always have a key and a value field.
+                        self.write_field(entry.field(1).unwrap(), key, depth + 1)?;
+                        self.write_field(entry.field(2).unwrap(), value, depth + 1)?;
+                        write_indent(self.out, depth)?;
+                        self.out.write_str("}\n")?;
+                    }
+                }
+                value => self.write_field(field, value, depth)?,
+            }
+        }
+        for field in message.unknown_fields() {
+            raw::write_field(self.out, field, depth)?;
+        }
+        Ok(())
+    }
+
+    // Writes an `Any` as its type URL and expanded value. Returns `false` if the message is not an
+    // `Any`, or its value cannot be decoded.
+    fn write_any(
+        &mut self,
+        message: &DynamicMessage<'_, '_>,
+        depth: usize,
+    ) -> Result<bool, PrintError> {
+        if message.descriptor().full_name() != "google.protobuf.Any"
+            || !message.unknown_fields().is_empty()
+        {
+            return Ok(false);
+        }
+        let Some(Value::String(type_url)) = message.get(1) else {
+            return Ok(false);
+        };
+        let Some(descriptor) = type_url
+            .rsplit_once('/')
+            .and_then(|(_, name)| self.pool.find_message(name))
+        else {
+            return Ok(false);
+        };
+        // The value is decoded as a message nested in the `Any`, with the options and limits of
+        // the `Any`. A value merged from an earlier occurrence of the `Any` cannot be located.
+        let value = match (message.get(2), message.source().get_last(2)) {
+            (None, _) => DynamicMessage::empty(descriptor),
+            (Some(Value::Bytes(_)), Ok(Some(FieldValue::LengthDelimited(value)))) => {
+                match DynamicMessage::decode(self.pool, descriptor, value.get_message()) {
+                    Ok(value) => value,
+                    Err(_) => return Ok(false),
+                }
+            }
+            _ => return Ok(false),
+        };
+        write_indent(self.out, depth)?;
+        writeln!(self.out, "[{}] {{", type_url)?;
+        self.write_message(&value, depth + 1)?;
+        write_indent(self.out, depth)?;
+        self.out.write_str("}\n")?;
+        Ok(true)
+    }
+
+    fn write_name(&mut self, field: &FieldDescriptor, depth: usize) -> fmt::Result {
+        write_indent(self.out, depth)?;
+        match field.field_type() {
+            _ if field.extendee().is_some() => write!(self.out, "[{}]", field.full_name()),
+            // Groups are named by their type.
+            FieldType::Group(id) => self.out.write_str(self.pool.message(id).name()),
+            _ => self.out.write_str(field.name()),
+        }
+    }
+
+    fn write_field(
+        &mut self,
+        field: &FieldDescriptor,
+        value: &Value<'_, '_>,
+        depth: usize,
+    ) -> Result<(), PrintError> {
+        self.write_name(field, depth)?;
+        if let Value::Message(message) = value {
+            self.out.write_str(" {\n")?;
+            self.write_message(message, depth + 1)?;
+            write_indent(self.out, depth)?;
+            self.out.write_str("}\n")?;
+            return Ok(());
+        }
+        self.out.write_str(": ")?;
+        match value {
+            Value::Bool(v) => write!(self.out, "{}", v)?,
+            Value::I32(v) => write!(self.out, "{}", v)?,
+            Value::I64(v) => write!(self.out, "{}", v)?,
+            Value::U32(v) => write!(self.out, "{}", v)?,
+            Value::U64(v) => write!(self.out, "{}", v)?,
+            Value::F32(v) => write_float(self.out, *v as f64, v)?,
+            Value::F64(v) => write_float(self.out, *v, v)?,
+            Value::String(v) => {
+                self.out.write_char('"')?;
+                write_escaped(self.out, v.as_bytes(), true)?;
+                self.out.write_char('"')?;
+            }
+            Value::Bytes(v) => {
+                self.out.write_char('"')?;
+                write_escaped(self.out, v, false)?;
+                self.out.write_char('"')?;
+            }
+            Value::Enum(v) => {
+                let name = match field.field_type() {
+                    FieldType::Enum(id) => self.pool.enum_type(id).value(*v).map(|v| v.name()),
+                    _ => None,
+                };
+                match name {
+                    Some(name) => self.out.write_str(name)?,
+                    None => write!(self.out, "{}", v)?,
+                }
+            }
+            Value::Message(_) | Value::List(_) | Value::Map(_) => unreachable!(),
+        }
+        self.out.write_char('\n')?;
+        Ok(())
+    }
+}
+
+// Writes a float or double as its shortest representation, with `inf` and `nan` for non-finite
+// values. Very large and very small magnitudes are written in exponent form.
+fn write_float<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    value: f64,
+    display: &(impl fmt::Display + fmt::LowerExp),
+) -> fmt::Result {
+    if value.is_nan() {
+        out.write_str("nan")
+    } else if value.is_infinite() {
+        out.write_str(if value < 0.0 { "-inf" } else { "inf" })
+    } else if value != 0.0 && !(1e-6..1e21).contains(&value.abs()) {
+        write!(out, "{:e}", display)
+    } else {
+        write!(out, "{}", display)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::print;
+    use crate::descriptor::tests::{addressbook_file, encode_files, test_pool, well_known_file};
+    use crate::descriptor::DescriptorPool;
+    use crate::message::{DecodeOptions, Message};
+    use crate::text::PrintError;
+    use crate::writer::MessageWriter;
+    use crate::{Error, ErrorKind};
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    fn person() -> MessageWriter {
+        let mut person = MessageWriter::new();
+        person.add_string(1, "Alice");
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-4321");
+        phone.add_enum(2, 2);
+        phone.close();
+        person
+    }
+
+    #[test]
+    fn print_test() {
+        let pool = test_pool();
+        let test = pool.find_message("test.Test").unwrap();
+        let mut w = MessageWriter::new();
+        let mut entry = w.open_message(1);
+        entry.add_string(1, "a");
+        entry.add_int32(2, 1);
+        entry.close();
+        w.add_message(3, Message::new(person().as_bytes()));
+        w.add_packed_int32(4, [1, 2]);
+        w.add_sint64(5, -1);
+        w.add_double(8, 0.5);
+        let mut child = w.open_message(9);
+        child.add_double(8, f64::NEG_INFINITY);
+        child.add_string(2, "\u{e9}\n");
+        child.close();
+        w.add_string(100, "note");
+        w.add_uint64(300, 5);
+        let mut buf = w.into_vec();
+        // Item { data: "\0\xff" }
+        buf.extend_from_slice(b"\x33\x3a\x02\x00\xff\x34");
+
+        let mut out = String::new();
+        print(&mut out, &pool, test, Message::new(&buf)).unwrap();
+        assert_eq!(
+            out,
+            "counts {\n  key: \"a\"\n  value: 1\n}\n\
+             person {\n  name: \"Alice\"\n  phones {\n    number: \"555-4321\"\n    type: WORK\n  }\n}\n\
+             packed: 1\npacked: 2\nunpacked: -1\n\
+             Item {\n  data: \"\\000\\377\"\n}\n\
+             ratio: 0.5\n\
+             child {\n  text: \"\u{e9}\\n\"\n  ratio: -inf\n}\n\
+             [test.note]: \"note\"\n\
+             300: 5\n"
+        );
+
+        // Very large and very small magnitudes are written in exponent form.
+        for (value, expected) in [(1e300, "ratio: 1e300\n"), (-1.5e-7, "ratio: -1.5e-7\n")] {
+            let mut w = MessageWriter::new();
+            w.add_double(8, value);
+            out.clear();
+            print(&mut out, &pool, test, Message::new(w.as_bytes())).unwrap();
+            assert_eq!(out, expected);
+        }
+
+        assert_eq!(
+            print(&mut out, &pool, test, Message::new(b"\x08")),
+            Err(PrintError::Decode(Error::new(
+                ErrorKind::TruncatedVarint,
+                1
+            )))
+        );
+    }
+
+    #[test]
+    fn print_any() {
+        let files = [addressbook_file(), well_known_file()];
+        let pool = DescriptorPool::decode(&encode_files(&files)).unwrap();
+        let any = pool.find_message("google.protobuf.Any").unwrap();
+        let mut w = MessageWriter::new();
+        w.add_string(1, "type.googleapis.com/tutorial.Person");
+        w.add_bytes(2, person().as_bytes());
+        let mut out = String::new();
+        print(&mut out, &pool, any, Message::new(w.as_bytes())).unwrap();
+        assert_eq!(
+            out,
+            "[type.googleapis.com/tutorial.Person] {\n  name: \"Alice\"\n  phones {\n    \
+             number: \"555-4321\"\n    type: WORK\n  }\n}\n"
+        );
+
+        // Unresolvable types are written as plain messages.
+        let mut w = MessageWriter::new();
+        w.add_string(1, "type.googleapis.com/Unknown");
+        w.add_bytes(2, b"\x08\x01");
+        out.clear();
+        print(&mut out, &pool, any, Message::new(w.as_bytes())).unwrap();
+        assert_eq!(
+            out,
+            "type_url: \"type.googleapis.com/Unknown\"\nvalue: \"\\010\\001\"\n"
+        );
+
+        // The value is decoded with the options of the `Any`.
+        let mut w = MessageWriter::new();
+        w.add_string(1, "type.googleapis.com/tutorial.Person");
+        // id: 7, with a non-canonical varint.
+        w.add_bytes(2, b"\x10\x87\x00");
+        out.clear();
+        print(&mut out, &pool, any, Message::new(w.as_bytes())).unwrap();
+        assert_eq!(out, "[type.googleapis.com/tutorial.Person] {\n  id: 7\n}\n");
+        out.clear();
+        let strict = DecodeOptions::new().strict(true);
+        print(
+            &mut out,
+            &pool,
+            any,
+            Message::with_options(w.as_bytes(), strict),
+        )
+        .unwrap();
+        assert!(out.starts_with("type_url: "));
+
+        // Values nested too deeply are written as plain messages.
+        let mut buf = Vec::new();
+        for _ in 0..150 {
+            let mut w = MessageWriter::new();
+            w.add_string(1, "type.googleapis.com/google.protobuf.Any");
+            w.add_bytes(2, &buf);
+            buf = w.into_vec();
+        }
+        out.clear();
+        print(&mut out, &pool, any, Message::new(&buf)).unwrap();
+        assert_eq!(out.matches("/google.protobuf.Any] {").count(), 100);
+    }
+}
@@ -0,0 +1,180 @@
+use super::{write_escaped, write_indent, PrintError};
+use crate::field::{Field, FieldValue};
+use crate::message::{Message, MAX_DEPTH};
+use core::fmt;
+
+/// Writes the fields of a message without a schema, like `protoc --decode_raw`.
+///
+/// Each field is written on its own line as its number and value, with nested messages and groups
+/// indented in braces:
+///
+/// ```text
+/// 1 {
+///   1: "Alice"
+///   2: 7
+/// }
+/// ```
+///
+/// Varints are written as unsigned integers, and fixed-width values in hexadecimal. A
+/// length-delimited value is written as a nested message if it decodes as one, otherwise as a
+/// string if it is valid UTF-8, and otherwise as escaped bytes.
+///
+/// The whole message is validated before anything is written. If it is malformed, or has groups
+/// nested more than 100 deep, [`PrintError::Decode`] is returned.
+pub fn decode_raw<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    message: Message<'_>,
+) -> Result<(), PrintError> {
+    let mut message = message;
+    message.options = message.options.groups(true);
+    let limits = &mut message.options.limits;
+    limits.max_depth = limits.max_depth.min(MAX_DEPTH);
+    message.validate()?;
+    write_message(out, message, 0)
+}
+
+// Length-delimited values nested `MAX_DEPTH` deep are not printed as messages.
+fn is_message(message: &Message<'_>) -> bool {
+    !message.buf.is_empty() && message.depth < MAX_DEPTH && message.validate().is_ok()
+}
+
+fn write_message<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    message: Message<'_>,
+    depth: usize,
+) -> Result<(), PrintError> {
+    for field in message.fields() {
+        write_field(out, &field?, depth)?;
+    }
+    Ok(())
+}
+
+// Writes a field of a message decoded with groups, indented to `depth`.
+pub(super) fn write_field<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    field: &Field<'_>,
+    depth: usize,
+) -> Result<(), PrintError> {
+    write_indent(out, depth)?;
+    match field.value {
+        FieldValue::Varint(v) => writeln!(out, "{}: {}", field.number, v.get_uint64())?,
+        FieldValue::Fixed64(v) => writeln!(out, "{}: 0x{:016x}", field.number, v.get_fixed64())?,
+        FieldValue::Fixed32(v) => writeln!(out, "{}: 0x{:08x}", field.number, v.get_fixed32())?,
+        FieldValue::Group(group) => {
+            writeln!(out, "{} {{", field.number)?;
+            write_message(out, group, depth + 1)?;
+            write_indent(out, depth)?;
+            out.write_str("}\n")?;
+        }
+        FieldValue::LengthDelimited(v) => {
+            let nested = v.get_message();
+            if is_message(&nested) {
+                writeln!(out, "{} {{", field.number)?;
+                write_message(out, nested, depth + 1)?;
+                write_indent(out, depth)?;
+                out.write_str("}\n")?;
+            } else {
+                let bytes = v.get_bytes();
+                write!(out, "{}: \"", field.number)?;
+                write_escaped(out, bytes, core::str::from_utf8(bytes).is_ok())?;
+                out.write_str("\"\n")?;
+            }
+        }
+        // Groups are decoded as a whole.
+        FieldValue::StartGroup(_) | FieldValue::EndGroup(_) => unreachable!(),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_raw;
+    use crate::message::{DecodeLimits, DecodeOptions, Message};
+    use crate::text::PrintError;
+    use crate::{Error, ErrorKind};
+    use core::fmt::{self, Write};
+
+    // A writer into a fixed buffer.
+    struct Buffer {
+        buf: [u8; 256],
+        len: usize,
+    }
+
+    impl Write for Buffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.buf
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    fn print(buf: &[u8]) -> Result<Buffer, PrintError> {
+        let mut out = Buffer {
+            buf: [0; 256],
+            len: 0,
+        };
+        decode_raw(&mut out, Message::new(buf))?;
+        Ok(out)
+    }
+
+    fn assert_prints(buf: &[u8], expected: &str) {
+        let out = print(buf).unwrap();
+        assert_eq!(core::str::from_utf8(&out.buf[..out.len]).unwrap(), expected);
+    }
+
+    #[test]
+    fn decode_raw_fields() {
+        assert_prints(
+            b"\x08\x96\x01\x11\x01\x00\x00\x00\x00\x00\xf0\x3f\x1d\x00\x00\x80\x3f",
+            "1: 150\n2: 0x3ff0000000000001\n3: 0x3f800000\n",
+        );
+        assert_prints(
+            b"\x0a\x09\x0a\x05Alice\x10\x07\x13\x18\x01\x14",
+            "1 {\n  1: \"Alice\"\n  2: 7\n}\n2 {\n  3: 1\n}\n",
+        );
+        // Strings that do not decode as messages, bytes and empty values.
+        assert_prints(
+            b"\x0a\x03\xc3\xa9\n\x12\x03\x00\xff\"\x1a\x00",
+            "1: \"\u{e9}\\n\"\n2: \"\\000\\377\\\"\"\n3: \"\"\n",
+        );
+    }
+
+    #[test]
+    fn decode_raw_errors() {
+        assert_eq!(
+            print(b"\x08\x01\x0b").err(),
+            Some(PrintError::Decode(Error::new(
+                ErrorKind::UnterminatedGroup,
+                2
+            )))
+        );
+        // Groups nested too deeply are an error, whatever the limits of the message.
+        let mut buf = [0x0b; 40_000];
+        buf[20_000..].fill(0x0c);
+        let limits = DecodeLimits::new().max_depth(u32::MAX);
+        let message = Message::with_options(&buf, DecodeOptions::new().limits(limits));
+        let mut out = Buffer {
+            buf: [0; 256],
+            len: 0,
+        };
+        assert_eq!(
+            decode_raw(&mut out, message),
+            Err(PrintError::Decode(Error::new(
+                ErrorKind::DepthLimitExceeded,
+                101
+            )))
+        );
+        let mut out = Buffer {
+            buf: [0; 256],
+            len: 253,
+        };
+        assert_eq!(
+            decode_raw(&mut out, Message::new(b"\x08\x01")),
+            Err(PrintError::Fmt)
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::field::{
+    FieldValue, PackedBool, PackedDouble, PackedEnum, PackedFixed32, PackedFixed64, PackedFloat,
+    PackedInt32, PackedInt64, PackedSfixed32, PackedSfixed64, PackedSint32, PackedSint64,
+    PackedUint32, PackedUint64, Repeated,
+};
+use crate::message::Message;
+use crate::Error;
+
+/// A protobuf field type that can be read from a [`FieldValue`].
+///
+/// This trait is implemented by the marker types in this module, one for each protobuf field
+/// type. It is used by [`FieldValue::get`] to read a value generically, for example
+/// `value.get::<Sint64>()`.
+pub trait ProtoScalar {
+    /// The Rust type of a value.
+    type Value<'a>;
+
+    /// Returns the value of a field of this type.
+    ///
+    /// If the wire type is not compatible, `Err` is returned.
+    fn get<'a>(value: &FieldValue<'a>) -> Result<Self::Value<'a>, Error>;
+}
+
+/// A protobuf field type that can be packed.
+///
+/// This trait is implemented by the marker types of the numeric types. It is used by
+/// [`FieldValue::get_repeated`] to read a repeated field generically.
+pub trait PackedScalar: ProtoScalar {
+    /// The iterator over values of a packed field of this type.
+    type Packed<'a>: Iterator<Item = Result<Self::Value<'a>, Error>>;
+
+    /// Returns an iterator over values of a repeated field of this type.
+    ///
+    /// If the wire type is not compatible, `Err` is returned.
+    fn get_repeated<'a>(
+        value: &FieldValue<'a>,
+    ) -> Result<Repeated<Self::Value<'a>, Self::Packed<'a>>, Error>;
+}
+
+macro_rules! impl_scalar {
+    ($(#[$meta:meta])* $name:ident, $ty:ty, $get_fn:ident, $get_repeated_fn:ident, $packed:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug)]
+        pub enum $name {}
+
+        impl ProtoScalar for $name {
+            type Value<'a> = $ty;
+
+            #[inline]
+            fn get(value: &FieldValue<'_>) -> Result<$ty, Error> {
+                value.$get_fn()
+            }
+        }
+
+        impl PackedScalar for $name {
+            type Packed<'a> = $packed<'a>;
+
+            #[inline]
+            fn get_repeated<'a>(
+                value: &FieldValue<'a>,
+            ) -> Result<Repeated<$ty, $packed<'a>>, Error> {
+                value.$get_repeated_fn()
+            }
+        }
+    };
+}
+
+impl_scalar!(
+    /// The protobuf `bool` type.
+    Bool,
+    bool,
+    get_bool,
+    get_repeated_bool,
+    PackedBool
+);
+impl_scalar!(
+    /// A protobuf `enum` type.
+    Enum,
+    i32,
+    get_enum,
+    get_repeated_enum,
+    PackedEnum
+);
+impl_scalar!(
+    /// The protobuf `int32` type.
+    Int32,
+    i32,
+    get_int32,
+    get_repeated_int32,
+    PackedInt32
+);
+impl_scalar!(
+    /// The protobuf `int64` type.
+    Int64,
+    i64,
+    get_int64,
+    get_repeated_int64,
+    PackedInt64
+);
+impl_scalar!(
+    /// The protobuf `sint32` type.
+    Sint32,
+    i32,
+    get_sint32,
+    get_repeated_sint32,
+    PackedSint32
+);
+impl_scalar!(
+    /// The protobuf `sint64` type.
+    Sint64,
+    i64,
+    get_sint64,
+    get_repeated_sint64,
+    PackedSint64
+);
+impl_scalar!(
+    /// The protobuf `uint32` type.
+    Uint32,
+    u32,
+    get_uint32,
+    get_repeated_uint32,
+    PackedUint32
+);
+impl_scalar!(
+    /// The protobuf `uint64` type.
+    Uint64,
+    u64,
+    get_uint64,
+    get_repeated_uint64,
+    PackedUint64
+);
+impl_scalar!(
+    /// The protobuf `fixed64` type.
+    Fixed64,
+    u64,
+    get_fixed64,
+    get_repeated_fixed64,
+    PackedFixed64
+);
+impl_scalar!(
+    /// The protobuf `sfixed64` type.
+    Sfixed64,
+    i64,
+    get_sfixed64,
+    get_repeated_sfixed64,
+    PackedSfixed64
+);
+impl_scalar!(
+    /// The protobuf `double` type.
+    Double,
+    f64,
+    get_double,
+    get_repeated_double,
+    PackedDouble
+);
+impl_scalar!(
+    /// The protobuf `fixed32` type.
+    Fixed32,
+    u32,
+    get_fixed32,
+    get_repeated_fixed32,
+    PackedFixed32
+);
+impl_scalar!(
+    /// The protobuf `sfixed32` type.
+    Sfixed32,
+    i32,
+    get_sfixed32,
+    get_repeated_sfixed32,
+    PackedSfixed32
+);
+impl_scalar!(
+    /// The protobuf `float` type.
+    Float,
+    f32,
+    get_float,
+    get_repeated_float,
+    PackedFloat
+);
+
+macro_rules! impl_type {
+    ($(#[$meta:meta])* $name:ident, $ty:ty, $get_fn:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug)]
+        pub enum $name {}
+
+        impl ProtoScalar for $name {
+            type Value<'a> = $ty;
+
+            #[inline]
+            fn get<'a>(value: &FieldValue<'a>) -> Result<$ty, Error> {
+                value.$get_fn()
+            }
+        }
+    };
+}
+
+impl_type!(
+    /// The protobuf `string` type.
+    Str,
+    &'a str,
+    get_string
+);
+impl_type!(
+    /// The protobuf `bytes` type.
+    Bytes,
+    &'a [u8],
+    get_bytes
+);
+impl_type!(
+    /// A protobuf message type.
+    Msg,
+    Message<'a>,
+    get_message
+);
+impl_type!(
+    /// A protobuf group.
+    Group,
+    Message<'a>,
+    get_group
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{Bool, Bytes, Fixed32, Int32, Msg, ProtoScalar, Sint64, Str};
+    use crate::message::Message;
+    use crate::Error;
+
+    fn last<T: ProtoScalar>(
+        message: &Message<'static>,
+        number: u64,
+    ) -> Result<T::Value<'static>, Error> {
+        T::get(&message.get_last(number)?.unwrap())
+    }
+
+    #[test]
+    fn types() {
+        // 1: 1, 2: 3, 3: "ab", 4: { 1: 1 }, 5: [1, 2] (packed fixed32), 5: 3 (fixed32)
+        let buf = b"\x08\x01\x10\x03\x1a\x02ab\x22\x02\x08\x01\
+            \x2a\x08\x01\x00\x00\x00\x02\x00\x00\x00\x2d\x03\x00\x00\x00";
+        let message = Message::new(buf);
+        assert_eq!(last::<Bool>(&message, 1), Ok(true));
+        assert_eq!(last::<Int32>(&message, 2), Ok(3));
+        assert_eq!(last::<Sint64>(&message, 2), Ok(-2));
+        assert_eq!(last::<Str>(&message, 3), Ok("ab"));
+        assert_eq!(last::<Bytes>(&message, 3), Ok(&b"ab"[..]));
+        let nested = last::<Msg>(&message, 4).unwrap();
+        assert_eq!(last::<Bool>(&nested, 1), Ok(true));
+        assert!(last::<Fixed32>(&message, 1).is_err());
+
+        let values = message
+            .find_all(5)
+            .flat_map(|value| value.unwrap().get_repeated::<Fixed32>().unwrap())
+            .map(Result::unwrap);
+        assert!(values.eq([1, 2, 3]));
+        let value = message.get(1).unwrap().unwrap();
+        assert!(value.get_repeated::<Int32>().unwrap().eq([Ok(1)]));
+    }
+}
@@ -0,0 +1,1232 @@
+use crate::encoding::{encode_varint, varint_len, zigzag, VARINT_MAX_LEN};
+#[cfg(feature = "alloc")]
+use crate::encoding::{WIRE_TYPE_END_GROUP, WIRE_TYPE_START_GROUP};
+use crate::encoding::{
+    WIRE_TYPE_FIXED32, WIRE_TYPE_FIXED64, WIRE_TYPE_LENGTH_DELIMITED, WIRE_TYPE_VARINT,
+};
+use crate::message::{Message, FIELD_NUMBER_MAX};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+
+#[inline]
+fn tag(number: u64, wire_type: u8) -> u64 {
+    assert!(
+        (1..=FIELD_NUMBER_MAX).contains(&number),
+        "invalid field number {}",
+        number
+    );
+    (number << 3) | wire_type as u64
+}
+
+/// The error type returned when a [`SliceWriter`] runs out of space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OutOfSpace;
+
+impl fmt::Display for OutOfSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("out of space")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutOfSpace {}
+
+// Up to two varints preceding the payload of a field: the tag and either the value or the length.
+struct Header {
+    bytes: [u8; 2 * VARINT_MAX_LEN],
+    len: usize,
+}
+
+impl Header {
+    #[inline]
+    fn new(number: u64, wire_type: u8) -> Self {
+        let mut header = Self {
+            bytes: [0; 2 * VARINT_MAX_LEN],
+            len: 0,
+        };
+        header.push_varint(tag(number, wire_type));
+        header
+    }
+
+    #[inline]
+    fn push_varint(&mut self, value: u64) {
+        let mut bytes = [0; VARINT_MAX_LEN];
+        let len = encode_varint(value, &mut bytes);
+        self.bytes[self.len..self.len + len].copy_from_slice(&bytes[..len]);
+        self.len += len;
+    }
+
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+// Generates the typed `add_*` methods of a writer. Each writer provides the primitives
+// `write_varint_field`, `write_fixed64_field`, `write_fixed32_field`,
+// `write_length_delimited_field`, `write_packed_varint_field` and `write_packed_fixed_field`
+// returning `$ret`.
+macro_rules! impl_add {
+    ($ret:ty) => {
+        /// Writes a `bool` field.
+        #[inline]
+        pub fn add_bool(&mut self, number: u64, value: bool) -> $ret {
+            self.write_varint_field(number, value as u64)
+        }
+
+        /// Writes an `enum` field.
+        #[inline]
+        pub fn add_enum(&mut self, number: u64, value: i32) -> $ret {
+            self.write_varint_field(number, value as i64 as u64)
+        }
+
+        /// Writes an `int32` field.
+        ///
+        /// Negative values are sign-extended and always take ten bytes.
+        #[inline]
+        pub fn add_int32(&mut self, number: u64, value: i32) -> $ret {
+            self.write_varint_field(number, value as i64 as u64)
+        }
+
+        /// Writes an `int64` field.
+        #[inline]
+        pub fn add_int64(&mut self, number: u64, value: i64) -> $ret {
+            self.write_varint_field(number, value as u64)
+        }
+
+        /// Writes a `sint32` field.
+        #[inline]
+        pub fn add_sint32(&mut self, number: u64, value: i32) -> $ret {
+            self.write_varint_field(number, zigzag::encode_32(value) as u64)
+        }
+
+        /// Writes a `sint64` field.
+        #[inline]
+        pub fn add_sint64(&mut self, number: u64, value: i64) -> $ret {
+            self.write_varint_field(number, zigzag::encode_64(value))
+        }
+
+        /// Writes a `uint32` field.
+        #[inline]
+        pub fn add_uint32(&mut self, number: u64, value: u32) -> $ret {
+            self.write_varint_field(number, value as u64)
+        }
+
+        /// Writes a `uint64` field.
+        #[inline]
+        pub fn add_uint64(&mut self, number: u64, value: u64) -> $ret {
+            self.write_varint_field(number, value)
+        }
+
+        /// Writes a `fixed64` field.
+        #[inline]
+        pub fn add_fixed64(&mut self, number: u64, value: u64) -> $ret {
+            self.write_fixed64_field(number, value.to_le_bytes())
+        }
+
+        /// Writes a `sfixed64` field.
+        #[inline]
+        pub fn add_sfixed64(&mut self, number: u64, value: i64) -> $ret {
+            self.write_fixed64_field(number, value.to_le_bytes())
+        }
+
+        /// Writes a `double` field.
+        #[inline]
+        pub fn add_double(&mut self, number: u64, value: f64) -> $ret {
+            self.write_fixed64_field(number, value.to_le_bytes())
+        }
+
+        /// Writes a `bytes` field.
+        #[inline]
+        pub fn add_bytes(&mut self, number: u64, value: &[u8]) -> $ret {
+            self.write_length_delimited_field(number, value)
+        }
+
+        /// Writes a `message` field.
+        #[inline]
+        pub fn add_message(&mut self, number: u64, value: Message<'_>) -> $ret {
+            self.write_length_delimited_field(number, value.buf)
+        }
+
+        /// Writes a `string` field.
+        #[inline]
+        pub fn add_string(&mut self, number: u64, value: &str) -> $ret {
+            self.write_length_delimited_field(number, value.as_bytes())
+        }
+
+        /// Writes a `fixed32` field.
+        #[inline]
+        pub fn add_fixed32(&mut self, number: u64, value: u32) -> $ret {
+            self.write_fixed32_field(number, value.to_le_bytes())
+        }
+
+        /// Writes a `sfixed32` field.
+        #[inline]
+        pub fn add_sfixed32(&mut self, number: u64, value: i32) -> $ret {
+            self.write_fixed32_field(number, value.to_le_bytes())
+        }
+
+        /// Writes a `float` field.
+        #[inline]
+        pub fn add_float(&mut self, number: u64, value: f32) -> $ret {
+            self.write_fixed32_field(number, value.to_le_bytes())
+        }
+
+        /// Writes a `bool` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_bool<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<bool>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(number, values.into_iter().map(|v| *v.borrow() as u64))
+        }
+
+        /// Writes an `enum` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_enum<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<i32>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(
+                number,
+                values.into_iter().map(|v| *v.borrow() as i64 as u64),
+            )
+        }
+
+        /// Writes an `int32` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_int32<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<i32>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(
+                number,
+                values.into_iter().map(|v| *v.borrow() as i64 as u64),
+            )
+        }
+
+        /// Writes an `int64` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_int64<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<i64>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(number, values.into_iter().map(|v| *v.borrow() as u64))
+        }
+
+        /// Writes a `sint32` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_sint32<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<i32>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(
+                number,
+                values
+                    .into_iter()
+                    .map(|v| zigzag::encode_32(*v.borrow()) as u64),
+            )
+        }
+
+        /// Writes a `sint64` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_sint64<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<i64>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(
+                number,
+                values.into_iter().map(|v| zigzag::encode_64(*v.borrow())),
+            )
+        }
+
+        /// Writes a `uint32` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_uint32<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<u32>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(number, values.into_iter().map(|v| *v.borrow() as u64))
+        }
+
+        /// Writes a `uint64` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_uint64<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<u64>,
+            I::IntoIter: Clone,
+        {
+            self.write_packed_varint_field(number, values.into_iter().map(|v| *v.borrow()))
+        }
+
+        /// Writes a `fixed64` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_fixed64<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<u64>,
+            I::IntoIter: ExactSizeIterator,
+        {
+            self.write_packed_fixed_field(
+                number,
+                values.into_iter().map(|v| v.borrow().to_le_bytes()),
+            )
+        }
+
+        /// Writes a `sfixed64` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_sfixed64<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<i64>,
+            I::IntoIter: ExactSizeIterator,
+        {
+            self.write_packed_fixed_field(
+                number,
+                values.into_iter().map(|v| v.borrow().to_le_bytes()),
+            )
+        }
+
+        /// Writes a `double` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_double<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<f64>,
+            I::IntoIter: ExactSizeIterator,
+        {
+            self.write_packed_fixed_field(
+                number,
+                values.into_iter().map(|v| v.borrow().to_le_bytes()),
+            )
+        }
+
+        /// Writes a `fixed32` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_fixed32<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<u32>,
+            I::IntoIter: ExactSizeIterator,
+        {
+            self.write_packed_fixed_field(
+                number,
+                values.into_iter().map(|v| v.borrow().to_le_bytes()),
+            )
+        }
+
+        /// Writes a `sfixed32` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_sfixed32<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<i32>,
+            I::IntoIter: ExactSizeIterator,
+        {
+            self.write_packed_fixed_field(
+                number,
+                values.into_iter().map(|v| v.borrow().to_le_bytes()),
+            )
+        }
+
+        /// Writes a `float` field as a packed repeated field.
+        ///
+        /// Nothing is written if `values` is empty.
+        #[inline]
+        pub fn add_packed_float<I>(&mut self, number: u64, values: I) -> $ret
+        where
+            I: IntoIterator,
+            I::Item: Borrow<f32>,
+            I::IntoIter: ExactSizeIterator,
+        {
+            self.write_packed_fixed_field(
+                number,
+                values.into_iter().map(|v| v.borrow().to_le_bytes()),
+            )
+        }
+    };
+}
+
+/// A growable protobuf message writer.
+///
+/// Fields are appended to an internal buffer in the order they are added. Each `add_*` method
+/// is the inverse of the `get_*` method of the same name on
+/// [`FieldValue`][crate::field::FieldValue].
+///
+/// Field numbers must be in the range `1..=`[`FIELD_NUMBER_MAX`]. The `add_*` and `open_*`
+/// methods panic on other numbers.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default)]
+pub struct MessageWriter {
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl MessageWriter {
+    /// Creates a new, empty [`MessageWriter`].
+    #[inline]
+    pub fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    /// Creates a new, empty [`MessageWriter`] with at least the specified capacity.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the encoded message.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Consumes the writer, returning the encoded message.
+    #[inline]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Returns the length of the encoded message in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns `true` if no fields have been written.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    impl_add!(());
+
+    /// Starts a nested `message` field.
+    ///
+    /// Fields of the nested message are written to the returned [`NestedMessageWriter`]. The
+    /// length of the field is filled in when the nested writer is
+    /// [closed][NestedMessageWriter::close]. If the nested writer is dropped without being
+    /// closed, the field is discarded.
+    #[inline]
+    pub fn open_message(&mut self, number: u64) -> NestedMessageWriter<'_> {
+        let field_start = self.buf.len();
+        self.put_varint(tag(number, WIRE_TYPE_LENGTH_DELIMITED));
+        // Reserve one byte for the length. It is widened on close if necessary.
+        self.buf.push(0);
+        let start = self.buf.len();
+        NestedMessageWriter {
+            writer: self,
+            field_start,
+            start,
+            closed: false,
+        }
+    }
+
+    // Writes a group field with the given encoded fields.
+    pub(crate) fn add_group(&mut self, number: u64, fields: &[u8]) {
+        self.put_varint(tag(number, WIRE_TYPE_START_GROUP));
+        self.buf.extend_from_slice(fields);
+        self.put_varint(tag(number, WIRE_TYPE_END_GROUP));
+    }
+
+    #[inline]
+    fn put_varint(&mut self, value: u64) {
+        let mut bytes = [0; VARINT_MAX_LEN];
+        let len = encode_varint(value, &mut bytes);
+        self.buf.extend_from_slice(&bytes[..len]);
+    }
+
+    #[inline]
+    fn write_varint_field(&mut self, number: u64, value: u64) {
+        self.put_varint(tag(number, WIRE_TYPE_VARINT));
+        self.put_varint(value);
+    }
+
+    #[inline]
+    fn write_fixed64_field(&mut self, number: u64, bytes: [u8; 8]) {
+        self.put_varint(tag(number, WIRE_TYPE_FIXED64));
+        self.buf.extend_from_slice(&bytes);
+    }
+
+    #[inline]
+    fn write_fixed32_field(&mut self, number: u64, bytes: [u8; 4]) {
+        self.put_varint(tag(number, WIRE_TYPE_FIXED32));
+        self.buf.extend_from_slice(&bytes);
+    }
+
+    #[inline]
+    fn write_length_delimited_field(&mut self, number: u64, bytes: &[u8]) {
+        self.put_varint(tag(number, WIRE_TYPE_LENGTH_DELIMITED));
+        self.put_varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn write_packed_varint_field<I>(&mut self, number: u64, values: I)
+    where
+        I: Iterator<Item = u64> + Clone,
+    {
+        let len: usize = values.clone().map(varint_len).sum();
+        if len == 0 {
+            return;
+        }
+        self.put_varint(tag(number, WIRE_TYPE_LENGTH_DELIMITED));
+        self.put_varint(len as u64);
+        self.buf.reserve(len);
+        for value in values {
+            self.put_varint(value);
+        }
+    }
+
+    fn write_packed_fixed_field<const N: usize, I>(&mut self, number: u64, values: I)
+    where
+        I: ExactSizeIterator<Item = [u8; N]>,
+    {
+        let len = values.len() * N;
+        if len == 0 {
+            return;
+        }
+        self.put_varint(tag(number, WIRE_TYPE_LENGTH_DELIMITED));
+        self.put_varint(len as u64);
+        self.buf.reserve(len);
+        for bytes in values {
+            self.buf.extend_from_slice(&bytes);
+        }
+    }
+}
+
+/// A writer for a nested message field of a [`MessageWriter`].
+///
+/// This struct is returned from the [`open_message`][MessageWriter::open_message] method of
+/// [`MessageWriter`] and [`NestedMessageWriter`].
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct NestedMessageWriter<'w> {
+    writer: &'w mut MessageWriter,
+    field_start: usize,
+    start: usize,
+    closed: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl NestedMessageWriter<'_> {
+    impl_add!(());
+
+    /// Starts a nested `message` field within this message.
+    #[inline]
+    pub fn open_message(&mut self, number: u64) -> NestedMessageWriter<'_> {
+        self.writer.open_message(number)
+    }
+
+    /// Finishes the nested message, filling in its length.
+    pub fn close(mut self) {
+        let buf = &mut self.writer.buf;
+        let mut bytes = [0; VARINT_MAX_LEN];
+        let len = encode_varint((buf.len() - self.start) as u64, &mut bytes);
+        buf.splice(self.start - 1..self.start, bytes[..len].iter().copied());
+        self.closed = true;
+    }
+
+    /// Discards the nested message, including its tag.
+    #[inline]
+    pub fn rollback(self) {}
+
+    #[inline]
+    fn write_varint_field(&mut self, number: u64, value: u64) {
+        self.writer.write_varint_field(number, value)
+    }
+
+    #[inline]
+    fn write_fixed64_field(&mut self, number: u64, bytes: [u8; 8]) {
+        self.writer.write_fixed64_field(number, bytes)
+    }
+
+    #[inline]
+    fn write_fixed32_field(&mut self, number: u64, bytes: [u8; 4]) {
+        self.writer.write_fixed32_field(number, bytes)
+    }
+
+    #[inline]
+    fn write_length_delimited_field(&mut self, number: u64, bytes: &[u8]) {
+        self.writer.write_length_delimited_field(number, bytes)
+    }
+
+    #[inline]
+    fn write_packed_varint_field<I>(&mut self, number: u64, values: I)
+    where
+        I: Iterator<Item = u64> + Clone,
+    {
+        self.writer.write_packed_varint_field(number, values)
+    }
+
+    #[inline]
+    fn write_packed_fixed_field<const N: usize, I>(&mut self, number: u64, values: I)
+    where
+        I: ExactSizeIterator<Item = [u8; N]>,
+    {
+        self.writer.write_packed_fixed_field(number, values)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Drop for NestedMessageWriter<'_> {
+    fn drop(&mut self) {
+        if !self.closed {
+            self.writer.buf.truncate(self.field_start);
+        }
+    }
+}
+
+/// A protobuf message writer backed by a caller-provided buffer.
+///
+/// `SliceWriter` never allocates. If a field does not fit in the remaining space,
+/// [`OutOfSpace`] is returned and nothing is written. Each `add_*` method is the inverse of the
+/// `get_*` method of the same name on [`FieldValue`][crate::field::FieldValue].
+///
+/// Field numbers must be in the range `1..=`[`FIELD_NUMBER_MAX`]. The `add_*` and `open_*`
+/// methods panic on other numbers.
+///
+/// A writer created with [`sizer`][SliceWriter::sizer] counts bytes without storing them. Running
+/// the same encoding routine against a sizer and then against a buffer of exactly
+/// [`len`][SliceWriter::len] bytes allows two-pass serialization.
+#[derive(Debug)]
+pub struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+    sizer: bool,
+}
+
+impl<'a> SliceWriter<'a> {
+    /// Creates a new [`SliceWriter`] writing to the start of `buf`.
+    #[inline]
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            sizer: false,
+        }
+    }
+
+    /// Creates a new [`SliceWriter`] that only counts the bytes written.
+    ///
+    /// A sizer never runs out of space. Its [`as_bytes`][SliceWriter::as_bytes] is always empty.
+    #[inline]
+    pub fn sizer() -> SliceWriter<'static> {
+        SliceWriter {
+            buf: &mut [],
+            pos: 0,
+            sizer: true,
+        }
+    }
+
+    /// Returns `true` if the writer only counts bytes.
+    #[inline]
+    pub fn is_sizer(&self) -> bool {
+        self.sizer
+    }
+
+    /// Returns the bytes written so far.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.sizer {
+            return &[];
+        }
+        &self.buf[..self.pos]
+    }
+
+    /// Consumes the writer, returning the bytes written.
+    #[inline]
+    pub fn into_bytes(self) -> &'a [u8] {
+        if self.sizer {
+            return &[];
+        }
+        &self.buf[..self.pos]
+    }
+
+    /// Returns the number of bytes written.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns `true` if no fields have been written.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pos == 0
+    }
+
+    /// Returns the number of bytes that can still be written.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        if self.sizer {
+            return usize::MAX - self.pos;
+        }
+        self.buf.len() - self.pos
+    }
+
+    impl_add!(Result<(), OutOfSpace>);
+
+    /// Starts a nested `message` field.
+    ///
+    /// Fields of the nested message are written to the returned [`NestedSliceWriter`]. The
+    /// length of the field is filled in when the nested writer is
+    /// [closed][NestedSliceWriter::close]. If the nested writer is dropped without being closed,
+    /// the field is discarded.
+    #[inline]
+    pub fn open_message(&mut self, number: u64) -> Result<NestedSliceWriter<'_, 'a>, OutOfSpace> {
+        let field_start = self.pos;
+        let mut header = Header::new(number, WIRE_TYPE_LENGTH_DELIMITED);
+        // Reserve one byte for the length. It is widened on close if necessary.
+        header.push_varint(0);
+        self.put(&header, &[])?;
+        let start = self.pos;
+        Ok(NestedSliceWriter {
+            writer: self,
+            field_start,
+            start,
+            closed: false,
+        })
+    }
+
+    #[inline]
+    fn put(&mut self, header: &Header, payload: &[u8]) -> Result<(), OutOfSpace> {
+        let header = header.as_bytes();
+        if self.remaining() < header.len() + payload.len() {
+            return Err(OutOfSpace);
+        }
+        self.put_slice(header);
+        self.put_slice(payload);
+        Ok(())
+    }
+
+    // The caller must have checked that there is enough space.
+    #[inline]
+    fn put_slice(&mut self, bytes: &[u8]) {
+        if !self.sizer {
+            self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        }
+        self.pos += bytes.len();
+    }
+
+    #[inline]
+    fn write_varint_field(&mut self, number: u64, value: u64) -> Result<(), OutOfSpace> {
+        let mut header = Header::new(number, WIRE_TYPE_VARINT);
+        header.push_varint(value);
+        self.put(&header, &[])
+    }
+
+    #[inline]
+    fn write_fixed64_field(&mut self, number: u64, bytes: [u8; 8]) -> Result<(), OutOfSpace> {
+        self.put(&Header::new(number, WIRE_TYPE_FIXED64), &bytes)
+    }
+
+    #[inline]
+    fn write_fixed32_field(&mut self, number: u64, bytes: [u8; 4]) -> Result<(), OutOfSpace> {
+        self.put(&Header::new(number, WIRE_TYPE_FIXED32), &bytes)
+    }
+
+    #[inline]
+    fn write_length_delimited_field(
+        &mut self,
+        number: u64,
+        bytes: &[u8],
+    ) -> Result<(), OutOfSpace> {
+        let mut header = Header::new(number, WIRE_TYPE_LENGTH_DELIMITED);
+        header.push_varint(bytes.len() as u64);
+        self.put(&header, bytes)
+    }
+
+    fn write_packed_varint_field<I>(&mut self, number: u64, values: I) -> Result<(), OutOfSpace>
+    where
+        I: Iterator<Item = u64> + Clone,
+    {
+        let len: usize = values.clone().map(varint_len).sum();
+        if len == 0 {
+            return Ok(());
+        }
+        let mut header = Header::new(number, WIRE_TYPE_LENGTH_DELIMITED);
+        header.push_varint(len as u64);
+        if self.remaining() < header.as_bytes().len() + len {
+            return Err(OutOfSpace);
+        }
+        self.put(&header, &[])?;
+        for value in values {
+            let mut bytes = [0; VARINT_MAX_LEN];
+            let len = encode_varint(value, &mut bytes);
+            self.put_slice(&bytes[..len]);
+        }
+        Ok(())
+    }
+
+    fn write_packed_fixed_field<const N: usize, I>(
+        &mut self,
+        number: u64,
+        values: I,
+    ) -> Result<(), OutOfSpace>
+    where
+        I: ExactSizeIterator<Item = [u8; N]>,
+    {
+        let len = values.len() * N;
+        if len == 0 {
+            return Ok(());
+        }
+        let mut header = Header::new(number, WIRE_TYPE_LENGTH_DELIMITED);
+        header.push_varint(len as u64);
+        if self.remaining() < header.as_bytes().len() + len {
+            return Err(OutOfSpace);
+        }
+        self.put(&header, &[])?;
+        for bytes in values {
+            self.put_slice(&bytes);
+        }
+        Ok(())
+    }
+}
+
+/// A writer for a nested message field of a [`SliceWriter`].
+///
+/// This struct is returned from the [`open_message`][SliceWriter::open_message] method of
+/// [`SliceWriter`] and [`NestedSliceWriter`].
+#[derive(Debug)]
+pub struct NestedSliceWriter<'w, 'a> {
+    writer: &'w mut SliceWriter<'a>,
+    field_start: usize,
+    start: usize,
+    closed: bool,
+}
+
+impl<'a> NestedSliceWriter<'_, 'a> {
+    impl_add!(Result<(), OutOfSpace>);
+
+    /// Starts a nested `message` field within this message.
+    #[inline]
+    pub fn open_message(&mut self, number: u64) -> Result<NestedSliceWriter<'_, 'a>, OutOfSpace> {
+        self.writer.open_message(number)
+    }
+
+    /// Finishes the nested message, filling in its length.
+    ///
+    /// If there is not enough space to widen the length, the nested message is discarded and
+    /// [`OutOfSpace`] is returned.
+    pub fn close(mut self) -> Result<(), OutOfSpace> {
+        let writer = &mut *self.writer;
+        let mut bytes = [0; VARINT_MAX_LEN];
+        let len = encode_varint((writer.pos - self.start) as u64, &mut bytes);
+        let extra = len - 1;
+        if writer.remaining() < extra {
+            return Err(OutOfSpace);
+        }
+        if !writer.sizer {
+            writer
+                .buf
+                .copy_within(self.start..writer.pos, self.start + extra);
+            writer.buf[self.start - 1..self.start + extra].copy_from_slice(&bytes[..len]);
+        }
+        writer.pos += extra;
+        self.closed = true;
+        Ok(())
+    }
+
+    /// Discards the nested message, including its tag.
+    #[inline]
+    pub fn rollback(self) {}
+
+    #[inline]
+    fn write_varint_field(&mut self, number: u64, value: u64) -> Result<(), OutOfSpace> {
+        self.writer.write_varint_field(number, value)
+    }
+
+    #[inline]
+    fn write_fixed64_field(&mut self, number: u64, bytes: [u8; 8]) -> Result<(), OutOfSpace> {
+        self.writer.write_fixed64_field(number, bytes)
+    }
+
+    #[inline]
+    fn write_fixed32_field(&mut self, number: u64, bytes: [u8; 4]) -> Result<(), OutOfSpace> {
+        self.writer.write_fixed32_field(number, bytes)
+    }
+
+    #[inline]
+    fn write_length_delimited_field(
+        &mut self,
+        number: u64,
+        bytes: &[u8],
+    ) -> Result<(), OutOfSpace> {
+        self.writer.write_length_delimited_field(number, bytes)
+    }
+
+    #[inline]
+    fn write_packed_varint_field<I>(&mut self, number: u64, values: I) -> Result<(), OutOfSpace>
+    where
+        I: Iterator<Item = u64> + Clone,
+    {
+        self.writer.write_packed_varint_field(number, values)
+    }
+
+    #[inline]
+    fn write_packed_fixed_field<const N: usize, I>(
+        &mut self,
+        number: u64,
+        values: I,
+    ) -> Result<(), OutOfSpace>
+    where
+        I: ExactSizeIterator<Item = [u8; N]>,
+    {
+        self.writer.write_packed_fixed_field(number, values)
+    }
+}
+
+impl Drop for NestedSliceWriter<'_, '_> {
+    fn drop(&mut self) {
+        if !self.closed {
+            self.writer.pos = self.field_start;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OutOfSpace, SliceWriter};
+    use crate::message::{Message, FIELD_NUMBER_MAX};
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn message_writer_round_trip() {
+        use super::MessageWriter;
+
+        let mut writer = MessageWriter::new();
+        writer.add_bool(1, true);
+        writer.add_enum(2, -2);
+        writer.add_int32(3, -137);
+        writer.add_int64(4, i64::MIN);
+        writer.add_sint32(5, -137);
+        writer.add_sint64(6, i64::MIN);
+        writer.add_uint32(7, u32::MAX);
+        writer.add_uint64(8, u64::MAX);
+        writer.add_fixed64(9, 0x0102030405060708);
+        writer.add_sfixed64(10, -9);
+        writer.add_double(11, 1.5);
+        writer.add_bytes(12, b"\x00\xff");
+        writer.add_message(13, Message::new(&b"\x08\x01"[..]));
+        writer.add_string(14, "hello");
+        writer.add_fixed32(15, 0x01020304);
+        writer.add_sfixed32(16, -7);
+        writer.add_float(17, -0.25);
+
+        let mut fields = Message::new(writer.as_bytes()).fields();
+        let mut next = |number| {
+            let field = fields.next().unwrap().unwrap();
+            assert_eq!(field.number, number);
+            field.value
+        };
+        assert_eq!(next(1).get_bool(), Ok(true));
+        assert_eq!(next(2).get_enum(), Ok(-2));
+        assert_eq!(next(3).get_int32(), Ok(-137));
+        assert_eq!(next(4).get_int64(), Ok(i64::MIN));
+        assert_eq!(next(5).get_sint32(), Ok(-137));
+        assert_eq!(next(6).get_sint64(), Ok(i64::MIN));
+        assert_eq!(next(7).get_uint32(), Ok(u32::MAX));
+        assert_eq!(next(8).get_uint64(), Ok(u64::MAX));
+        assert_eq!(next(9).get_fixed64(), Ok(0x0102030405060708));
+        assert_eq!(next(10).get_sfixed64(), Ok(-9));
+        assert_eq!(next(11).get_double(), Ok(1.5));
+        assert_eq!(next(12).get_bytes(), Ok(&b"\x00\xff"[..]));
+        let message = next(13).get_message().unwrap();
+        assert_eq!(message.buf, b"\x08\x01");
+        assert_eq!(next(14).get_string(), Ok("hello"));
+        assert_eq!(next(15).get_fixed32(), Ok(0x01020304));
+        assert_eq!(next(16).get_sfixed32(), Ok(-7));
+        assert_eq!(next(17).get_float(), Ok(-0.25));
+        assert!(fields.next().is_none());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn message_writer_encoding() {
+        use super::MessageWriter;
+
+        let mut writer = MessageWriter::new();
+        writer.add_int32(1, 150);
+        writer.add_string(2, "testing");
+        assert_eq!(writer.as_bytes(), b"\x08\x96\x01\x12\x07testing");
+
+        let mut writer = MessageWriter::new();
+        writer.add_int32(1, -1);
+        assert_eq!(
+            writer.as_bytes(),
+            b"\x08\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"
+        );
+    }
+
+    #[test]
+    fn slice_writer_round_trip() {
+        let mut buf = [0; 64];
+        let mut writer = SliceWriter::new(&mut buf);
+        writer.add_sint64(1, -3).unwrap();
+        writer.add_fixed32(2, 7).unwrap();
+        writer.add_double(3, 2.5).unwrap();
+        writer.add_string(4, "hi").unwrap();
+        assert_eq!(writer.len(), 2 + 5 + 9 + 4);
+
+        let mut fields = Message::new(writer.into_bytes()).fields();
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!((field.number, field.value.get_sint64()), (1, Ok(-3)));
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!((field.number, field.value.get_fixed32()), (2, Ok(7)));
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!((field.number, field.value.get_double()), (3, Ok(2.5)));
+        let field = fields.next().unwrap().unwrap();
+        assert_eq!((field.number, field.value.get_string()), (4, Ok("hi")));
+        assert!(fields.next().is_none());
+    }
+
+    #[test]
+    fn slice_writer_out_of_space() {
+        let mut buf = [0; 6];
+        let mut writer = SliceWriter::new(&mut buf);
+        writer.add_int32(1, 150).unwrap();
+        assert_eq!(writer.add_string(2, "abc"), Err(OutOfSpace));
+        assert_eq!(writer.add_fixed32(2, 0), Err(OutOfSpace));
+        assert_eq!(writer.as_bytes(), b"\x08\x96\x01");
+        writer.add_bytes(2, b"a").unwrap();
+        assert_eq!(writer.remaining(), 0);
+        assert_eq!(writer.add_bool(3, false), Err(OutOfSpace));
+        assert_eq!(writer.as_bytes(), b"\x08\x96\x01\x12\x01a");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid field number 536870912")]
+    fn slice_writer_invalid_field_number() {
+        let mut buf = [0; 16];
+        let _ = SliceWriter::new(&mut buf).add_bool(FIELD_NUMBER_MAX + 1, true);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn message_writer_nested() {
+        use super::MessageWriter;
+
+        let mut writer = MessageWriter::new();
+        // AddressBook -> Person -> PhoneNumber
+        let mut person = writer.open_message(1);
+        person.add_string(1, "Alice");
+        person.add_int32(2, 7);
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "555-0100");
+        phone.add_enum(2, 1);
+        phone.close();
+        let mut phone = person.open_message(4);
+        phone.add_string(1, "discarded");
+        phone.rollback();
+        person.close();
+        let mut person = writer.open_message(1);
+        person.add_bytes(1, &[0; 200]);
+        person.close();
+        {
+            let mut person = writer.open_message(1);
+            person.add_string(1, "dropped");
+        }
+
+        let mut people = Message::new(writer.as_bytes()).fields();
+        let person = people.next().unwrap().unwrap().value.get_message().unwrap();
+        let mut fields = person.fields();
+        assert_eq!(
+            fields.next().unwrap().unwrap().value.get_string(),
+            Ok("Alice")
+        );
+        assert_eq!(fields.next().unwrap().unwrap().value.get_int32(), Ok(7));
+        let phone = fields.next().unwrap().unwrap();
+        assert_eq!(phone.number, 4);
+        let mut phone = phone.value.get_message().unwrap().fields();
+        assert_eq!(
+            phone.next().unwrap().unwrap().value.get_string(),
+            Ok("555-0100")
+        );
+        assert_eq!(phone.next().unwrap().unwrap().value.get_enum(), Ok(1));
+        assert!(phone.next().is_none());
+        assert!(fields.next().is_none());
+        let person = people.next().unwrap().unwrap().value.get_message().unwrap();
+        let field = person.fields().next().unwrap().unwrap();
+        assert_eq!(field.value.get_bytes(), Ok(&[0; 200][..]));
+        assert!(people.next().is_none());
+    }
+
+    #[test]
+    fn slice_writer_nested() {
+        let mut buf = [0; 16];
+        let mut writer = SliceWriter::new(&mut buf);
+        let mut outer = writer.open_message(1).unwrap();
+        let mut inner = outer.open_message(2).unwrap();
+        inner.add_bool(3, true).unwrap();
+        inner.close().unwrap();
+        outer.close().unwrap();
+        assert_eq!(writer.as_bytes(), b"\x0a\x04\x12\x02\x18\x01");
+
+        let mut nested = writer.open_message(4).unwrap();
+        nested.add_bool(5, true).unwrap();
+        nested.rollback();
+        assert_eq!(writer.len(), 6);
+    }
+
+    #[test]
+    fn slice_writer_nested_widen_length() {
+        let mut buf = [0; 131];
+        let mut writer = SliceWriter::new(&mut buf);
+        let mut nested = writer.open_message(1).unwrap();
+        nested.add_bytes(2, &[7; 127]).unwrap();
+        assert_eq!(nested.close(), Err(OutOfSpace));
+        assert!(writer.is_empty());
+
+        let mut buf = [0; 132];
+        let mut writer = SliceWriter::new(&mut buf);
+        let mut nested = writer.open_message(1).unwrap();
+        nested.add_bytes(2, &[7; 127]).unwrap();
+        nested.close().unwrap();
+        let field = Message::new(writer.as_bytes())
+            .fields()
+            .next()
+            .unwrap()
+            .unwrap();
+        let message = field.value.get_message().unwrap();
+        let field = message.fields().next().unwrap().unwrap();
+        assert_eq!(field.value.get_bytes(), Ok(&[7; 127][..]));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn message_writer_packed() {
+        use super::MessageWriter;
+        use alloc::vec::Vec;
+
+        let mut writer = MessageWriter::new();
+        writer.add_packed_bool(1, [true, false, true]);
+        writer.add_packed_enum(2, [0, -1, 2].iter());
+        writer.add_packed_int32(3, [1, -1, 300].iter());
+        writer.add_packed_int64(4, [i64::MIN, 0]);
+        writer.add_packed_sint32(5, [-1, 1, i32::MIN]);
+        writer.add_packed_sint64(6, [-1, 1, i64::MAX]);
+        writer.add_packed_uint32(7, [0, u32::MAX]);
+        writer.add_packed_uint64(8, [0, u64::MAX]);
+        writer.add_packed_fixed64(9, [1, 2]);
+        writer.add_packed_sfixed64(10, [-1, -2].iter());
+        writer.add_packed_double(11, [0.5, -0.5]);
+        writer.add_packed_fixed32(12, [3, 4]);
+        writer.add_packed_sfixed32(13, [-3, -4]);
+        writer.add_packed_float(14, [1.5, -1.5]);
+        writer.add_packed_int32(15, core::iter::empty::<i32>());
+        writer.add_packed_float(15, [0.0; 0]);
+
+        let mut fields = Message::new(writer.as_bytes()).fields();
+        let mut next = |number| {
+            let field = fields.next().unwrap().unwrap();
+            assert_eq!(field.number, number);
+            field.value
+        };
+        macro_rules! collect {
+            ($number:expr, $get_fn:ident) => {
+                next($number)
+                    .$get_fn()
+                    .unwrap()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap()
+            };
+        }
+        assert_eq!(collect!(1, get_repeated_bool), [true, false, true]);
+        assert_eq!(collect!(2, get_repeated_enum), [0, -1, 2]);
+        assert_eq!(collect!(3, get_repeated_int32), [1, -1, 300]);
+        assert_eq!(collect!(4, get_repeated_int64), [i64::MIN, 0]);
+        assert_eq!(collect!(5, get_repeated_sint32), [-1, 1, i32::MIN]);
+        assert_eq!(collect!(6, get_repeated_sint64), [-1, 1, i64::MAX]);
+        assert_eq!(collect!(7, get_repeated_uint32), [0, u32::MAX]);
+        assert_eq!(collect!(8, get_repeated_uint64), [0, u64::MAX]);
+        assert_eq!(collect!(9, get_repeated_fixed64), [1, 2]);
+        assert_eq!(collect!(10, get_repeated_sfixed64), [-1, -2]);
+        assert_eq!(collect!(11, get_repeated_double), [0.5, -0.5]);
+        assert_eq!(collect!(12, get_repeated_fixed32), [3, 4]);
+        assert_eq!(collect!(13, get_repeated_sfixed32), [-3, -4]);
+        assert_eq!(collect!(14, get_repeated_float), [1.5, -1.5]);
+        assert!(fields.next().is_none());
+    }
+
+    #[test]
+    fn slice_writer_packed() {
+        let mut buf = [0; 8];
+        let mut writer = SliceWriter::new(&mut buf);
+        writer.add_packed_sint32(1, [0, -1, 1, 150].iter()).unwrap();
+        assert_eq!(writer.as_bytes(), b"\x0a\x05\x00\x01\x02\xac\x02");
+        assert_eq!(writer.add_packed_fixed32(2, [1]), Err(OutOfSpace));
+        assert_eq!(writer.len(), 7);
+    }
+
+    #[test]
+    fn slice_writer_sizer() {
+        fn encode(writer: &mut SliceWriter<'_>) -> Result<(), OutOfSpace> {
+            writer.add_string(1, "Alice")?;
+            writer.add_int32(2, -1)?;
+            let mut phone = writer.open_message(4)?;
+            phone.add_bytes(1, &[b'5'; 200])?;
+            phone.add_packed_uint32(3, [1, 300, 70000])?;
+            phone.close()?;
+            writer.add_packed_double(5, [1.0, 2.0])
+        }
+
+        let mut sizer = SliceWriter::sizer();
+        encode(&mut sizer).unwrap();
+        assert!(sizer.as_bytes().is_empty());
+        let len = sizer.len();
+        assert_eq!(len, 7 + 11 + 3 + 203 + 8 + 18);
+
+        let mut buf = [0; 250];
+        let mut writer = SliceWriter::new(&mut buf[..len]);
+        encode(&mut writer).unwrap();
+        assert_eq!(writer.remaining(), 0);
+        assert_eq!(Message::new(writer.as_bytes()).fields().count(), 4);
+
+        let mut writer = SliceWriter::new(&mut buf[..len - 1]);
+        assert_eq!(encode(&mut writer), Err(OutOfSpace));
+    }
+}